@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use crate::{fe::{ast::{AstNode, BinOp, Slot, Statement}, diagnostic::Span}, value::Value};
+
+use super::{Chunk, Op};
+
+/// compiles a program, or gives `None` if it uses
+/// anything that only the tree-walker can run
+pub fn compile(ast: &AstNode) -> Option<Chunk> {
+    let mut compiler = Compiler {
+        chunk: Chunk::default(),
+        interned: HashMap::new()
+    };
+    compiler.expr(ast)?;
+    Some(compiler.chunk)
+}
+
+/// whether running `node` could leave a break underway. calls count,
+/// since a `break` in a function carries on out of it
+fn may_break(node: &AstNode) -> bool {
+    match node {
+        AstNode::Value(_) | AstNode::Ident(..) => false,
+        AstNode::Assign(_, _, value, _) | AstNode::Unary(_, value, _) => may_break(value),
+        AstNode::MultiAssign(_, values) => values.iter().any(may_break),
+        AstNode::Binary(_, a, b, _) => may_break(a) || may_break(b),
+        AstNode::Compare(first, chain, _) => {
+            may_break(first) || chain.iter().any(|(_, rhs)| may_break(rhs))
+        },
+        AstNode::Slice { target, start, end, .. } => {
+            may_break(target) || [start, end].into_iter().flatten().any(|b| may_break(b))
+        },
+        AstNode::If { cond, then, or, .. } => {
+            may_break(cond) || may_break(then) || or.as_ref().is_some_and(|or| may_break(or))
+        },
+        AstNode::Block(stmts, _) => stmts.iter().any(|Statement::Expression(e, _)| may_break(e)),
+        _ => true
+    }
+}
+
+struct Compiler {
+    chunk: Chunk,
+    interned: HashMap<String, u32>
+}
+
+impl Compiler {
+    fn emit(&mut self, op: Op) -> usize {
+        self.emit_at(op, Span::empty())
+    }
+
+    /// notes that the next instruction starts a statement
+    fn statement(&mut self, span: Span) {
+        if !span.is_empty() {
+            self.chunk.statements.insert(self.chunk.code.len(), span);
+        }
+    }
+
+    /// emits an instruction that errors raised by are reported at `span`
+    fn emit_at(&mut self, op: Op, span: Span) -> usize {
+        self.chunk.code.push(op);
+        self.chunk.spans.push(span);
+        self.chunk.code.len() - 1
+    }
+
+    fn here(&self) -> u32 {
+        self.chunk.code.len() as u32
+    }
+
+    /// points the jump at `at` to the next instruction
+    fn patch(&mut self, at: usize) {
+        let to = self.here();
+        match &mut self.chunk.code[at] {
+            Op::Jump(t) | Op::JumpIfFalse(t) | Op::JumpIfTrue(t) | Op::Compare(_, t)
+                | Op::BreakOut(t) | Op::LoopBreak(_, t) | Op::IterNext(_, t)
+                | Op::TryStart(_, t) => *t = to,
+            op => unreachable!("{:?} doesn't jump", op)
+        }
+    }
+
+    fn constant(&mut self, val: Value) -> u32 {
+        self.chunk.consts.push(val);
+        self.chunk.consts.len() as u32 - 1
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        if let Some(i) = self.interned.get(name) {
+            return *i
+        }
+        let i = self.chunk.names.len() as u32;
+        self.chunk.names.push(name.to_string());
+        self.interned.insert(name.to_string(), i);
+        i
+    }
+
+    fn block(&mut self, stmts: &[Statement], scoped: bool) -> Option<()> {
+        if scoped {
+            self.emit(Op::PushScope);
+        }
+        let mut exits = Vec::new();
+        match stmts.split_last() {
+            Some((Statement::Expression(last, last_span), init)) => {
+                for Statement::Expression(e, span) in init {
+                    self.statement(*span);
+                    let check = may_break(e);
+                    match e.as_ref() {
+                        // the value of an assignment is always thrown away
+                        AstNode::Assign(name, slot, value, _) if !check => {
+                            self.assign(name, *slot, value)?;
+                            continue
+                        },
+                        e => self.expr(e)?
+                    }
+                    // a block cut short by a break has the value of
+                    // the statement that started it
+                    if check {
+                        exits.push(self.emit(Op::BreakOut(0)));
+                    }
+                    self.emit(Op::Pop);
+                }
+                self.statement(*last_span);
+                self.expr(last)?;
+            },
+            None => {
+                self.emit(Op::Nil);
+            }
+        }
+        for exit in exits {
+            self.patch(exit);
+        }
+        if scoped {
+            self.emit(Op::PopScope);
+        }
+        Some(())
+    }
+
+    /// evaluates `value` into the variable, leaving nothing on the stack
+    fn assign(&mut self, name: &str, slot: Slot, value: &AstNode) -> Option<()> {
+        match value {
+            // `name += rhs`, which may be able to append in place
+            AstNode::Binary(BinOp::Add, lhs, rhs, span) if matches!(&**lhs, AstNode::Ident(n, _, _) if n == name) => {
+                self.expr(lhs)?;
+                self.expr(rhs)?;
+                let name = self.name(name);
+                self.emit_at(Op::AddSet(name, slot), *span);
+            },
+            value => {
+                self.expr(value)?;
+                let name = self.name(name);
+                self.emit(Op::Set(name, slot));
+            }
+        }
+        Some(())
+    }
+
+    fn expr(&mut self, node: &AstNode) -> Option<()> {
+        match node {
+            AstNode::Call(func, args, span) => {
+                self.expr(func)?;
+                for a in args {
+                    self.expr(a)?;
+                }
+                self.emit_at(Op::Call(args.len() as u32), *span);
+            },
+            AstNode::Value(v) => match v.as_ref() {
+                Value::Nil => {
+                    self.emit(Op::Nil);
+                },
+                v => {
+                    let i = self.constant(v.clone());
+                    self.emit(Op::Const(i));
+                }
+            },
+            AstNode::Ident(name, slot, _) => {
+                let name = self.name(name);
+                self.emit(Op::Get(name, *slot));
+            },
+            AstNode::Assign(name, slot, value, _) => {
+                self.assign(name, *slot, value)?;
+                self.emit(Op::Nil);
+            },
+            AstNode::MultiAssign(names, values) => {
+                for v in values {
+                    self.expr(v)?;
+                }
+                // the names are kept together, in order
+                let first = self.chunk.names.len() as u32;
+                let count = names.len().min(values.len()) as u32;
+                self.chunk.names.extend(names.iter().take(count as usize).cloned());
+                // values without a name are dropped
+                for _ in count as usize..values.len() {
+                    self.emit(Op::Pop);
+                }
+                self.emit(Op::MultiSet(first, count));
+                self.emit(Op::Nil);
+            },
+            AstNode::Binary(op, a, b, span) => {
+                self.expr(a)?;
+                self.expr(b)?;
+                self.emit_at(Op::Binary(*op), *span);
+            },
+            AstNode::Slice { target, start, end, span } => {
+                self.expr(target)?;
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound)?;
+                }
+                self.emit_at(Op::Slice(start.is_some(), end.is_some()), *span);
+            },
+            AstNode::Compare(first, chain, span) => {
+                self.expr(first)?;
+                if let [(op, rhs)] = chain.as_slice() {
+                    self.expr(rhs)?;
+                    self.emit_at(Op::Binary(*op), *span);
+                    return Some(())
+                }
+                let mut fails = Vec::new();
+                for (op, rhs) in chain {
+                    self.expr(rhs)?;
+                    fails.push(self.emit_at(Op::Compare(*op, 0), *span));
+                }
+                self.emit(Op::Pop);
+                let yes = self.constant(Value::Bool(true));
+                self.emit(Op::Const(yes));
+                for fail in fails {
+                    self.patch(fail);
+                }
+            },
+            AstNode::Unary(op, a, span) => {
+                self.expr(a)?;
+                self.emit_at(Op::Unary(*op), *span);
+            },
+            AstNode::If { cond, then, or, .. } => {
+                self.expr(cond)?;
+                let skip = self.emit(Op::JumpIfFalse(0));
+                self.expr(then)?;
+                let end = self.emit(Op::Jump(0));
+                self.patch(skip);
+                match or {
+                    Some(or) => self.expr(or)?,
+                    None => {
+                        self.emit(Op::Nil);
+                    }
+                }
+                self.patch(end);
+            },
+            AstNode::Block(stmts, scoped) => self.block(stmts, *scoped)?,
+            AstNode::Loop { label, cond, run } => {
+                let label = label.as_deref().map(|l| self.name(l));
+                let head = self.here();
+                let exit = self.emit(Op::LoopBreak(label, 0));
+                let skip = match cond {
+                    Some(cond) => {
+                        self.expr(cond)?;
+                        Some(self.emit(Op::JumpIfFalse(0)))
+                    },
+                    None => None
+                };
+                self.expr(run)?;
+                self.emit(Op::Pop);
+                // a false condition skips the body, but keeps looping
+                if let Some(skip) = skip {
+                    self.patch(skip);
+                }
+                self.emit(Op::Jump(head));
+                self.patch(exit);
+                self.emit(Op::Nil);
+            },
+            AstNode::For { binding, iterable, run } => {
+                self.expr(iterable)?;
+                let binding = self.name(binding);
+                self.emit(Op::IterStart(binding));
+                let head = self.here();
+                let done = self.emit(Op::IterNext(binding, 0));
+                self.expr(run)?;
+                self.emit(Op::Pop);
+                let exit = self.emit(Op::LoopBreak(None, 0));
+                self.emit(Op::Jump(head));
+                self.patch(done);
+                self.patch(exit);
+                self.emit(Op::IterEnd);
+                self.emit(Op::Nil);
+            },
+            AstNode::Break(label) => {
+                let label = label.as_deref().map(|l| self.name(l));
+                self.emit(Op::Break(label));
+                self.emit(Op::Nil);
+            },
+            AstNode::Try { body, binding, handler } => {
+                let binding = binding.as_deref().map(|b| self.name(b));
+                let catch = self.emit(Op::TryStart(binding, 0));
+                self.expr(body)?;
+                self.emit(Op::TryEnd);
+                let end = self.emit(Op::Jump(0));
+                // the machine opens the handler's scope as it jumps here
+                self.patch(catch);
+                self.expr(handler)?;
+                self.emit(Op::PopScope);
+                self.patch(end);
+            },
+            AstNode::Throw(thrown) => {
+                self.expr(thrown)?;
+                self.emit(Op::Throw);
+            },
+            AstNode::Assert { cond, message, source, span } => {
+                self.expr(cond)?;
+                let ok = self.emit(Op::JumpIfTrue(0));
+                if let Some(message) = message {
+                    self.expr(message)?;
+                }
+                self.chunk.asserts.push((source.clone(), *span));
+                let i = self.chunk.asserts.len() as u32 - 1;
+                self.emit(Op::Assert(i, message.is_some()));
+                self.patch(ok);
+                self.emit(Op::Nil);
+            },
+            AstNode::Use { path, binding } => {
+                let path = self.name(path);
+                let binding = self.name(binding);
+                self.emit(Op::Use(path, binding));
+                self.emit(Op::Nil);
+            },
+            AstNode::Pub { value, .. } => self.expr(value)?,
+            AstNode::Defer(expr) => {
+                self.chunk.deferred.push(expr.clone());
+                let i = self.chunk.deferred.len() as u32 - 1;
+                self.emit(Op::Defer(i));
+                self.emit(Op::Nil);
+            },
+            // a generator's body is always run by the tree-walker
+            AstNode::Yield(_) | AstNode::Error => return None
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,258 @@
+use std::rc::Rc;
+
+use crate::{fe::ast::BinOp, executor::{binary_at, caught, compare, slice, throw, traced, unary_at, RuntimeError, FOR_BINDING, VM}, fe::ast::Type, iterator::Iterate, module, value::Value};
+
+use super::{Chunk, Op};
+
+/// where to go when something in a `try` body fails,
+/// and how much to unwind before getting there
+struct Handler {
+    pc: usize,
+    binding: Option<u32>,
+    depth: usize,
+    height: usize,
+    iters: usize,
+    calls: usize
+}
+
+struct Machine<'a> {
+    chunk: &'a Chunk,
+    pc: usize,
+    stack: Vec<Value>,
+    /// the iterators of the `for` loops being run, innermost last
+    iters: Vec<Rc<dyn Iterate>>,
+    handlers: Vec<Handler>
+}
+
+/// pops scopes until only `depth` remain. the failure that got
+/// here takes priority over any from deferred expressions
+fn unwind(vm: &mut VM, depth: usize) {
+    while vm.locals.depth() > depth {
+        let _ = vm.pop_scope();
+    }
+}
+
+/// runs a compiled program, giving the value of its last expression
+pub fn run(vm: &mut VM, chunk: &Chunk) -> Result<Value, RuntimeError> {
+    let depth = vm.locals.depth();
+    let mut machine = Machine {
+        chunk,
+        pc: 0,
+        stack: Vec::new(),
+        iters: Vec::new(),
+        handlers: Vec::new()
+    };
+    loop {
+        let mut e = match machine.execute(vm) {
+            Ok(v) => return Ok(v),
+            Err(e) => match chunk.spans[machine.pc - 1] {
+                span if span.is_empty() => e,
+                span => e.at(span)
+            }
+        };
+        vm.report_error(&mut e);
+        let handler = match &e {
+            RuntimeError::Yield(_) => None,
+            e if e.fatal() => None,
+            _ => machine.handlers.pop()
+        };
+        let Some(handler) = handler else {
+            unwind(vm, depth);
+            return Err(e)
+        };
+        unwind(vm, handler.depth);
+        machine.stack.truncate(handler.height);
+        machine.iters.truncate(handler.iters);
+        vm.calls.truncate(handler.calls);
+        vm.push_scope();
+        if let Some(binding) = handler.binding {
+            vm.locals.define(&chunk.names[binding as usize], caught(e));
+        }
+        machine.pc = handler.pc;
+    }
+}
+
+/// the common operators on two ints, skipping the general case when
+/// nothing can fail. overflow and division are left to [`binary`]
+fn int_binary(op: BinOp, a: i64, b: i64) -> Option<Value> {
+    Some(match op {
+        BinOp::Add => Value::Int(a.checked_add(b)?),
+        BinOp::Sub => Value::Int(a.checked_sub(b)?),
+        BinOp::Mul => Value::Int(a.checked_mul(b)?),
+        BinOp::Mod => Value::Int(a.checked_rem(b)?),
+        BinOp::Eq => Value::Bool(a == b),
+        BinOp::NotEq => Value::Bool(a != b),
+        BinOp::Lt => Value::Bool(a < b),
+        BinOp::LtEq => Value::Bool(a <= b),
+        BinOp::Gt => Value::Bool(a > b),
+        BinOp::GtEq => Value::Bool(a >= b),
+        BinOp::BitAnd => Value::Int(a & b),
+        BinOp::BitOr => Value::Int(a | b),
+        BinOp::BitXor => Value::Int(a ^ b),
+        _ => return None
+    })
+}
+
+impl Machine<'_> {
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("popped an empty stack")
+    }
+
+    /// runs from `pc` until the end of the chunk or the first failure
+    fn execute(&mut self, vm: &mut VM) -> Result<Value, RuntimeError> {
+        let chunk = self.chunk;
+        let name = |i: u32| chunk.names[i as usize].as_str();
+        while let Some(op) = chunk.code.get(self.pc) {
+            let at = self.pc;
+            self.pc += 1;
+            vm.step()?;
+            if vm.watched() {
+                if let Some(span) = chunk.statements.get(&at) {
+                    vm.enter_statement(*span);
+                }
+            }
+            match *op {
+                Op::Const(i) => self.stack.push(chunk.consts[i as usize].clone()),
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::Get(i, slot) => self.stack.push(vm.locals.get_slot(name(i), slot)),
+                Op::Set(i, slot) => {
+                    let val = self.pop();
+                    vm.locals.set_slot(name(i), slot, val);
+                },
+                Op::AddSet(i, slot) => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    match (&a, &b) {
+                        (Value::Int(x), Value::Int(y)) if x.checked_add(*y).is_some() => {
+                            vm.locals.set_slot(name(i), slot, Value::Int(x + y));
+                        },
+                        _ => vm.add_assign(name(i), slot, a, b, chunk.spans[self.pc - 1])?
+                    }
+                },
+                Op::MultiSet(first, count) => {
+                    let vals = self.stack.split_off(self.stack.len() - count as usize);
+                    for (i, val) in (first..first + count).zip(vals) {
+                        vm.locals.set(name(i), val);
+                    }
+                },
+                Op::Pop => {
+                    self.pop();
+                },
+                Op::Binary(op) => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    let out = match (&a, &b) {
+                        (Value::Int(x), Value::Int(y)) => int_binary(op, *x, *y),
+                        _ => None
+                    };
+                    match out {
+                        Some(v) => self.stack.push(v),
+                        None => {
+                            vm.check_binary(&op, &a, &b)?;
+                            self.stack.push(binary_at(&op, a, b, chunk.spans[self.pc - 1])?)
+                        }
+                    }
+                },
+                Op::Unary(op) => {
+                    let val = self.pop();
+                    self.stack.push(unary_at(&op, val, chunk.spans[self.pc - 1])?);
+                },
+                Op::Compare(op, to) => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let holds = compare(&op, &lhs, &rhs).map_err(|e| e.during(chunk.spans[self.pc - 1], || {
+                        format!("{} {} {}", Type::of(&lhs).name(), op.symbol(), Type::of(&rhs).name())
+                    }))?;
+                    if holds {
+                        self.stack.push(rhs);
+                    } else {
+                        self.stack.push(Value::Bool(false));
+                        self.pc = to as usize;
+                    }
+                },
+                Op::Slice(start, end) => {
+                    let end = if end { Some(self.pop().int()?) } else { None };
+                    let start = if start { Some(self.pop().int()?) } else { None };
+                    let target = self.pop();
+                    self.stack.push(slice(target, start, end)?);
+                },
+                Op::Jump(to) => self.pc = to as usize,
+                Op::JumpIfFalse(to) => if !self.pop().truthy() {
+                    self.pc = to as usize;
+                },
+                Op::JumpIfTrue(to) => if self.pop().truthy() {
+                    self.pc = to as usize;
+                },
+                Op::PushScope => vm.push_scope(),
+                Op::PopScope => vm.pop_scope()?,
+                Op::Call(argc) => {
+                    let args = self.stack.split_off(self.stack.len() - argc as usize);
+                    let func = self.pop().func()?;
+                    self.stack.push(vm.call_at(func, args, chunk.spans[self.pc - 1])?);
+                },
+                Op::Break(label) => vm.start_break(label.map(|l| name(l).to_string())),
+                Op::BreakOut(to) => if vm.breaking() {
+                    self.pc = to as usize;
+                },
+                Op::LoopBreak(label, to) => if vm.breaking() {
+                    let label = label.map(|l| name(l).to_string());
+                    if vm.take_break(&label) {
+                        self.pc = to as usize;
+                    }
+                },
+                Op::IterStart(binding) => {
+                    let iter = self.pop().iter()?;
+                    // holds the loop variable
+                    vm.push_scope();
+                    vm.locals.define(name(binding), Value::Nil);
+                    self.iters.push(iter);
+                },
+                Op::IterNext(binding, to) => {
+                    let iter = self.iters.last().expect("no loop to iterate").clone();
+                    match iter.next(vm)? {
+                        Some(v) => vm.locals.set_slot(name(binding), FOR_BINDING, v),
+                        None => self.pc = to as usize
+                    }
+                },
+                Op::IterEnd => {
+                    self.iters.pop();
+                    vm.pop_scope()?;
+                },
+                Op::TryStart(binding, to) => self.handlers.push(Handler {
+                    pc: to as usize,
+                    binding,
+                    depth: vm.locals.depth(),
+                    height: self.stack.len(),
+                    iters: self.iters.len(),
+                    calls: vm.calls.len()
+                }),
+                Op::TryEnd => {
+                    self.handlers.pop();
+                },
+                Op::Throw => return Err(throw(self.pop())),
+                Op::Assert(i, has_message) => {
+                    let message = if has_message { Some(self.pop().to_string()) } else { None };
+                    let (source, span) = &chunk.asserts[i as usize];
+                    return Err(RuntimeError::AssertionFailed {
+                        expr: source.clone(),
+                        message,
+                        span: *span
+                    }.at(*span))
+                },
+                Op::Use(path, binding) => {
+                    let module = module::import(vm, name(path))?;
+                    vm.locals.set(name(binding), module);
+                },
+                Op::Defer(i) => vm.defer(chunk.deferred[i as usize].clone())
+            }
+            if vm.tracing() {
+                let span = chunk.spans[at];
+                let span = if span.is_empty() { String::new() } else { format!(" at {}..{}", span.start, span.end) };
+                // most instructions leave their result on top of the stack
+                let top = self.stack.last().map(|v| format!(" => {}", traced(Ok(v)))).unwrap_or_default();
+                eprintln!("[trace] {:>4} {:?}{}{}", at, op, span, top);
+            }
+        }
+        Ok(self.stack.pop().unwrap_or(Value::Nil))
+    }
+}
@@ -0,0 +1,89 @@
+//! A compiler from the AST to a flat list of instructions, and a stack
+//! machine that runs them. Scoping stays dynamic, so variables still live
+//! in the VM's `LocalStack` and functions called from compiled code are
+//! run by the tree-walker, which is also used for anything `compile`
+//! doesn't handle.
+
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{fe::{ast::{AstNode, BinOp, Slot, UnOp}, diagnostic::Span}, value::Value};
+
+mod compile;
+mod machine;
+
+pub use compile::compile;
+pub use machine::run;
+
+/// an instruction. operands index into the tables of the `Chunk`,
+/// and jumps hold the position of the instruction to go to. every
+/// expression leaves one value on the stack, so instructions for
+/// statements with no value of their own are followed by a `Nil`
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    /// pushes a constant
+    Const(u32),
+    Nil,
+    /// pushes the variable with this name
+    Get(u32, Slot),
+    /// pops a value into the variable with this name
+    Set(u32, Slot),
+    /// pops a value and adds it to the one below, which is what the variable
+    /// with this name held, popping that into the variable. see `VM::add_assign`
+    AddSet(u32, Slot),
+    /// pops `count` values into the names starting at the first, in order
+    MultiSet(u32, u32),
+    Pop,
+    Binary(BinOp),
+    Unary(UnOp),
+    /// one link of a comparison chain. pops both operands, then
+    /// pushes the right one to carry on with, or `false` and jumps
+    Compare(BinOp, u32),
+    /// pops the target and whichever bounds are given
+    Slice(bool, bool),
+    Jump(u32),
+    /// pops the condition
+    JumpIfFalse(u32),
+    JumpIfTrue(u32),
+    PushScope,
+    PopScope,
+    /// pops the arguments, then the function
+    Call(u32),
+    /// starts a break with the optional label
+    Break(Option<u32>),
+    /// skips the rest of a block when a break is underway
+    BreakOut(u32),
+    /// leaves a loop when a break aimed at the label is underway
+    LoopBreak(Option<u32>, u32),
+    /// pops an iterable, opening a scope that binds the name
+    IterStart(u32),
+    /// binds the next value, or jumps once the iterator is done
+    IterNext(u32, u32),
+    /// drops the iterator and its scope
+    IterEnd,
+    /// catches errors by binding them to the name and jumping to the handler
+    TryStart(Option<u32>, u32),
+    TryEnd,
+    Throw,
+    /// fails with the source of the assertion, popping the message if there is one
+    Assert(u32, bool),
+    /// imports the module at the path, binding it to the name
+    Use(u32, u32),
+    Defer(u32)
+}
+
+/// a compiled program
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub consts: Vec<Value>,
+    /// the variables, labels and paths named by instructions
+    pub names: Vec<String>,
+    pub deferred: Vec<Rc<AstNode>>,
+    /// the source text and span of each assertion
+    pub asserts: Vec<(String, Span)>,
+    /// where each instruction came from, or an empty span
+    /// for those that can't fail in a way worth pointing at
+    pub spans: Vec<Span>,
+    /// the span of each statement, by the instruction it starts at
+    pub statements: HashMap<usize, Span>
+}
@@ -0,0 +1,250 @@
+//! Functions that change an array do so in place, on the shared handle.
+//! The others, like `map` and `filter`, return a new array.
+
+use std::cmp::Ordering;
+
+use crate::executor::{resolve_index, slice_range};
+
+use super::*;
+
+pub fn array() -> Value {
+    let mut array = NativeLibModule::new();
+
+    array.export_fn("of", of);
+    array.export_fn("len", len);
+    array.export_fn("push", push);
+    array.export_fn("pop", pop);
+    array.export_fn("insert", insert);
+    array.export_fn("remove", remove);
+    array.export_fn("slice", slice);
+    array.export_fn("reverse", reverse);
+    array.export_fn("sort", sort);
+    array.export_fn("map", map);
+    array.export_fn("filter", filter);
+    array.export_fn("reduce", reduce);
+    array.export_fn("find", find);
+    array.export_fn("contains", contains);
+    array.export_fn("flatten", flatten);
+    array.export_fn("min", min);
+    array.export_fn("max", max);
+
+    array.into()
+}
+
+/// an array of the arguments
+fn of(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(Value::Array(Handle::new(vals)))
+}
+
+fn len(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let len = arr.borrow().len();
+    Ok(Value::Int(len as i64))
+}
+
+/// appends every value after the array
+fn push(_vm: &mut VM, mut vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if vals.is_empty() {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let arr = vals[0].array()?;
+    arr.borrow_mut().extend(vals.drain(1..));
+    Ok(Value::Nil)
+}
+
+/// removes and returns the last element, or nil when the array is empty
+fn pop(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let last = arr.borrow_mut().pop();
+    Ok(last.unwrap_or(Value::Nil))
+}
+
+/// `insert(arr, i, val)` puts `val` at `i`, where `i` may be the length to append
+fn insert(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let arr = vals[0].array()?;
+    let mut arr = arr.borrow_mut();
+    let len = arr.len();
+    let i = match vals[1].int()? {
+        i if i == len as i64 => len,
+        i => resolve_index(i, len).ok_or(RuntimeError::IndexOutOfRange)?
+    };
+    arr.insert(i, vals[2].clone());
+    Ok(Value::Nil)
+}
+
+/// removes and returns the element at the index, or nil when it's out of range
+fn remove(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let arr = vals[0].array()?;
+    let mut arr = arr.borrow_mut();
+    Ok(match resolve_index(vals[1].int()?, arr.len()) {
+        Some(i) => arr.remove(i),
+        None => Value::Nil
+    })
+}
+
+/// `slice(arr, start, end)`, which works like `arr[start..end]`
+fn slice(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let arr = vals[0].array()?;
+    let arr = arr.borrow();
+    let start = vals[1].int()?;
+    let end = vals.get(2).map(|e| e.int()).transpose()?;
+    let range = slice_range(Some(start), end, arr.len());
+    Ok(Value::Array(Handle::new(arr[range].to_vec())))
+}
+
+fn reverse(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    arr.borrow_mut().reverse();
+    Ok(Value::Nil)
+}
+
+/// a stable merge sort that stops at the first failed comparison.
+/// `slice::sort_by` can't be used because it may panic when the
+/// comparison isn't a total order, which a script's comparator needn't be
+fn merge_sort(items: Vec<Value>, cmp: &mut impl FnMut(&Value, &Value) -> Result<Ordering, RuntimeError>)
+    -> Result<Vec<Value>, RuntimeError> {
+    if items.len() <= 1 {
+        return Ok(items)
+    }
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let left = merge_sort(left, cmp)?;
+    let right = merge_sort(right, cmp)?;
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // taking from the left on ties keeps equal elements in order
+        if cmp(r, l)? == Ordering::Less {
+            merged.push(right.next().unwrap());
+        } else {
+            merged.push(left.next().unwrap());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+/// sorts into ascending order by `Value::compare`, or sorts by a
+/// comparator, `sort(arr, func(a, b) { })`, which returns a negative
+/// number when `a` comes first, a positive one when `b` does, and 0 for a tie
+fn sort(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let arr = vals[0].array()?;
+    // the comparator may look at the array, so it isn't borrowed while sorting
+    let items = arr.borrow().clone();
+    let sorted = match vals.get(1) {
+        Some(func) => {
+            let func = func.func()?;
+            merge_sort(items, &mut |a, b| {
+                let n = func.call(vm, vec![a.clone(), b.clone()])?.num()?;
+                n.partial_cmp(&0.0).ok_or(RuntimeError::CannotCompare)
+            })?
+        },
+        None => merge_sort(items, &mut |a, b| Ok(a.compare(b)))?
+    };
+    *arr.borrow_mut() = sorted;
+    Ok(Value::Nil)
+}
+
+fn map(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    // the callback may change the array, so it works on a copy
+    let items = vals[0].array()?.borrow().clone();
+    let func = vals[1].func()?;
+    let mapped = items.into_iter()
+        .map(|v| func.call(vm, vec![v]))
+        .collect::<Result<_, _>>()?;
+    Ok(Value::Array(Handle::new(mapped)))
+}
+
+fn filter(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let items = vals[0].array()?.borrow().clone();
+    let func = vals[1].func()?;
+    let mut kept = Vec::new();
+    for v in items {
+        if func.call(vm, vec![v.clone()])?.truthy() {
+            kept.push(v);
+        }
+    }
+    Ok(Value::Array(Handle::new(kept)))
+}
+
+/// `reduce(arr, func(acc, x) { }, init)`. without `init`, the first
+/// element starts the accumulator, and an empty array gives nil
+fn reduce(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let items = vals[0].array()?.borrow().clone();
+    let func = vals[1].func()?;
+    let mut items = items.into_iter();
+    let Some(mut acc) = vals.get(2).cloned().or_else(|| items.next()) else {
+        return Ok(Value::Nil)
+    };
+    for v in items {
+        acc = func.call(vm, vec![acc, v])?;
+    }
+    Ok(acc)
+}
+
+/// the first element the function returns something truthy for, or nil
+fn find(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let items = vals[0].array()?.borrow().clone();
+    let func = vals[1].func()?;
+    for v in items {
+        if func.call(vm, vec![v.clone()])?.truthy() {
+            return Ok(v)
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn contains(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let arr = vals[0].array()?;
+    let found = arr.borrow().contains(&vals[1]);
+    Ok(Value::Bool(found))
+}
+
+/// flattens one level of nesting, failing on an array that contains itself
+fn flatten(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let mut flat = Vec::new();
+    for v in arr.borrow().iter() {
+        match v {
+            Value::Array(inner) if inner.ptr_eq(&arr) => {
+                return Err(RuntimeError::InvalidArgument("can't flatten an array that contains itself".to_owned()))
+            },
+            Value::Array(inner) => flat.extend(inner.borrow().iter().cloned()),
+            v => flat.push(v.clone())
+        }
+    }
+    Ok(Value::Array(Handle::new(flat)))
+}
+
+/// the item that orders first by `Value::compare`, or nil if there are none.
+/// unlike `math.min` it takes items of any type
+fn min(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let min = arr.borrow().iter().min_by(|a, b| a.compare(b)).cloned();
+    Ok(min.unwrap_or(Value::Nil))
+}
+
+/// the item that orders last by `Value::compare`, or nil if there are none
+fn max(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let max = arr.borrow().iter().max_by(|a, b| a.compare(b)).cloned();
+    Ok(max.unwrap_or(Value::Nil))
+}
@@ -0,0 +1,234 @@
+//! Binary buffers. like arrays, bytes values are shared handles, and the
+//! functions that change them do so in place. `read` and `write` take the
+//! layout of a number as a name like `u8`, `i32` or `f64`, little endian
+//! unless it ends in `_be`.
+
+use crate::executor::{resolve_index, slice_range};
+
+use super::*;
+
+pub fn bytes() -> Value {
+    let mut bytes = NativeLibModule::new();
+
+    bytes.export_fn("new", new);
+    bytes.export_fn("from_string", from_string);
+    bytes.export_fn("to_string", to_string);
+    bytes.export_fn("from_array", from_array);
+    bytes.export_fn("to_array", to_array);
+    bytes.export_fn("len", len);
+    bytes.export_fn("slice", slice);
+    bytes.export_fn("push", push);
+    bytes.export_fn("set", set);
+    bytes.export_fn("read", read);
+    bytes.export_fn("write", write);
+
+    bytes.into()
+}
+
+fn byte(val: &Value) -> Result<u8, RuntimeError> {
+    let n = val.int()?;
+    u8::try_from(n).map_err(|_| RuntimeError::InvalidArgument(format!("{} is not a byte", n)))
+}
+
+/// `new(n)` makes `n` zero bytes, or `new(n, fill)` `n` copies of `fill`
+fn new(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 0, 2)?;
+    let n = match vals.first() {
+        Some(n) => n.int()?.max(0) as usize,
+        None => 0
+    };
+    let fill = match vals.get(1) {
+        Some(fill) => byte(fill)?,
+        None => 0
+    };
+    vm.reserve(n)?;
+    Ok(Value::Bytes(Handle::new(vec![fill; n])))
+}
+
+fn from_string(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let bytes = s.borrow().as_bytes().to_vec();
+    Ok(Value::Bytes(Handle::new(bytes)))
+}
+
+/// fails on invalid UTF-8, unless `to_string(b, true)` is used to have it replaced
+fn to_string(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let bytes = vals[0].bytes()?;
+    let bytes = bytes.borrow();
+    if vals.get(1).is_some_and(|lossy| lossy.truthy()) {
+        return Ok(String::from_utf8_lossy(&bytes).into_owned().into())
+    }
+    std::str::from_utf8(&bytes)
+        .map(Value::from)
+        .map_err(|e| RuntimeError::InvalidFormat(format!("bytes are not valid UTF-8: {}", e)))
+}
+
+fn from_array(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let bytes = arr.borrow().iter().map(byte).collect::<Result<_, _>>()?;
+    Ok(Value::Bytes(Handle::new(bytes)))
+}
+
+fn to_array(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let bytes = vals[0].bytes()?;
+    let ints = bytes.borrow().iter().map(|b| Value::Int(*b as i64)).collect();
+    Ok(Value::Array(Handle::new(ints)))
+}
+
+fn len(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(Value::Int(vals[0].bytes()?.borrow().len() as i64))
+}
+
+fn slice(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let bytes = vals[0].bytes()?;
+    let bytes = bytes.borrow();
+    let start = vals[1].int()?;
+    let end = vals.get(2).map(|e| e.int()).transpose()?;
+    let range = slice_range(Some(start), end, bytes.len());
+    Ok(Value::Bytes(Handle::new(bytes[range].to_vec())))
+}
+
+/// appends each argument, which may be a byte, or a string or bytes to copy in
+fn push(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if vals.is_empty() {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let bytes = vals[0].bytes()?;
+    let mut added = Vec::new();
+    for val in &vals[1..] {
+        match val {
+            Value::String(_) | Value::Bytes(_) => added.extend(val.data()?),
+            val => added.push(byte(val)?)
+        }
+    }
+    // added separately, so pushing a buffer onto itself works
+    bytes.borrow_mut().extend(added);
+    Ok(Value::Nil)
+}
+
+/// `set(b, i, byte)`, where a negative `i` counts from the end
+fn set(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let bytes = vals[0].bytes()?;
+    let value = byte(&vals[2])?;
+    let mut bytes = bytes.borrow_mut();
+    let i = resolve_index(vals[1].int()?, bytes.len()).ok_or(RuntimeError::IndexOutOfRange)?;
+    bytes[i] = value;
+    Ok(Value::Nil)
+}
+
+/// how a number is laid out in memory
+struct Layout {
+    width: usize,
+    float: bool,
+    signed: bool,
+    big_endian: bool
+}
+
+impl Layout {
+    fn parse(val: &Value) -> Result<Self, RuntimeError> {
+        let name = val.string()?;
+        let name = name.borrow();
+        let (kind, big_endian) = match name.strip_suffix("_be") {
+            Some(kind) => (kind, true),
+            None => (name.strip_suffix("_le").unwrap_or(&name), false)
+        };
+        let (float, signed, width) = match kind {
+            "u8" => (false, false, 1),
+            "i8" => (false, true, 1),
+            "u16" => (false, false, 2),
+            "i16" => (false, true, 2),
+            "u32" => (false, false, 4),
+            "i32" => (false, true, 4),
+            "u64" => (false, false, 8),
+            "i64" => (false, true, 8),
+            "f32" => (true, true, 4),
+            "f64" => (true, true, 8),
+            _ => return Err(RuntimeError::InvalidArgument(format!("unknown number layout `{}`", name)))
+        };
+        Ok(Self { width, float, signed, big_endian })
+    }
+}
+
+/// `read(b, offset, layout)` decodes the number at `offset`
+fn read(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let bytes = vals[0].bytes()?;
+    let bytes = bytes.borrow();
+    let layout = Layout::parse(&vals[2])?;
+    let start = usize::try_from(vals[1].int()?).map_err(|_| RuntimeError::IndexOutOfRange)?;
+    let Some(raw) = bytes.get(start..start.saturating_add(layout.width)) else {
+        return Err(RuntimeError::IndexOutOfRange)
+    };
+
+    // widened to eight bytes, so every layout decodes the same way
+    let mut buf = [0; 8];
+    if layout.big_endian {
+        buf[8 - layout.width..].copy_from_slice(raw);
+        buf.reverse();
+    } else {
+        buf[..layout.width].copy_from_slice(raw);
+    }
+    let bits = u64::from_le_bytes(buf);
+    Ok(match (layout.float, layout.width) {
+        (true, 4) => Value::Number(f32::from_bits(bits as u32) as f64),
+        (true, _) => Value::Number(f64::from_bits(bits)),
+        (false, _) if layout.signed => {
+            // sign extend from the top bit of the layout
+            let shift = 64 - 8 * layout.width as u32;
+            Value::Int(((bits << shift) as i64) >> shift)
+        },
+        // a u64 past the range of an int becomes a float
+        (false, _) => match i64::try_from(bits) {
+            Ok(i) => Value::Int(i),
+            Err(_) => Value::Number(bits as f64)
+        }
+    })
+}
+
+/// `write(b, offset, layout, n)` encodes `n` at `offset`. writing past
+/// the end grows the buffer, but the offset must be within it
+fn write(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 4)?;
+    let bytes = vals[0].bytes()?;
+    let layout = Layout::parse(&vals[2])?;
+    let bits = match (layout.float, layout.width) {
+        (true, 4) => (vals[3].num()? as f32).to_bits() as u64,
+        (true, _) => vals[3].num()?.to_bits(),
+        (false, width) => {
+            let n = vals[3].int()?;
+            let bits = 8 * width as u32;
+            let fits = if layout.signed {
+                bits == 64 || (-(1i64 << (bits - 1))..1i64 << (bits - 1)).contains(&n)
+            } else {
+                n >= 0 && (bits == 64 || n < 1i64 << bits)
+            };
+            if !fits {
+                return Err(RuntimeError::InvalidArgument(format!("{} doesn't fit in {} bytes", n, width)))
+            }
+            n as u64
+        }
+    };
+    let mut raw = bits.to_le_bytes()[..layout.width].to_vec();
+    if layout.big_endian {
+        raw.reverse();
+    }
+
+    let mut bytes = bytes.borrow_mut();
+    let start = usize::try_from(vals[1].int()?)
+        .ok()
+        .filter(|start| *start <= bytes.len())
+        .ok_or(RuntimeError::IndexOutOfRange)?;
+    let end = start + layout.width;
+    if end > bytes.len() {
+        bytes.resize(end, 0);
+    }
+    bytes[start..end].copy_from_slice(&raw);
+    Ok(Value::Nil)
+}
@@ -0,0 +1,149 @@
+//! Coroutines, for running several tasks in turns without threads.
+//! `create(f)` makes one from a function, which `resume` runs until it
+//! yields, returning the yielded value, and returns the function's own
+//! value from the `resume` that finishes it. the arguments of the first
+//! `resume` are passed to `f`; later ones can't take any, since a `yield`
+//! can't receive them. `status` is `"suspended"` until the function
+//! returns or fails, then `"dead"`, and `"running"` while it runs.
+//! coroutines can also be iterated, which resumes them to get each
+//! yielded value.
+
+use std::cell::{Cell, RefCell};
+
+use crate::{function::Callable, iterator::Iterate};
+
+use super::*;
+
+pub fn coroutine() -> Value {
+    let mut coroutine = NativeLibModule::new();
+
+    coroutine.export_fn("create", create);
+    coroutine.export_fn("resume", resume);
+    coroutine.export_fn("status", status);
+
+    coroutine.into()
+}
+
+#[derive(Debug)]
+enum State {
+    /// waiting for the first `resume` to call the function
+    Fresh(Rc<dyn Callable>),
+    /// the generator the function returned, which runs its body
+    Started(Rc<dyn Iterate>),
+    Dead
+}
+
+/// what a `resume` ran into
+enum Step {
+    Yielded(Value),
+    Returned(Value)
+}
+
+#[derive(Debug)]
+pub struct Coroutine {
+    state: RefCell<State>,
+    running: Cell<bool>
+}
+
+impl Coroutine {
+    fn resume(&self, vm: &mut VM, args: Vec<Value>) -> Result<Step, RuntimeError> {
+        if self.running.get() {
+            return Err(RuntimeError::InvalidArgument("a coroutine can't resume itself".to_owned()))
+        }
+        if !args.is_empty() && !matches!(*self.state.borrow(), State::Fresh(_)) {
+            return Err(RuntimeError::InvalidArgument(
+                "only the first resume of a coroutine takes arguments".to_owned()
+            ))
+        }
+        let state = std::mem::replace(&mut *self.state.borrow_mut(), State::Dead);
+        let generator = match state {
+            State::Fresh(func) => {
+                self.running.set(true);
+                let started = func.call(vm, args);
+                self.running.set(false);
+                match started? {
+                    Value::Iterator(generator) => generator,
+                    // a function without a `yield` is done as soon as it returns
+                    value => return Ok(Step::Returned(value))
+                }
+            },
+            State::Started(generator) => generator,
+            State::Dead => return Ok(Step::Returned(Value::Nil))
+        };
+        self.running.set(true);
+        let next = generator.next(vm);
+        self.running.set(false);
+        match next? {
+            Some(value) => {
+                *self.state.borrow_mut() = State::Started(generator);
+                Ok(Step::Yielded(value))
+            },
+            None => Ok(Step::Returned(generator.as_generator().map_or(Value::Nil, |g| g.returned())))
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match (&*self.state.borrow(), self.running.get()) {
+            (_, true) => "running",
+            (State::Dead, _) => "dead",
+            _ => "suspended"
+        }
+    }
+}
+
+impl Iterate for Coroutine {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        match self.resume(vm, vec![])? {
+            Step::Yielded(value) => Ok(Some(value)),
+            Step::Returned(_) => Ok(None)
+        }
+    }
+
+    fn display(&self) -> String {
+        format!("< {} coroutine >", self.status())
+    }
+
+    fn as_coroutine(&self) -> Option<&Coroutine> {
+        Some(self)
+    }
+}
+
+fn coroutine_arg(val: &Value) -> Result<Rc<dyn Iterate>, RuntimeError> {
+    match val {
+        Value::Iterator(iter) if iter.as_coroutine().is_some() => Ok(iter.clone()),
+        _ => Err(RuntimeError::InvalidArgument(format!("{} is not a coroutine", val)))
+    }
+}
+
+fn create(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let coroutine = Coroutine {
+        state: RefCell::new(State::Fresh(vals[0].func()?)),
+        running: Cell::new(false)
+    };
+    Ok(Value::Iterator(Rc::new(coroutine)))
+}
+
+/// `resume(co, args...)` runs `co` until it next yields, returning the value,
+/// or until it finishes, returning what the function did. resuming a dead
+/// coroutine fails
+fn resume(vm: &mut VM, mut vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if vals.is_empty() {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let co = coroutine_arg(&vals[0])?;
+    let co = co.as_coroutine().expect("checked by coroutine_arg");
+    if co.status() == "dead" {
+        return Err(RuntimeError::InvalidArgument("can't resume a dead coroutine".to_owned()))
+    }
+    let args = vals.split_off(1);
+    match co.resume(vm, args)? {
+        Step::Yielded(value) | Step::Returned(value) => Ok(value)
+    }
+}
+
+fn status(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let co = coroutine_arg(&vals[0])?;
+    Ok(co.as_coroutine().expect("checked by coroutine_arg").status().into())
+}
@@ -0,0 +1,140 @@
+//! Comma separated values, following RFC 4180: fields may be quoted,
+//! quotes inside them are doubled, and quoted fields may span lines.
+
+use super::*;
+
+pub fn csv() -> Value {
+    let mut csv = NativeLibModule::new();
+
+    csv.export_fn("parse", parse);
+    csv.export_fn("stringify", stringify);
+
+    csv.into()
+}
+
+/// the delimiter argument at `i`, which defaults to a comma
+fn delimiter(vals: &[Value], i: usize) -> Result<char, RuntimeError> {
+    let Some(d) = vals.get(i) else {
+        return Ok(',')
+    };
+    let d = d.string()?;
+    let d = d.borrow();
+    let mut chars = d.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '"' && c != '\n' && c != '\r' => Ok(c),
+        _ => Err(RuntimeError::InvalidArgument(format!("`{}` can't be a csv delimiter", d)))
+    }
+}
+
+fn rows(text: &str, delim: char) -> Result<Vec<Vec<String>>, RuntimeError> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    // whether anything has been seen since the last row ended
+    let mut pending = false;
+    while let Some(c) = chars.next() {
+        pending = true;
+        match c {
+            '"' if field.is_empty() => {
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        },
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            field.push(c)
+                        },
+                        None => return Err(RuntimeError::InvalidFormat(
+                            format!("unterminated quoted csv field on line {}", line)))
+                    }
+                }
+                if !matches!(chars.peek(), None | Some('\n' | '\r')) && chars.peek() != Some(&delim) {
+                    return Err(RuntimeError::InvalidFormat(
+                        format!("unexpected character after a quoted csv field on line {}", line)))
+                }
+            },
+            c if c == delim => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {},
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                pending = false;
+                line += 1;
+            },
+            c => field.push(c)
+        }
+    }
+    if pending {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// `parse(text, header, delim)` gives an array of rows, each an array of
+/// strings. with a truthy `header`, the first row names the fields and
+/// every other row becomes a map from those names
+fn parse(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 3)?;
+    let text = vals[0].string()?;
+    let header = vals.get(1).is_some_and(|h| h.truthy());
+    let rows = rows(&text.borrow(), delimiter(&vals, 2)?)?;
+
+    let to_array = |row: Vec<String>| Value::Array(Handle::new(row.into_iter().map(Value::from).collect()));
+    if !header {
+        return Ok(Value::Array(Handle::new(rows.into_iter().map(to_array).collect())))
+    }
+    let mut rows = rows.into_iter();
+    let names = rows.next().unwrap_or_default();
+    let maps = rows
+        .map(|row| {
+            // missing fields are nil, and extra ones are dropped
+            let mut row = row.into_iter();
+            let pairs: Vec<(Value, Value)> = names.iter()
+                .map(|n| (n.as_str().into(), row.next().map(Value::from).unwrap_or(Value::Nil)))
+                .collect();
+            Value::Map(Handle::new(pairs.into_iter().collect()))
+        })
+        .collect();
+    Ok(Value::Array(Handle::new(maps)))
+}
+
+fn write_field(out: &mut String, field: &str, delim: char) {
+    if field.contains([delim, '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(field);
+    }
+}
+
+/// `stringify(rows, delim)` turns an array of arrays into csv text, quoting
+/// fields where needed. nil fields are written empty
+fn stringify(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let rows = vals[0].array()?;
+    let delim = delimiter(&vals, 1)?;
+    let mut out = String::new();
+    for row in rows.borrow().iter() {
+        let row = row.array()?;
+        for (i, field) in row.borrow().iter().enumerate() {
+            if i > 0 {
+                out.push(delim);
+            }
+            match field {
+                Value::Nil => {},
+                field => write_field(&mut out, &field.to_string(), delim)
+            }
+        }
+        out.push('\n');
+    }
+    Ok(out.into())
+}
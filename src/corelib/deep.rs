@@ -0,0 +1,102 @@
+//! `clone` and `deep_eq`, which follow arrays and maps all the way down.
+//! both keep track of the containers they've seen, so values that
+//! contain themselves are handled.
+
+use std::collections::HashSet;
+
+use super::*;
+
+/// the address of a container, which identifies it while it's being walked
+fn addr<T>(handle: &Handle<T>) -> usize {
+    &**handle as *const _ as *const () as usize
+}
+
+/// `clone(val)` copies strings, arrays, maps and bytes, along with everything
+/// inside them. a container that appears several times is copied once, so
+/// the copy shares its structure the same way. map keys aren't copied
+pub fn clone(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(deep_clone(&vals[0], &mut HashMap::new()))
+}
+
+fn deep_clone(val: &Value, copies: &mut HashMap<usize, Value>) -> Value {
+    let key = match val {
+        Value::String(s) => addr(s),
+        Value::Array(a) => addr(a),
+        Value::Map(m) => addr(m),
+        Value::Bytes(b) => addr(b),
+        val => return val.clone()
+    };
+    if let Some(copy) = copies.get(&key) {
+        return copy.clone()
+    }
+    match val {
+        Value::String(s) => {
+            let copy = Value::String(Handle::new(s.borrow().clone()));
+            copies.insert(key, copy.clone());
+            copy
+        },
+        Value::Bytes(b) => {
+            let copy = Value::Bytes(Handle::new(b.borrow().clone()));
+            copies.insert(key, copy.clone());
+            copy
+        },
+        Value::Array(a) => {
+            // recorded before the elements are copied, so a cycle finds it
+            let handle = Handle::new(Vec::new());
+            copies.insert(key, Value::Array(handle.clone()));
+            let items = a.borrow().clone();
+            let items = items.iter().map(|v| deep_clone(v, copies)).collect();
+            *handle.borrow_mut() = items;
+            Value::Array(handle)
+        },
+        Value::Map(m) => {
+            let handle = Handle::new(HashMap::new());
+            copies.insert(key, Value::Map(handle.clone()));
+            let pairs: Vec<(Value, Value)> = m.borrow().iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            for (k, v) in pairs {
+                let v = deep_clone(&v, copies);
+                handle.borrow_mut().insert(k, v);
+            }
+            Value::Map(handle)
+        },
+        _ => unreachable!()
+    }
+}
+
+/// `deep_eq(a, b)` compares the contents of arrays and maps recursively,
+/// like `==`, but also finishes on values that contain themselves
+pub fn deep_eq(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    Ok(Value::Bool(equal(&vals[0], &vals[1], &mut HashSet::new())))
+}
+
+/// `assumed` holds the pairs of containers being compared further up,
+/// which are taken to be equal so a cycle ends the comparison
+fn equal(a: &Value, b: &Value, assumed: &mut HashSet<(usize, usize)>) -> bool {
+    match (a, b) {
+        (Value::Array(x), Value::Array(y)) => {
+            if x.ptr_eq(y) || !assumed.insert((addr(x), addr(y))) {
+                return true
+            }
+            let (x, y) = (x.borrow().clone(), y.borrow().clone());
+            x.len() == y.len() && x.iter().zip(&y).all(|(a, b)| equal(a, b, assumed))
+        },
+        (Value::Map(x), Value::Map(y)) => {
+            if x.ptr_eq(y) || !assumed.insert((addr(x), addr(y))) {
+                return true
+            }
+            let pairs: Vec<(Value, Option<Value>)> = {
+                let (x, y) = (x.borrow(), y.borrow());
+                if x.len() != y.len() {
+                    return false
+                }
+                x.iter().map(|(k, v)| (v.clone(), y.get(k).cloned())).collect()
+            };
+            pairs.iter().all(|(v, other)| other.as_ref().is_some_and(|o| equal(v, o, assumed)))
+        },
+        _ => a == b
+    }
+}
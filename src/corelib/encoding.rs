@@ -0,0 +1,93 @@
+//! Base64 (the standard alphabet, padded) and hex encodings of a
+//! string's UTF-8 bytes. decoding fails if the bytes aren't valid UTF-8.
+
+use super::*;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encoding() -> Value {
+    let mut encoding = NativeLibModule::new();
+
+    encoding.export_fn("base64_encode", base64_encode);
+    encoding.export_fn("base64_decode", base64_decode);
+    encoding.export_fn("hex_encode", hex_encode);
+    encoding.export_fn("hex_decode", hex_decode);
+
+    encoding.into()
+}
+
+fn invalid(what: &str) -> RuntimeError {
+    RuntimeError::InvalidFormat(format!("invalid {}", what))
+}
+
+fn decoded_string(bytes: Vec<u8>) -> Result<Value, RuntimeError> {
+    String::from_utf8(bytes)
+        .map(Value::from)
+        .map_err(|_| RuntimeError::InvalidFormat("decoded data is not valid UTF-8".to_string()))
+}
+
+fn base64_encode(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let data = vals[0].data()?;
+    let mut out = String::new();
+    for chunk in data.chunks(3) {
+        let n = chunk.iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    Ok(out.into())
+}
+
+/// accepts input with or without padding
+fn base64_decode(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let s = s.borrow();
+    let data = s.trim_end_matches('=');
+    let mut bytes = Vec::new();
+    for chunk in data.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return Err(invalid("base64"))
+        }
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let digit = BASE64.iter().position(|b| b == c).ok_or_else(|| invalid("base64"))?;
+            n |= (digit as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    decoded_string(bytes)
+}
+
+fn hex_encode(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let hex: String = vals[0].data()?.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(hex.into())
+}
+
+/// accepts either case
+fn hex_decode(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let s = s.borrow();
+    if s.len() % 2 != 0 {
+        return Err(invalid("hex"))
+    }
+    let bytes = s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| invalid("hex"))?;
+            u8::from_str_radix(pair, 16).map_err(|_| invalid("hex"))
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    decoded_string(bytes)
+}
@@ -0,0 +1,58 @@
+//! Structured errors. `error.new(kind, message, data)` makes an error value
+//! like the ones `catch` binds for the VM's own errors, so the same
+//! functions inspect both. throwing an error value raises it as it is,
+//! and any other thrown value has the kind `thrown`.
+
+use super::*;
+
+pub fn error() -> Value {
+    let mut error = NativeLibModule::new();
+
+    error.export_fn("new", new);
+    error.export_fn("kind", kind);
+    error.export_fn("message", message);
+    error.export_fn("data", data);
+
+    error.into()
+}
+
+/// `new(kind)`, `new(kind, message)` or `new(kind, message, data)`,
+/// where `data` is any value to carry along with the error
+fn new(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 3)?;
+    let kind = vals[0].string()?.borrow().clone();
+    let message = match vals.get(1) {
+        Some(m) => m.to_string(),
+        None => kind.clone()
+    };
+    let data = vals.get(2).cloned().unwrap_or(Value::Nil);
+    Ok(Value::Error(Rc::new(RuntimeError::Custom { kind, message, data })))
+}
+
+/// the error behind a caught value. values thrown with `throw`
+/// are caught as themselves, so they're treated as thrown again
+fn caught(val: &Value) -> Rc<RuntimeError> {
+    match val.error() {
+        Ok(e) => e,
+        Err(_) => Rc::new(RuntimeError::Thrown(val.clone()))
+    }
+}
+
+fn kind(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(caught(&vals[0]).kind().into())
+}
+
+fn message(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(caught(&vals[0]).message().into())
+}
+
+fn data(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(match caught(&vals[0]).inner() {
+        RuntimeError::Custom { data, .. } => data.clone(),
+        RuntimeError::Thrown(v) => v.clone(),
+        _ => Value::Nil
+    })
+}
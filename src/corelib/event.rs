@@ -0,0 +1,165 @@
+//! The event loop, which lets slow work overlap instead of each piece
+//! blocking the interpreter in turn. functions like `fs.read_async` start
+//! their work on another thread and return a task right away, which is an
+//! int handle. `after(ms, f)` makes a task that calls `f` once `ms` have
+//! passed. `await(task)` runs the loop until the task is done, returning
+//! its result, and anything else that comes due in the meantime runs too.
+//! once a script finishes, the loop runs until nothing is left.
+//!
+//! a task's result is kept until it's awaited. an error in a timer's
+//! callback is raised from whichever `await` happened to call it
+
+use std::{panic::{self, AssertUnwindSafe}, sync::mpsc::{self, Receiver, RecvTimeoutError, Sender}, time::Instant};
+
+use crate::function::Callable;
+
+use super::{thread::{Failure, Portable}, *};
+
+enum Task {
+    /// waiting to call the function once the time comes
+    Timer(Instant, Rc<dyn Callable>),
+    /// a timer whose function is being called
+    Running,
+    /// work started on another thread by `Events::start`
+    Job,
+    Done(Result<Value, RuntimeError>)
+}
+
+type Finished = (i64, Result<Portable, Failure>);
+
+/// the tasks of a VM, and the way its jobs report back
+pub struct Events {
+    tasks: HashMap<i64, Task>,
+    next: i64,
+    sender: Sender<Finished>,
+    finished: Receiver<Finished>
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        let (sender, finished) = mpsc::channel();
+        Self { tasks: HashMap::new(), next: 0, sender, finished }
+    }
+}
+
+impl Events {
+    fn add(&mut self, task: Task) -> i64 {
+        let id = self.next;
+        self.next += 1;
+        self.tasks.insert(id, task);
+        id
+    }
+
+    /// runs `job` on another thread, returning the task that awaits it
+    pub(super) fn start(&mut self, job: impl FnOnce() -> Result<Portable, Failure> + Send + 'static) -> Value {
+        let id = self.add(Task::Job);
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let out = panic::catch_unwind(AssertUnwindSafe(job)).unwrap_or_else(|_| Err(Failure {
+                kind: "job_panicked".to_owned(),
+                message: format!("task {} panicked", id)
+            }));
+            // the VM may be gone by the time the job is done
+            let _ = sender.send((id, out));
+        });
+        Value::Int(id)
+    }
+
+    /// the earliest timer still to fire
+    fn next_timer(&self) -> Option<(Instant, i64)> {
+        self.tasks.iter()
+            .filter_map(|(id, task)| match task {
+                Task::Timer(at, _) => Some((*at, *id)),
+                _ => None
+            })
+            .min()
+    }
+
+    /// whether any timer or job is still to finish
+    fn waiting(&self) -> bool {
+        self.tasks.values().any(|t| matches!(t, Task::Timer(..) | Task::Job))
+    }
+}
+
+fn finish(vm: &mut VM, (id, out): Finished) {
+    let out = match out {
+        Ok(out) => out.into_value(vm),
+        Err(failure) => Err(failure.into_error())
+    };
+    vm.events.tasks.insert(id, Task::Done(out));
+}
+
+/// fires the timers that are due and takes in the jobs that are done,
+/// first waiting for one of them if nothing is ready yet
+fn turn(vm: &mut VM) -> Result<(), RuntimeError> {
+    let mut ready = false;
+    while let Ok(finished) = vm.events.finished.try_recv() {
+        finish(vm, finished);
+        ready = true;
+    }
+    while let Some((at, id)) = vm.events.next_timer().filter(|(at, _)| *at <= Instant::now()) {
+        let Some(Task::Timer(_, func)) = vm.events.tasks.insert(id, Task::Running) else {
+            unreachable!("timer {} at {:?} went missing", id, at)
+        };
+        let out = func.call(vm, vec![]);
+        let out = out.inspect_err(|_| { vm.events.tasks.remove(&id); })?;
+        vm.events.tasks.insert(id, Task::Done(Ok(out)));
+        ready = true;
+    }
+    if ready {
+        return Ok(())
+    }
+    let finished = match vm.events.next_timer() {
+        Some((at, _)) => match vm.events.finished.recv_timeout(at.saturating_duration_since(Instant::now())) {
+            Ok(finished) => finished,
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => unreachable!("the events hold a sender")
+        },
+        None => vm.events.finished.recv().expect("the events hold a sender")
+    };
+    finish(vm, finished);
+    Ok(())
+}
+
+/// runs the event loop until every timer has fired and every job is done
+pub fn run_events(vm: &mut VM) -> Result<(), RuntimeError> {
+    while vm.events.waiting() {
+        turn(vm)?;
+    }
+    Ok(())
+}
+
+/// `after(ms, f)` calls `f` once `ms` milliseconds have passed, returning its task
+pub fn after(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let ms = vals[0].num()?;
+    let at = Duration::try_from_secs_f64(ms / 1000.0).ok()
+        .and_then(|wait| Instant::now().checked_add(wait));
+    let Some(at) = at else {
+        return Err(RuntimeError::InvalidArgument(format!("can't wait {} milliseconds", ms)))
+    };
+    let id = vm.events.add(Task::Timer(at, vals[1].func()?));
+    Ok(Value::Int(id))
+}
+
+/// `await(task)` runs the event loop until the task is done, returning its
+/// result or raising its error. each task can only be awaited once
+pub fn await_task(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let id = vals[0].int()?;
+    loop {
+        match vm.events.tasks.get(&id) {
+            None => return Err(RuntimeError::InvalidArgument(format!("{} is not a task", id))),
+            Some(Task::Done(_)) => {
+                let Some(Task::Done(out)) = vm.events.tasks.remove(&id) else {
+                    unreachable!("task {} was just done", id)
+                };
+                return out
+            },
+            // a timer awaiting itself can't finish
+            Some(Task::Running) if !vm.events.waiting() => return Err(RuntimeError::InvalidArgument(
+                format!("task {} would never finish", id))),
+            Some(_) => turn(vm)?
+        }
+    }
+}
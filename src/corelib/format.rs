@@ -0,0 +1,186 @@
+//! `format("x={}, y={:.2}", x, y)` fills each `{}` with the next argument.
+//! a placeholder may name an argument by position, `{1}`, and take a spec
+//! after a colon: `[[fill]align][+][0][width][.precision][type]`, where
+//! align is `<`, `>` or `^` and type is `x`, `X`, `o`, `b` or `e`.
+//! `{{` and `}}` write literal braces.
+
+use super::*;
+
+#[derive(Default)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    sign: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Option<char>
+}
+
+/// the widest a placeholder can be padded to, and the most digits it can
+/// show after the point. more is surely a mistake, and padding to it could
+/// take more memory than there is
+const MAX_WIDTH: usize = 1 << 16;
+
+fn error(message: &str) -> RuntimeError {
+    RuntimeError::InvalidFormat(format!("invalid format string: {}", message))
+}
+
+pub fn format(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if vals.is_empty() {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let template = vals[0].string()?;
+    let template = template.borrow();
+    let args = &vals[1..];
+
+    let mut out = String::new();
+    let mut next = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            },
+            '}' => return Err(error("unmatched `}`")),
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(error("unterminated `{`"))
+                    }
+                }
+                let (index, spec) = match placeholder.split_once(':') {
+                    Some((index, spec)) => (index, spec),
+                    None => (placeholder.as_str(), "")
+                };
+                let index = if index.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    index.trim().parse().map_err(|_| error(&format!("bad argument index `{}`", index)))?
+                };
+                let Some(arg) = args.get(index) else {
+                    return Err(RuntimeError::InvalidArgument(
+                        format!("format has no argument {}", index)))
+                };
+                write_arg(&mut out, arg, &parse_spec(spec)?)?;
+            },
+            c => out.push(c)
+        }
+    }
+    Ok(out.into())
+}
+
+fn parse_spec(spec: &str) -> Result<Spec, RuntimeError> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut at = 0;
+    let mut out = Spec::default();
+    let is_align = |c: Option<&char>| matches!(c, Some('<' | '>' | '^'));
+    if is_align(chars.get(1)) {
+        out.fill = Some(chars[0]);
+        out.align = Some(chars[1]);
+        at = 2;
+    } else if is_align(chars.first()) {
+        out.align = Some(chars[0]);
+        at = 1;
+    }
+    if chars.get(at) == Some(&'+') {
+        out.sign = true;
+        at += 1;
+    }
+    if chars.get(at) == Some(&'0') {
+        out.zero = true;
+        at += 1;
+    }
+    let digits = |at: &mut usize| {
+        let start = *at;
+        while chars.get(*at).is_some_and(|c| c.is_ascii_digit()) {
+            *at += 1;
+        }
+        // too many digits for a usize is still too wide
+        (*at > start).then(|| chars[start..*at].iter().collect::<String>().parse::<usize>().unwrap_or(usize::MAX))
+    };
+    out.width = digits(&mut at).unwrap_or(0);
+    if chars.get(at) == Some(&'.') {
+        at += 1;
+        out.precision = Some(digits(&mut at).ok_or_else(|| error("expected a precision after `.`"))?);
+    }
+    if out.width > MAX_WIDTH || out.precision.is_some_and(|p| p > MAX_WIDTH) {
+        return Err(error(&format!("width and precision can be at most {}", MAX_WIDTH)))
+    }
+    if let Some(c @ ('x' | 'X' | 'o' | 'b' | 'e')) = chars.get(at) {
+        out.kind = Some(*c);
+        at += 1;
+    }
+    if at != chars.len() {
+        return Err(error(&format!("unknown spec `{}`", spec)))
+    }
+    Ok(out)
+}
+
+fn write_arg(out: &mut String, arg: &Value, spec: &Spec) -> Result<(), RuntimeError> {
+    let numeric = matches!(arg, Value::Int(_) | Value::Number(_));
+    let mut text = match (spec.kind, arg) {
+        (Some(kind @ ('x' | 'X' | 'o' | 'b')), _) => {
+            let n = arg.int()?;
+            let digits = match kind {
+                'x' => format!("{:x}", n.unsigned_abs()),
+                'X' => format!("{:X}", n.unsigned_abs()),
+                'o' => format!("{:o}", n.unsigned_abs()),
+                _ => format!("{:b}", n.unsigned_abs())
+            };
+            if n < 0 { format!("-{}", digits) } else { digits }
+        },
+        (Some(_), _) => match spec.precision {
+            Some(p) => format!("{:.*e}", p, arg.num()?),
+            None => format!("{:e}", arg.num()?)
+        },
+        (None, Value::Int(_) | Value::Number(_)) => match spec.precision {
+            Some(p) => format!("{:.*}", p, arg.num()?),
+            None => arg.to_string()
+        },
+        // precision truncates anything that isn't a number
+        (None, _) => match spec.precision {
+            Some(p) => arg.to_string().chars().take(p).collect(),
+            None => arg.to_string()
+        }
+    };
+    if spec.sign && numeric && !text.starts_with('-') {
+        text.insert(0, '+');
+    }
+
+    let len = text.chars().count();
+    if len >= spec.width {
+        out.push_str(&text);
+        return Ok(())
+    }
+    let pad = spec.width - len;
+    // zero padding goes after the sign, and only applies without an alignment
+    if spec.zero && numeric && spec.align.is_none() {
+        let sign = if text.starts_with(['-', '+']) { text.remove(0).to_string() } else { String::new() };
+        out.push_str(&sign);
+        out.extend(std::iter::repeat_n('0', pad));
+        out.push_str(&text);
+        return Ok(())
+    }
+    let fill = spec.fill.unwrap_or(' ');
+    // numbers line up on the right by default, like rust
+    let align = spec.align.unwrap_or(if numeric { '>' } else { '<' });
+    let (before, after) = match align {
+        '<' => (0, pad),
+        '>' => (pad, 0),
+        _ => (pad / 2, pad - pad / 2)
+    };
+    out.extend(std::iter::repeat_n(fill, before));
+    out.push_str(&text);
+    out.extend(std::iter::repeat_n(fill, after));
+    Ok(())
+}
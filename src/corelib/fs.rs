@@ -0,0 +1,278 @@
+//! File system access. failures are returned as `RuntimeError::Io`,
+//! which scripts can catch with `try`.
+
+use std::{fs, io, path::{Path, PathBuf}};
+
+use super::{thread::{Failure, Portable}, *};
+
+pub fn fs() -> Value {
+    let mut fs = NativeLibModule::new();
+
+    fs.export_fn("read", read);
+    fs.export_fn("read_bytes", read_bytes);
+    fs.export_fn("read_async", read_async);
+    fs.export_fn("write", write);
+    fs.export_fn("append", append);
+    fs.export_fn("exists", exists);
+    fs.export_fn("remove", remove);
+    fs.export_fn("copy", copy);
+    fs.export_fn("rename", rename);
+    fs.export_fn("list_dir", list_dir);
+    fs.export_fn("create_dir", create_dir);
+    fs.export_fn("walk", walk);
+    fs.export_fn("glob", glob);
+
+    fs.into()
+}
+
+/// describes a failed operation on `path`
+fn io_error(action: &str, path: &str, e: io::Error) -> RuntimeError {
+    RuntimeError::Io(format!("failed to {} {}: {}", action, path, e))
+}
+
+fn read(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    let contents = fs::read_to_string(&path).map_err(|e| io_error("read", &path, e))?;
+    Ok(contents.into())
+}
+
+/// `read_async(path)` reads the file while the script carries on,
+/// returning a task whose result is the contents
+fn read_async(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    Ok(vm.events.start(move || fs::read_to_string(&path)
+        .map(Portable::String)
+        .map_err(|e| Failure::of(&io_error("read", &path, e)))))
+}
+
+fn read_bytes(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    let contents = fs::read(&path).map_err(|e| io_error("read", &path, e))?;
+    Ok(Value::Bytes(Handle::new(contents)))
+}
+
+/// replaces the file's contents, creating it if needed
+fn write(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let path = vals[0].string()?.borrow().clone();
+    let contents = payload(&vals[1]);
+    fs::write(&path, contents).map_err(|e| io_error("write", &path, e))?;
+    Ok(Value::Nil)
+}
+
+fn append(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let path = vals[0].string()?.borrow().clone();
+    let contents = payload(&vals[1]);
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(&contents))
+        .map_err(|e| io_error("append to", &path, e))?;
+    Ok(Value::Nil)
+}
+
+fn exists(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?;
+    let exists = Path::new(path.borrow().as_str()).exists();
+    Ok(Value::Bool(exists))
+}
+
+/// removes a file or an empty directory
+fn remove(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    let out = if Path::new(&path).is_dir() {
+        fs::remove_dir(&path)
+    } else {
+        fs::remove_file(&path)
+    };
+    out.map_err(|e| io_error("remove", &path, e))?;
+    Ok(Value::Nil)
+}
+
+fn copy(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let from = vals[0].string()?.borrow().clone();
+    let to = vals[1].string()?.borrow().clone();
+    fs::copy(&from, &to).map_err(|e| io_error("copy", &from, e))?;
+    Ok(Value::Nil)
+}
+
+fn rename(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let from = vals[0].string()?.borrow().clone();
+    let to = vals[1].string()?.borrow().clone();
+    fs::rename(&from, &to).map_err(|e| io_error("rename", &from, e))?;
+    Ok(Value::Nil)
+}
+
+/// the names of the directory's entries, sorted
+fn list_dir(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    let mut names = fs::read_dir(&path)
+        .and_then(|entries| entries
+            .map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, _>>())
+        .map_err(|e| io_error("list", &path, e))?;
+    names.sort();
+    let names = names.into_iter().map(Value::from).collect();
+    Ok(Value::Array(Handle::new(names)))
+}
+
+/// creates the directory along with any missing parents
+fn create_dir(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    fs::create_dir_all(&path).map_err(|e| io_error("create", &path, e))?;
+    Ok(Value::Nil)
+}
+
+fn path_string(path: &Path) -> Value {
+    path.to_string_lossy().into_owned().into()
+}
+
+/// the entries of `dir`, sorted, or none when it can't be read
+fn sorted_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+        Err(_) => Vec::new()
+    };
+    entries.sort();
+    entries
+}
+
+/// whether to descend into `path`. symlinks aren't followed, so a link
+/// back up the tree can't make a walk go on forever
+fn is_real_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+/// every file and directory below `dir`, sorted
+fn walk(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    if !Path::new(&path).is_dir() {
+        return Err(RuntimeError::Io(format!("failed to walk {}: not a directory", path)))
+    }
+    let mut found = Vec::new();
+    let mut stack = vec![PathBuf::from(&path)];
+    while let Some(dir) = stack.pop() {
+        for entry in sorted_entries(&dir) {
+            if is_real_dir(&entry) {
+                stack.push(entry.clone());
+            }
+            found.push(entry);
+        }
+    }
+    found.sort();
+    Ok(Value::Array(Handle::new(found.iter().map(|p| path_string(p)).collect())))
+}
+
+/// `glob("src/**/*.tr")` finds the paths matching a pattern, sorted.
+/// `*` matches within a name, `?` one character, `[abc]` or `[a-z]` one
+/// of a set, `[!abc]` one character outside it, and `**` any number of
+/// directories. wildcards don't match names starting with `.` unless the
+/// pattern does too
+fn glob(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let pattern = vals[0].string()?.borrow().clone();
+    let (base, parts) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (PathBuf::new(), pattern.as_str())
+    };
+    let parts: Vec<&str> = parts.split('/').filter(|p| !p.is_empty()).collect();
+    let mut found = Vec::new();
+    glob_in(&base, &parts, &mut found)?;
+    found.sort();
+    found.dedup();
+    Ok(Value::Array(Handle::new(found.iter().map(|p| path_string(p)).collect())))
+}
+
+fn glob_in(dir: &Path, parts: &[&str], found: &mut Vec<PathBuf>) -> Result<(), RuntimeError> {
+    let Some((part, rest)) = parts.split_first() else {
+        found.push(dir.to_path_buf());
+        return Ok(())
+    };
+    // reading `` would fail, so the current directory is spelled out
+    let listed = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+    if *part == "**" {
+        // zero directories, then each one below
+        glob_in(dir, rest, found)?;
+        for entry in sorted_entries(listed) {
+            let name = entry.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if is_real_dir(&entry) && !name.starts_with('.') {
+                glob_in(&dir.join(name), parts, found)?;
+            }
+        }
+        return Ok(())
+    }
+    if !part.contains(['*', '?', '[']) {
+        let path = dir.join(part);
+        if fs::symlink_metadata(&path).is_ok() && (rest.is_empty() || path.is_dir()) {
+            glob_in(&path, rest, found)?;
+        }
+        return Ok(())
+    }
+    let pattern: Vec<char> = part.chars().collect();
+    for entry in sorted_entries(listed) {
+        let Some(name) = entry.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+            continue
+        };
+        if name.starts_with('.') && !part.starts_with('.') {
+            continue
+        }
+        let name_chars: Vec<char> = name.chars().collect();
+        if wildcard_match(&pattern, &name_chars)? && (rest.is_empty() || entry.is_dir()) {
+            glob_in(&dir.join(&name), rest, found)?;
+        }
+    }
+    Ok(())
+}
+
+/// matches one path component against a pattern without `/`
+fn wildcard_match(pattern: &[char], name: &[char]) -> Result<bool, RuntimeError> {
+    match pattern.first() {
+        None => Ok(name.is_empty()),
+        Some('*') => {
+            for skip in 0..=name.len() {
+                if wildcard_match(&pattern[1..], &name[skip..])? {
+                    return Ok(true)
+                }
+            }
+            Ok(false)
+        },
+        Some('?') => Ok(!name.is_empty() && wildcard_match(&pattern[1..], &name[1..])?),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(2).position(|c| *c == ']').map(|i| i + 2) else {
+                return Err(RuntimeError::InvalidArgument("unclosed `[` in glob pattern".to_string()))
+            };
+            let Some(c) = name.first() else {
+                return Ok(false)
+            };
+            let (negated, set) = match pattern[1] {
+                '!' | '^' => (true, &pattern[2..end]),
+                _ => (false, &pattern[1..end])
+            };
+            let mut hit = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    hit |= (set[i]..=set[i + 2]).contains(c);
+                    i += 3;
+                } else {
+                    hit |= set[i] == *c;
+                    i += 1;
+                }
+            }
+            Ok(hit != negated && wildcard_match(&pattern[end + 1..], &name[1..])?)
+        },
+        Some(p) => Ok(name.first() == Some(p) && wildcard_match(&pattern[1..], &name[1..])?)
+    }
+}
@@ -0,0 +1,162 @@
+//! The `fn` module. Each helper that returns a function returns a native
+//! callable holding what it wraps, which calls back into the VM when used.
+
+use std::cell::RefCell;
+
+use crate::function::Callable;
+
+use super::*;
+
+pub fn func() -> Value {
+    let mut func = NativeLibModule::new();
+
+    func.export_fn("compose", compose);
+    func.export_fn("partial", partial);
+    func.export_fn("curry", curry);
+    func.export_fn("identity", identity);
+    func.export_fn("memoize", memoize);
+    func.export_fn("times", times);
+
+    func.into()
+}
+
+/// `compose(f, g)` is a function where `compose(f, g)(x)` is `f(g(x))`
+#[derive(Debug)]
+struct Composed(Vec<Rc<dyn Callable>>);
+
+impl Callable for Composed {
+    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        // the last function takes the arguments, and each before it the result
+        let mut funcs = self.0.iter().rev();
+        let mut out = match funcs.next() {
+            Some(f) => f.call(vm, vals)?,
+            None => return Ok(vals.into_iter().next().unwrap_or(Value::Nil))
+        };
+        for f in funcs {
+            out = f.call(vm, vec![out])?;
+        }
+        Ok(out)
+    }
+
+    fn display(&self) -> String {
+        "< composed func >".to_string()
+    }
+}
+
+fn compose(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let funcs = vals.iter().map(|v| v.func()).collect::<Result<_, _>>()?;
+    Ok(Value::Function(Rc::new(Composed(funcs))))
+}
+
+/// a function with its first arguments already given
+#[derive(Debug)]
+struct Partial {
+    func: Rc<dyn Callable>,
+    args: Vec<Value>
+}
+
+impl Callable for Partial {
+    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        let args = self.args.iter().cloned().chain(vals).collect();
+        self.func.call(vm, args)
+    }
+
+    fn display(&self) -> String {
+        format!("< partial {} >", self.func.display())
+    }
+}
+
+/// `partial(f, a, b)` returns a function that calls `f(a, b, ...)`
+fn partial(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let Some(func) = vals.first() else {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    };
+    let func = func.func()?;
+    Ok(Value::Function(Rc::new(Partial { func, args: vals[1..].to_vec() })))
+}
+
+/// collects arguments over any number of calls, calling
+/// the function once `arity` of them have been given
+#[derive(Debug)]
+struct Curried {
+    func: Rc<dyn Callable>,
+    arity: usize,
+    args: Vec<Value>
+}
+
+impl Callable for Curried {
+    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        let args: Vec<Value> = self.args.iter().cloned().chain(vals).collect();
+        if args.len() >= self.arity {
+            return self.func.call(vm, args)
+        }
+        Ok(Value::Function(Rc::new(Curried { func: self.func.clone(), arity: self.arity, args })))
+    }
+
+    fn display(&self) -> String {
+        format!("< curried {} >", self.func.display())
+    }
+}
+
+/// `curry(f)`, or `curry(f, n)` for natives and functions with
+/// defaults or rest parameters, where the count can't be inferred
+fn curry(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let func = vals[0].func()?;
+    let arity = match vals.get(1) {
+        Some(n) => n.int()?.max(0) as usize,
+        None => match func.as_function() {
+            Some(f) => f.params.len(),
+            None => return Err(RuntimeError::InvalidArgument(
+                "curry needs the number of arguments for a native function".to_string()))
+        }
+    };
+    Ok(Value::Function(Rc::new(Curried { func, arity, args: Vec::new() })))
+}
+
+fn identity(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(vals.into_iter().next().unwrap())
+}
+
+/// caches results by the arguments they were called with. arrays and
+/// maps hash by identity, so only the same one is sure to hit the cache
+#[derive(Debug)]
+struct Memoized {
+    func: Rc<dyn Callable>,
+    cache: RefCell<HashMap<Vec<Value>, Value>>
+}
+
+impl Callable for Memoized {
+    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        let cached = self.cache.borrow().get(&vals).cloned();
+        if let Some(val) = cached {
+            return Ok(val)
+        }
+        // the cache isn't borrowed during the call, which may recurse into this
+        let val = self.func.call(vm, vals.clone())?;
+        self.cache.borrow_mut().insert(vals, val.clone());
+        Ok(val)
+    }
+
+    fn display(&self) -> String {
+        format!("< memoized {} >", self.func.display())
+    }
+}
+
+fn memoize(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let func = vals[0].func()?;
+    Ok(Value::Function(Rc::new(Memoized { func, cache: RefCell::default() })))
+}
+
+/// `times(n, f)` calls `f(i)` for each `i` from 0 to n - 1, returning the results
+fn times(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let n = vals[0].int()?;
+    let func = vals[1].func()?;
+    let out = (0..n.max(0))
+        .map(|i| func.call(vm, vec![Value::Int(i)]))
+        .collect::<Result<_, _>>()?;
+    Ok(Value::Array(Handle::new(out)))
+}
@@ -0,0 +1,42 @@
+//! gzip compression. `compress` takes a string or bytes, and both
+//! functions return bytes, which `bytes.to_string` turns back into text.
+
+use std::io::{Read, Write as _};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+
+use super::*;
+
+pub fn gzip() -> Value {
+    let mut gzip = NativeLibModule::new();
+
+    gzip.export_fn("compress", compress);
+    gzip.export_fn("decompress", decompress);
+
+    gzip.into()
+}
+
+/// `compress(data)`, or `compress(data, level)` with a level from 0 to 9
+fn compress(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let data = vals[0].data()?;
+    let level = match vals.get(1) {
+        Some(level) => level.int()?.clamp(0, 9) as u32,
+        None => 6
+    };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+    let out = encoder.write_all(&data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| RuntimeError::Io(format!("failed to compress: {}", e)))?;
+    Ok(Value::Bytes(Handle::new(out)))
+}
+
+/// reads every member of the stream, like `gunzip` does
+fn decompress(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let data = vals[0].data()?;
+    let mut out = Vec::new();
+    MultiGzDecoder::new(&data[..]).read_to_end(&mut out)
+        .map_err(|e| RuntimeError::InvalidFormat(format!("invalid gzip data: {}", e)))?;
+    Ok(Value::Bytes(Handle::new(out)))
+}
@@ -0,0 +1,47 @@
+//! Digests of a string's UTF-8 bytes, or of a bytes value, as lowercase hex. md5 and sha1
+//! are only here for checksums and compatibility, not for security.
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use super::*;
+
+pub fn hash() -> Value {
+    let mut hash = NativeLibModule::new();
+
+    hash.export_fn("sha256", sha256);
+    hash.export_fn("sha1", sha1);
+    hash.export_fn("md5", md5);
+    hash.export_fn("crc32", crc32);
+
+    hash.into()
+}
+
+fn hex(bytes: &[u8]) -> Value {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>().into()
+}
+
+fn digest<D: Digest>(vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let out = D::digest(vals[0].data()?);
+    Ok(hex(&out))
+}
+
+fn sha256(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    digest::<Sha256>(vals)
+}
+
+fn sha1(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    digest::<Sha1>(vals)
+}
+
+fn md5(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    digest::<Md5>(vals)
+}
+
+fn crc32(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let crc = crc32fast::hash(&vals[0].data()?);
+    Ok(hex(&crc.to_be_bytes()))
+}
@@ -0,0 +1,326 @@
+//! A blocking HTTP/1.1 server for mock servers and local webhooks.
+//! `serve(port, handler)` calls `handler` with a map for each request:
+//! its `method`, `path`, `query` (a map of the decoded parameters),
+//! `headers` (with lowercase names) and `body`. the handler returns a
+//! map with any of `status`, `headers` and `body`, or just the body.
+//! connections are closed after each response.
+//!
+//! `get(url)` is a client for plain `http://` urls, returning a map of the
+//! response's `status`, `headers` (again lowercase) and `body`, and
+//! `get_async(url)` fetches it in the background, returning a task.
+
+use std::{io::{self, BufRead, BufReader, Read, Write as _}, net::{TcpListener, TcpStream}};
+
+use crate::function::Callable;
+
+use super::{thread::{Failure, Portable}, *};
+
+/// the most a request or response's head may take, and its body,
+/// so a bad client or server can't exhaust memory
+const MAX_HEAD: usize = 64 * 1024;
+const MAX_BODY: u64 = 16 * 1024 * 1024;
+
+pub fn http() -> Value {
+    let mut http = NativeLibModule::new();
+
+    http.export_fn("serve", serve);
+    http.export_fn("get", get);
+    http.export_fn("get_async", get_async);
+
+    http.into()
+}
+
+fn http_error(action: &str, e: io::Error) -> RuntimeError {
+    RuntimeError::Io(format!("failed to {}: {}", action, e))
+}
+
+/// `serve(port, handler)` serves forever on localhost, or
+/// `serve(port, handler, n)` stops after `n` requests. the port can
+/// also be a `"host:port"` string to listen elsewhere
+fn serve(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let addr = match &vals[0] {
+        Value::String(s) => s.borrow().clone(),
+        port => format!("127.0.0.1:{}", port.int()?)
+    };
+    let handler = vals[1].func()?;
+    let limit = vals.get(2).map(|n| n.int()).transpose()?;
+
+    let listener = TcpListener::bind(&addr)
+        .map_err(|e| http_error(&format!("listen on {}", addr), e))?;
+    let mut served = 0;
+    while limit.is_none_or(|n| served < n) {
+        let (stream, _) = listener.accept().map_err(|e| http_error("accept a connection", e))?;
+        served += 1;
+        // a client that goes away mid-request shouldn't stop the server
+        match respond(vm, &handler, stream) {
+            Err(e) if e.fatal() => return Err(e),
+            Err(e) => eprintln!("== http: {}", e.message()),
+            Ok(()) => {}
+        }
+    }
+    Ok(Value::Nil)
+}
+
+fn get(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let url = vals[0].string()?.borrow().clone();
+    fetch(&url)?.into_value(vm)
+}
+
+fn get_async(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let url = vals[0].string()?.borrow().clone();
+    Ok(vm.events.start(move || fetch(&url).map_err(|e| Failure::of(&e))))
+}
+
+/// makes a `GET` request, as HTTP/1.0 so the body isn't chunked
+fn fetch(url: &str) -> Result<Portable, RuntimeError> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        return Err(RuntimeError::InvalidArgument(format!("only http:// urls can be fetched, not {}", url)))
+    };
+    let (host, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/")
+    };
+    let addr = if host.contains(':') { host.to_string() } else { format!("{}:80", host) };
+    let mut stream = TcpStream::connect(&addr)
+        .map_err(|e| http_error(&format!("connect to {}", addr), e))?;
+    write!(stream, "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host)
+        .and_then(|_| stream.flush())
+        .map_err(|e| http_error("send a request", e))?;
+
+    let bad = || RuntimeError::Io(format!("bad response from {}", url));
+    let mut reader = BufReader::new(stream);
+    let head = read_head(&mut reader)
+        .map_err(|e| http_error("read a response", e))?
+        .ok_or_else(bad)?;
+    let status = head.first()
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(bad)?;
+
+    let mut headers = Vec::new();
+    let mut length = None;
+    for line in head.iter().skip(1) {
+        let (name, value) = line.split_once(':').ok_or_else(bad)?;
+        let (name, value) = (name.trim().to_ascii_lowercase(), value.trim().to_string());
+        if name == "content-length" {
+            length = Some(value.parse().map_err(|_| bad())?);
+        }
+        headers.push((Portable::String(name), Portable::String(value)));
+    }
+    if length.is_some_and(|n| n > MAX_BODY) {
+        return Err(RuntimeError::Io(format!("the response from {} is too large", url)))
+    }
+    let mut body = Vec::new();
+    // without a length, the body is everything up to the connection closing
+    reader.take(length.unwrap_or(MAX_BODY + 1)).read_to_end(&mut body)
+        .map_err(|e| http_error("read a response body", e))?;
+    if body.len() as u64 > MAX_BODY {
+        return Err(RuntimeError::Io(format!("the response from {} is too large", url)))
+    }
+
+    Ok(Portable::Map(vec![
+        (Portable::String("status".to_owned()), Portable::Int(status)),
+        (Portable::String("headers".to_owned()), Portable::Map(headers)),
+        (Portable::String("body".to_owned()), Portable::String(String::from_utf8_lossy(&body).into_owned()))
+    ]))
+}
+
+/// what gets written back to the client
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>
+}
+
+impl Response {
+    fn plain(status: u16, body: &str) -> Self {
+        Self { status, headers: Vec::new(), body: body.as_bytes().to_vec() }
+    }
+}
+
+fn respond(vm: &mut VM, handler: &Rc<dyn Callable>, stream: TcpStream) -> Result<(), RuntimeError> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| http_error("clone a connection", e))?);
+    let response = match read_request(&mut reader)? {
+        Ok(request) => match handler.call(vm, vec![request]).and_then(response_from) {
+            Ok(response) => response,
+            // running out of fuel or memory stops the server, not just the request
+            Err(e) if e.fatal() => return Err(e),
+            Err(e) => {
+                eprintln!("== http handler failed: {}: {}", e.kind(), e.message());
+                Response::plain(500, "internal server error")
+            }
+        },
+        Err(response) => response
+    };
+
+    let mut out = format!("HTTP/1.1 {} {}\r\n", response.status, reason(response.status));
+    let mut has_type = false;
+    for (name, value) in &response.headers {
+        has_type |= name.eq_ignore_ascii_case("content-type");
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !has_type {
+        out.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+    }
+    out.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", response.body.len()));
+    let mut stream = stream;
+    stream.write_all(out.as_bytes())
+        .and_then(|_| stream.write_all(&response.body))
+        .and_then(|_| stream.flush())
+        .map_err(|e| http_error("write a response", e))
+}
+
+/// the lines of a head, up to the blank line that ends it, or none if it's
+/// longer than `MAX_HEAD`. each line is read through a `take` of what's
+/// left, so not even a line that never ends can go past it
+fn read_head(reader: &mut BufReader<TcpStream>) -> io::Result<Option<Vec<String>>> {
+    let mut head = Vec::new();
+    let mut left = MAX_HEAD;
+    loop {
+        let mut line = String::new();
+        let n = reader.by_ref().take(left as u64).read_line(&mut line)?;
+        if n == left && !line.ends_with('\n') {
+            return Ok(None)
+        }
+        left -= n;
+        let line = line.trim_end_matches(['\r', '\n']).to_string();
+        if n == 0 || line.is_empty() {
+            return Ok(Some(head))
+        }
+        head.push(line);
+    }
+}
+
+/// the request as a map, or the response to send instead if it can't be served
+fn read_request(reader: &mut BufReader<TcpStream>) -> Result<Result<Value, Response>, RuntimeError> {
+    let bad = || Ok(Err(Response::plain(400, "bad request")));
+    let Some(head) = read_head(reader).map_err(|e| http_error("read a request", e))? else {
+        return Ok(Err(Response::plain(431, "request header fields too large")))
+    };
+    let Some(request_line) = head.first() else {
+        return bad()
+    };
+    let mut words = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (words.next(), words.next()) else {
+        return bad()
+    };
+
+    let mut headers = Vec::new();
+    let mut length = 0;
+    for line in &head[1..] {
+        let Some((name, value)) = line.split_once(':') else {
+            return bad()
+        };
+        let (name, value) = (name.trim().to_ascii_lowercase(), value.trim().to_string());
+        if name == "content-length" {
+            let Ok(n) = value.parse() else {
+                return bad()
+            };
+            if n > MAX_BODY {
+                return Ok(Err(Response::plain(413, "content too large")))
+            }
+            length = n;
+        }
+        headers.push((Value::from(name), Value::from(value)));
+    }
+    let mut body = Vec::new();
+    reader.take(length).read_to_end(&mut body).map_err(|e| http_error("read a request body", e))?;
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (target, "")
+    };
+    let query = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((k, v)) => (percent_decode(k).into(), percent_decode(v).into()),
+            None => (percent_decode(pair).into(), "".into())
+        })
+        .collect::<Vec<(Value, Value)>>();
+
+    let mut request = NativeLibModule::new();
+    request.export("method", method.into());
+    request.export("path", percent_decode(path).into());
+    request.export("query", Value::Map(Handle::new(query.into_iter().collect())));
+    request.export("headers", Value::Map(Handle::new(headers.into_iter().collect())));
+    request.export("body", String::from_utf8_lossy(&body).into_owned().into());
+    Ok(Ok(request.into()))
+}
+
+/// decodes `%xx` escapes, and `+` as a space
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            },
+            (b'+', _) => {
+                out.push(b' ');
+                i += 1;
+            },
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// the response a handler asked for
+fn response_from(response: Value) -> Result<Response, RuntimeError> {
+    let Value::Map(map) = &response else {
+        return Ok(Response { status: 200, headers: Vec::new(), body: payload(&response) })
+    };
+    let field = |name: &str| map.borrow().get(&Value::from(name)).cloned();
+    let status = match field("status") {
+        Some(status) => u16::try_from(status.int()?)
+            .ok()
+            .filter(|s| (100..1000).contains(s))
+            .ok_or_else(|| RuntimeError::InvalidArgument(format!("{} is not an http status", status)))?,
+        None => 200
+    };
+    let mut headers: Vec<(String, String)> = match field("headers") {
+        Some(headers) => headers.map()?.borrow().iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        None => Vec::new()
+    };
+    headers.sort();
+    let body = match field("body") {
+        Some(Value::Nil) | None => Vec::new(),
+        Some(body) => payload(&body)
+    };
+    Ok(Response { status, headers, body })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => ""
+    }
+}
@@ -0,0 +1,71 @@
+//! Reading stdin, so scripts can be used as filters in a pipeline.
+//! lines are returned without their line ending.
+
+use std::io::{self, BufRead, Read};
+
+use crate::iterator::Iterate;
+
+use super::*;
+
+pub fn io() -> Value {
+    let mut io = NativeLibModule::new();
+
+    io.export_fn("read_all", read_all);
+    io.export_fn("read_line", read_line);
+    io.export_fn("lines", lines);
+
+    io.into()
+}
+
+fn stdin_error(e: io::Error) -> RuntimeError {
+    RuntimeError::Io(format!("failed to read stdin: {}", e))
+}
+
+/// the next line of stdin, or none at the end of it
+fn next_line() -> Result<Option<String>, RuntimeError> {
+    let mut line = String::new();
+    let n = io::stdin().lock().read_line(&mut line).map_err(stdin_error)?;
+    if n == 0 {
+        return Ok(None)
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+/// everything left on stdin
+fn read_all(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    let mut s = String::new();
+    io::stdin().lock().read_to_string(&mut s).map_err(stdin_error)?;
+    Ok(s.into())
+}
+
+/// returns nil at the end of stdin, which `prompt` can't tell from an empty line
+fn read_line(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(next_line()?.map(Value::from).unwrap_or(Value::Nil))
+}
+
+/// reads stdin a line at a time as it's iterated over
+#[derive(Debug)]
+struct Lines;
+
+impl Iterate for Lines {
+    fn next(&self, _vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        Ok(next_line()?.map(Value::from))
+    }
+
+    fn display(&self) -> String {
+        "< stdin lines >".to_owned()
+    }
+}
+
+fn lines(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(Value::Iterator(Rc::new(Lines)))
+}
@@ -0,0 +1,269 @@
+//! Lazy sequences. each function takes anything a `for` loop can go over
+//! and returns an iterator, which does no work until it's iterated.
+//! `collect` runs one into an array.
+
+use std::cell::Cell;
+
+use crate::{function::Callable, iterator::Iterate};
+
+use super::*;
+
+pub fn iter() -> Value {
+    let mut iter = NativeLibModule::new();
+
+    iter.export_fn("of", of);
+    iter.export_fn("range", range);
+    iter.export_fn("map", map);
+    iter.export_fn("filter", filter);
+    iter.export_fn("take", take);
+    iter.export_fn("skip", skip);
+    iter.export_fn("zip", zip);
+    iter.export_fn("enumerate", enumerate);
+    iter.export_fn("collect", collect);
+
+    iter.into()
+}
+
+fn wrap(iter: impl Iterate + 'static) -> Value {
+    Value::Iterator(Rc::new(iter))
+}
+
+/// an iterator over any iterable value
+fn of(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(Value::Iterator(vals[0].iter()?))
+}
+
+#[derive(Debug)]
+struct Range {
+    /// none once the range has stepped past what an int holds
+    next: Cell<Option<i64>>,
+    end: Option<i64>,
+    step: i64
+}
+
+impl Iterate for Range {
+    fn next(&self, _vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        let Some(n) = self.next.get() else {
+            return Ok(None)
+        };
+        let done = match self.end {
+            Some(end) => if self.step > 0 { n >= end } else { n <= end },
+            None => false
+        };
+        if done {
+            return Ok(None)
+        }
+        self.next.set(n.checked_add(self.step));
+        Ok(Some(Value::Int(n)))
+    }
+
+    fn display(&self) -> String {
+        "< range >".to_owned()
+    }
+}
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`, counting
+/// up to but not including `end`. an `end` of nil makes it go on forever
+fn range(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 3)?;
+    let bound = |v: &Value| match v {
+        Value::Nil => Ok(None),
+        v => v.int().map(Some)
+    };
+    let (start, end) = match vals.len() {
+        1 => (0, bound(&vals[0])?),
+        _ => (vals[0].int()?, bound(&vals[1])?)
+    };
+    let step = match vals.get(2) {
+        Some(step) => step.int()?,
+        None => 1
+    };
+    if step == 0 {
+        return Err(RuntimeError::InvalidArgument("a range's step can't be 0".to_string()))
+    }
+    Ok(wrap(Range { next: Cell::new(Some(start)), end, step }))
+}
+
+#[derive(Debug)]
+struct Map {
+    source: Rc<dyn Iterate>,
+    func: Rc<dyn Callable>
+}
+
+impl Iterate for Map {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        match self.source.next(vm)? {
+            Some(v) => self.func.call(vm, vec![v]).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    fn display(&self) -> String {
+        "< map iterator >".to_owned()
+    }
+}
+
+fn map(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    Ok(wrap(Map { source: vals[0].iter()?, func: vals[1].func()? }))
+}
+
+#[derive(Debug)]
+struct Filter {
+    source: Rc<dyn Iterate>,
+    func: Rc<dyn Callable>
+}
+
+impl Iterate for Filter {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        while let Some(v) = self.source.next(vm)? {
+            if self.func.call(vm, vec![v.clone()])?.truthy() {
+                return Ok(Some(v))
+            }
+        }
+        Ok(None)
+    }
+
+    fn display(&self) -> String {
+        "< filter iterator >".to_owned()
+    }
+}
+
+fn filter(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    Ok(wrap(Filter { source: vals[0].iter()?, func: vals[1].func()? }))
+}
+
+#[derive(Debug)]
+struct Take {
+    source: Rc<dyn Iterate>,
+    left: Cell<usize>
+}
+
+impl Iterate for Take {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        // checked first, so the source isn't advanced past what's taken
+        if self.left.get() == 0 {
+            return Ok(None)
+        }
+        self.left.set(self.left.get() - 1);
+        self.source.next(vm)
+    }
+
+    fn display(&self) -> String {
+        "< take iterator >".to_owned()
+    }
+}
+
+/// the first `n` values
+fn take(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let n = vals[1].int()?.max(0) as usize;
+    Ok(wrap(Take { source: vals[0].iter()?, left: Cell::new(n) }))
+}
+
+#[derive(Debug)]
+struct Skip {
+    source: Rc<dyn Iterate>,
+    skip: Cell<usize>
+}
+
+impl Iterate for Skip {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        for _ in 0..self.skip.replace(0) {
+            if self.source.next(vm)?.is_none() {
+                return Ok(None)
+            }
+        }
+        self.source.next(vm)
+    }
+
+    fn display(&self) -> String {
+        "< skip iterator >".to_owned()
+    }
+}
+
+/// everything after the first `n` values
+fn skip(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let n = vals[1].int()?.max(0) as usize;
+    Ok(wrap(Skip { source: vals[0].iter()?, skip: Cell::new(n) }))
+}
+
+#[derive(Debug)]
+struct Zip {
+    sources: Vec<Rc<dyn Iterate>>,
+    done: Cell<bool>
+}
+
+impl Iterate for Zip {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        if self.done.get() {
+            return Ok(None)
+        }
+        let mut items = Vec::with_capacity(self.sources.len());
+        for source in &self.sources {
+            match source.next(vm)? {
+                Some(v) => items.push(v),
+                None => {
+                    self.done.set(true);
+                    return Ok(None)
+                }
+            }
+        }
+        Ok(Some(Value::Array(Handle::new(items))))
+    }
+
+    fn display(&self) -> String {
+        "< zip iterator >".to_owned()
+    }
+}
+
+/// `zip(a, b, ...)` gives arrays of one value from each,
+/// stopping at the end of the shortest
+fn zip(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if vals.is_empty() {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let sources = vals.iter().map(|v| v.iter()).collect::<Result<_, _>>()?;
+    Ok(wrap(Zip { sources, done: Cell::new(false) }))
+}
+
+#[derive(Debug)]
+struct Enumerate {
+    source: Rc<dyn Iterate>,
+    index: Cell<i64>
+}
+
+impl Iterate for Enumerate {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        let Some(v) = self.source.next(vm)? else {
+            return Ok(None)
+        };
+        let i = self.index.get();
+        self.index.set(i + 1);
+        Ok(Some(Value::Array(Handle::new(vec![Value::Int(i), v]))))
+    }
+
+    fn display(&self) -> String {
+        "< enumerate iterator >".to_owned()
+    }
+}
+
+/// gives `[index, value]` pairs
+fn enumerate(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(wrap(Enumerate { source: vals[0].iter()?, index: Cell::new(0) }))
+}
+
+/// runs the iterator to its end, returning its values as an array
+fn collect(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let source = vals[0].iter()?;
+    let mut items = Vec::new();
+    while let Some(v) = source.next(vm)? {
+        items.push(v);
+    }
+    Ok(Value::Array(Handle::new(items)))
+}
@@ -0,0 +1,345 @@
+//! JSON objects become maps and JSON arrays become arrays. whole numbers
+//! decode to ints, and everything else to floats.
+
+use std::fmt::Write as _;
+
+use super::*;
+
+/// how deeply values may nest, which also catches arrays that contain themselves
+const MAX_DEPTH: usize = 512;
+
+pub fn json() -> Value {
+    let mut json = NativeLibModule::new();
+
+    json.export_fn("parse", parse);
+    json.export_fn("stringify", stringify);
+
+    json.into()
+}
+
+fn parse(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let source = vals[0].string()?;
+    let chars: Vec<char> = source.borrow().chars().collect();
+    let mut parser = JsonParser { chars, at: 0 };
+    let val = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.at < parser.chars.len() {
+        return Err(parser.error("unexpected trailing characters"))
+    }
+    Ok(val)
+}
+
+/// `stringify(val)`, or `stringify(val, true)` to indent the output.
+/// object keys are sorted so the output is deterministic
+fn stringify(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let pretty = vals.get(1).is_some_and(|p| p.truthy());
+    Ok(to_json(&vals[0], pretty)?.into())
+}
+
+/// the value as JSON, indented if `pretty`
+pub fn to_json(val: &Value, pretty: bool) -> Result<String, RuntimeError> {
+    let mut out = String::new();
+    write_value(&mut out, val, pretty, 0)?;
+    Ok(out)
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    at: usize
+}
+
+impl JsonParser {
+    fn error(&self, message: &str) -> RuntimeError {
+        RuntimeError::InvalidFormat(format!("invalid json at character {}: {}", self.at, message))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.at).is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            self.at += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), RuntimeError> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.at += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", c)))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, val: Value) -> Result<Value, RuntimeError> {
+        let end = self.at + word.chars().count();
+        if self.chars.get(self.at..end).is_some_and(|s| s.iter().copied().eq(word.chars())) {
+            self.at = end;
+            Ok(val)
+        } else {
+            Err(self.error("unknown literal"))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, RuntimeError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"))
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => Ok(self.string()?.into()),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('n') => self.keyword("null", Value::Nil),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input"))
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, RuntimeError> {
+        self.at += 1;
+        let mut pairs = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.at += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                if self.peek() != Some('"') {
+                    return Err(self.error("expected a string key"))
+                }
+                let key = self.string()?;
+                self.expect(':')?;
+                let val = self.value(depth + 1)?;
+                pairs.push((key.into(), val));
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.at += 1,
+                    Some('}') => {
+                        self.at += 1;
+                        break
+                    },
+                    _ => return Err(self.error("expected `,` or `}`"))
+                }
+            }
+        }
+        Ok(Value::Map(Handle::new(pairs.into_iter().collect())))
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, RuntimeError> {
+        self.at += 1;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.at += 1;
+        } else {
+            loop {
+                items.push(self.value(depth + 1)?);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(',') => self.at += 1,
+                    Some(']') => {
+                        self.at += 1;
+                        break
+                    },
+                    _ => return Err(self.error("expected `,` or `]`"))
+                }
+            }
+        }
+        Ok(Value::Array(Handle::new(items)))
+    }
+
+    fn hex4(&mut self) -> Result<u32, RuntimeError> {
+        let digits: String = self.chars.get(self.at..self.at + 4)
+            .ok_or_else(|| self.error("incomplete unicode escape"))?
+            .iter()
+            .collect();
+        let n = u32::from_str_radix(&digits, 16)
+            .map_err(|_| self.error("invalid unicode escape"))?;
+        self.at += 4;
+        Ok(n)
+    }
+
+    fn string(&mut self) -> Result<String, RuntimeError> {
+        self.at += 1;
+        let mut s = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("unterminated string"))
+            };
+            self.at += 1;
+            match c {
+                '"' => return Ok(s),
+                '\\' => {
+                    let Some(e) = self.peek() else {
+                        return Err(self.error("unterminated string"))
+                    };
+                    self.at += 1;
+                    match e {
+                        '"' => s.push('"'),
+                        '\\' => s.push('\\'),
+                        '/' => s.push('/'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            // characters outside the basic plane come as surrogate pairs
+                            if (0xD800..0xDC00).contains(&code) {
+                                if self.chars.get(self.at..self.at + 2) != Some(&['\\', 'u']) {
+                                    return Err(self.error("unpaired surrogate"))
+                                }
+                                self.at += 2;
+                                let low = self.hex4()?;
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error("unpaired surrogate"))
+                                }
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                            }
+                            s.push(char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))?);
+                        },
+                        _ => return Err(self.error("unknown escape"))
+                    }
+                },
+                c if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                c => s.push(c)
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, RuntimeError> {
+        let start = self.at;
+        let digits = |p: &mut Self| {
+            let from = p.at;
+            while p.peek().is_some_and(|c| c.is_ascii_digit()) {
+                p.at += 1;
+            }
+            p.at > from
+        };
+        if self.peek() == Some('-') {
+            self.at += 1;
+        }
+        if self.peek() == Some('0') {
+            self.at += 1;
+        } else if !digits(self) {
+            return Err(self.error("expected digits"))
+        }
+        let mut whole = true;
+        if self.peek() == Some('.') {
+            self.at += 1;
+            whole = false;
+            if !digits(self) {
+                return Err(self.error("expected digits after `.`"))
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.at += 1;
+            whole = false;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.at += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("expected digits in exponent"))
+            }
+        }
+        let text: String = self.chars[start..self.at].iter().collect();
+        if whole {
+            if let Ok(i) = text.parse() {
+                return Ok(Value::Int(i))
+            }
+        }
+        match text.parse::<f64>() {
+            // json has no infinity, so one too big to hold is an error, not `inf`
+            Ok(n) if n.is_finite() => Ok(Value::Number(n)),
+            Ok(_) => Err(self.error("number out of range")),
+            Err(_) => Err(self.error("malformed number"))
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+fn newline(out: &mut String, pretty: bool, depth: usize) {
+    if pretty {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth));
+    }
+}
+
+fn write_value(out: &mut String, val: &Value, pretty: bool, depth: usize) -> Result<(), RuntimeError> {
+    if depth > MAX_DEPTH {
+        return Err(RuntimeError::CannotEncode("value is nested too deeply, or contains itself".to_string()))
+    }
+    match val {
+        Value::Nil => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Int(i) => out.push_str(&i.to_string()),
+        Value::Number(n) if n.is_finite() => out.push_str(&n.to_string()),
+        Value::Number(n) => return Err(RuntimeError::CannotEncode(format!("{} has no json representation", n))),
+        Value::String(s) => write_string(out, &s.borrow()),
+        Value::Array(a) => {
+            let a = a.borrow();
+            out.push('[');
+            for (i, item) in a.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, pretty, depth + 1);
+                write_value(out, item, pretty, depth + 1)?;
+            }
+            if !a.is_empty() {
+                newline(out, pretty, depth);
+            }
+            out.push(']');
+        },
+        Value::Map(m) => {
+            let m = m.borrow();
+            let mut pairs: Vec<(String, &Value)> = m.iter().map(|(k, v)| (k.to_string(), v)).collect();
+            pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push('{');
+            for (i, (k, v)) in pairs.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, pretty, depth + 1);
+                write_string(out, k);
+                out.push(':');
+                if pretty {
+                    out.push(' ');
+                }
+                write_value(out, v, pretty, depth + 1)?;
+            }
+            if !pairs.is_empty() {
+                newline(out, pretty, depth);
+            }
+            out.push('}');
+        },
+        Value::Bytes(_) | Value::Function(_) | Value::Error(_) | Value::Iterator(_) | Value::UserData(_) => {
+            return Err(RuntimeError::CannotEncode(format!("a {} has no json representation", Type::of(val).name())))
+        }
+    }
+    Ok(())
+}
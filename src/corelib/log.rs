@@ -0,0 +1,138 @@
+//! Leveled logging to stderr. each line has a UTC timestamp and the
+//! level, and a map given as the last argument is written as `key=value`
+//! fields after the message. the minimum level starts as `TRAM_LOG`
+//! from the environment, or `info`.
+
+use std::io::Write as _;
+
+use super::{time::{strftime, unix_now}, *};
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    /// above every level, so nothing is logged
+    Off
+}
+
+impl Level {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "debug" => Self::Debug,
+            "info" => Self::Info,
+            "warn" => Self::Warn,
+            "error" => Self::Error,
+            "off" => Self::Off,
+            _ => return None
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+            Self::Off => "off"
+        }
+    }
+}
+
+/// the settings behind the `log` module
+#[derive(Debug)]
+pub struct Logger {
+    level: Level,
+    timestamps: bool
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        let level = std::env::var("TRAM_LOG").ok()
+            .and_then(|l| Level::from_name(&l))
+            .unwrap_or(Level::Info);
+        Self { level, timestamps: true }
+    }
+}
+
+pub fn log() -> Value {
+    let mut log = NativeLibModule::new();
+
+    log.export_fn("debug", debug);
+    log.export_fn("info", info);
+    log.export_fn("warn", warn);
+    log.export_fn("error", error);
+    log.export_fn("set_level", set_level);
+    log.export_fn("level", level);
+    log.export_fn("set_timestamps", set_timestamps);
+
+    log.into()
+}
+
+fn write(vm: &mut VM, level: Level, mut vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if level < vm.logger.level {
+        return Ok(Value::Nil)
+    }
+    let fields = match vals.last() {
+        Some(Value::Map(m)) => {
+            let mut fields: Vec<String> = m.borrow().iter()
+                .map(|(k, v)| format!("{}={:?}", k, v))
+                .collect();
+            fields.sort();
+            vals.pop();
+            fields
+        },
+        _ => Vec::new()
+    };
+
+    let mut line = String::new();
+    if vm.logger.timestamps {
+        line.push_str(&strftime(unix_now()?, "%Y-%m-%dT%H:%M:%S.%fZ "));
+    }
+    line.push_str(&format!("{:<5}", level.name().to_ascii_uppercase()));
+    for val in vals.iter().map(Value::to_string).chain(fields) {
+        line.push(' ');
+        line.push_str(&val);
+    }
+    // a closed stderr isn't worth failing the script over
+    let _ = writeln!(std::io::stderr(), "{}", line);
+    Ok(Value::Nil)
+}
+
+fn debug(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    write(vm, Level::Debug, vals)
+}
+
+fn info(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    write(vm, Level::Info, vals)
+}
+
+fn warn(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    write(vm, Level::Warn, vals)
+}
+
+fn error(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    write(vm, Level::Error, vals)
+}
+
+/// `set_level("warn")` hides anything less severe. `"off"` hides everything
+fn set_level(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let name = vals[0].string()?;
+    let name = name.borrow();
+    vm.logger.level = Level::from_name(&name)
+        .ok_or_else(|| RuntimeError::InvalidArgument(format!("unknown log level `{}`", name)))?;
+    Ok(Value::Nil)
+}
+
+fn level(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(vm.logger.level.name().into())
+}
+
+fn set_timestamps(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    vm.logger.timestamps = vals[0].truthy();
+    Ok(Value::Nil)
+}
@@ -0,0 +1,113 @@
+//! Like the array module, functions that change a map do so in place.
+
+use super::*;
+
+pub fn map() -> Value {
+    let mut map = NativeLibModule::new();
+
+    map.export_fn("new", new);
+    map.export_fn("get", get);
+    map.export_fn("set", set);
+    map.export_fn("keys", keys);
+    map.export_fn("values", values);
+    map.export_fn("has", has);
+    map.export_fn("remove", remove);
+    map.export_fn("len", len);
+    map.export_fn("merge", merge);
+    map.export_fn("entries", entries);
+    map.export_fn("clear", clear);
+
+    map.into()
+}
+
+/// `new(k1, v1, k2, v2, ...)` builds a map from pairs of arguments
+fn new(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if !vals.len().is_multiple_of(2) {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let mut vals = vals.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(k), Some(v)) = (vals.next(), vals.next()) {
+        pairs.push((k, v));
+    }
+    Ok(Value::Map(Handle::new(pairs.into_iter().collect())))
+}
+
+/// the value at the key, or the default (nil if not given) when it's missing
+fn get(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let m = vals[0].map()?;
+    let val = m.borrow().get(&vals[1]).cloned();
+    Ok(val.or_else(|| vals.get(2).cloned()).unwrap_or(Value::Nil))
+}
+
+fn set(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let m = vals[0].map()?;
+    m.borrow_mut().insert(vals[1].clone(), vals[2].clone());
+    Ok(Value::Nil)
+}
+
+fn keys(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let m = vals[0].map()?;
+    let keys = m.borrow().keys().cloned().collect();
+    Ok(Value::Array(Handle::new(keys)))
+}
+
+fn values(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let m = vals[0].map()?;
+    let values = m.borrow().values().cloned().collect();
+    Ok(Value::Array(Handle::new(values)))
+}
+
+fn has(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let m = vals[0].map()?;
+    let has = m.borrow().contains_key(&vals[1]);
+    Ok(Value::Bool(has))
+}
+
+/// removes the key, returning its value or nil if it wasn't there
+fn remove(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let m = vals[0].map()?;
+    let removed = m.borrow_mut().remove(&vals[1]);
+    Ok(removed.unwrap_or(Value::Nil))
+}
+
+fn len(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let m = vals[0].map()?;
+    let len = m.borrow().len();
+    Ok(Value::Int(len as i64))
+}
+
+/// a new map with the entries of every argument,
+/// where later maps win when keys clash
+fn merge(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut pairs = Vec::new();
+    for m in &vals {
+        let m = m.map()?;
+        pairs.extend(m.borrow().iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    Ok(Value::Map(Handle::new(pairs.into_iter().collect())))
+}
+
+/// the entries as an array of `[key, value]` arrays
+fn entries(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let m = vals[0].map()?;
+    let entries = m.borrow().iter()
+        .map(|(k, v)| Value::Array(Handle::new(vec![k.clone(), v.clone()])))
+        .collect();
+    Ok(Value::Array(Handle::new(entries)))
+}
+
+fn clear(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let m = vals[0].map()?;
+    m.borrow_mut().clear();
+    Ok(Value::Nil)
+}
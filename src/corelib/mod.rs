@@ -1,6 +1,88 @@
-use std::{collections::HashMap, fs, io::Write, process, rc::Rc, thread, time::Duration};
+use std::{cmp::Ordering, collections::HashMap, io::Write, path::Path, rc::Rc, time::Duration};
 
-use crate::{executor::{RuntimeError, VM}, fe::ast::Ast, function::NativeFunction, handle::Handle, value::Value};
+use crate::{bytecode, executor::{RuntimeError, VM}, fe::ast::{Ast, Type}, function::NativeFunction, handle::Handle, value::Value};
+
+mod array;
+mod bytes;
+mod coroutine;
+mod csv;
+mod deep;
+mod encoding;
+mod error;
+mod event;
+mod format;
+mod fs;
+mod func;
+mod gzip;
+mod hash;
+mod http;
+mod io;
+mod iter;
+mod json;
+mod log;
+mod map;
+mod net;
+mod os;
+mod path;
+mod process;
+mod random;
+mod regex;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod string;
+mod term;
+mod test;
+mod thread;
+mod time;
+mod uuid;
+mod weak;
+#[cfg(feature = "toml")]
+mod toml;
+#[cfg(feature = "yaml")]
+mod yaml;
+#[cfg(feature = "zstd")]
+mod zstd;
+
+pub use array::array;
+pub use bytes::bytes;
+pub use coroutine::{coroutine, Coroutine};
+pub use csv::csv;
+pub use deep::{clone, deep_eq};
+pub use encoding::encoding;
+pub use error::error;
+pub use event::{after, await_task, run_events, Events};
+pub use format::format;
+pub use fs::fs;
+pub use func::func;
+pub use gzip::gzip;
+pub use hash::hash;
+pub use http::http;
+pub use io::io;
+pub use iter::iter;
+pub use json::{json, to_json};
+pub use log::{log, Logger};
+pub use map::map;
+pub use net::net;
+pub use os::os;
+pub use path::path;
+pub use process::process;
+pub use random::{random, Rng};
+pub use regex::regex;
+#[cfg(feature = "sqlite")]
+pub use sqlite::sqlite;
+pub use string::string;
+pub use term::term;
+pub use test::{test, TestReport};
+pub use thread::{thread, Threads};
+pub use time::time;
+pub use uuid::uuid;
+pub use weak::{deref, weak};
+#[cfg(feature = "toml")]
+pub use toml::toml;
+#[cfg(feature = "yaml")]
+pub use yaml::yaml;
+#[cfg(feature = "zstd")]
+pub use zstd::zstd;
 
 fn assert_val_length(vals: &[Value], len: usize) -> Result<(), RuntimeError> {
     if vals.len() == len {
@@ -10,10 +92,27 @@ fn assert_val_length(vals: &[Value], len: usize) -> Result<(), RuntimeError> {
     }
 }
 
+/// checks for between `min` and `max` arguments, for functions with optional ones
+fn assert_val_range(vals: &[Value], min: usize, max: usize) -> Result<(), RuntimeError> {
+    if (min..=max).contains(&vals.len()) {
+        Ok(())
+    } else {
+        Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+}
+
+/// what a value is written as: a bytes value's contents, or anything else's text
+fn payload(val: &Value) -> Vec<u8> {
+    match val {
+        Value::Bytes(b) => b.borrow().clone(),
+        val => val.to_string().into_bytes()
+    }
+}
+
 pub fn print(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
     let mut s = String::new();
     for val in vals {
-        if s.len() > 0 {
+        if !s.is_empty() {
             s.push(' ');
         }
         s.push_str(&val.to_string());
@@ -23,20 +122,17 @@ pub fn print(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
 }
 
 pub fn prompt(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
-    match vals.get(0) {
-        Some(i) => {
-            print!("{}", i.string()?.borrow());
-            std::io::stdout().flush().unwrap();
-        },
-        None => {}
-    };
+    if let Some(i) = vals.first() {
+        print!("{}", i.string()?.borrow());
+        std::io::stdout().flush().unwrap();
+    }
     let mut s = String::new();
     std::io::stdin().read_line(&mut s).unwrap();
     Ok(s.trim_end().into())
 }
 
 pub fn exit(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
-    process::exit(vals.first()
+    std::process::exit(vals.first()
         .map(|x| x.num().map(|x| x.round() as i32).unwrap_or(0))
         .unwrap_or(0));
 }
@@ -46,19 +142,71 @@ pub fn corelib_type(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeErro
 
     let val = &vals[0];
 
-    let ty = match val {
-        Value::Number(_) => "number",
-        Value::String(_) => "string",
-        Value::Bool(_) => "bool",
-        Value::Array(_) => "array",
-        Value::Map(_) => "map",
-        Value::Function(_) => "func",
-        Value::Nil => "nil",
-    };
+    let ty = Type::of(val).name();
 
     Ok(ty.into())
 }
 
+/// `num(s)` parses a number, or `num(s, base)` an integer in that
+/// base. gives nil when the string isn't one. numbers are returned as they are
+pub fn num(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let s = match &vals[0] {
+        Value::Int(_) | Value::Number(_) if vals.len() == 1 => return Ok(vals[0].clone()),
+        v => v.string()?
+    };
+    let s = s.borrow();
+    let s = s.trim();
+    if let Some(base) = vals.get(1) {
+        let base = base.int()?;
+        if !(2..=36).contains(&base) {
+            return Err(RuntimeError::InvalidArgument(format!("base {} is not between 2 and 36", base)))
+        }
+        return Ok(i64::from_str_radix(s, base as u32).map(Value::Int).unwrap_or(Value::Nil))
+    }
+    if let Ok(i) = s.parse() {
+        return Ok(Value::Int(i))
+    }
+    Ok(s.parse().map(Value::Number).unwrap_or(Value::Nil))
+}
+
+/// the value as `print` would show it
+pub fn str(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(vals[0].to_string().into())
+}
+
+/// `to_fixed(n, digits)` formats with exactly that many decimal places
+pub fn to_fixed(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let n = vals[0].num()?;
+    let digits = vals[1].int()?.clamp(0, 100) as usize;
+    Ok(format!("{:.*}", digits, n).into())
+}
+
+/// `to_base(n, base)` writes an integer in a base from 2 to 36
+pub fn to_base(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let n = vals[0].int()?;
+    let base = vals[1].int()?;
+    if !(2..=36).contains(&base) {
+        return Err(RuntimeError::InvalidArgument(format!("base {} is not between 2 and 36", base)))
+    }
+    let mut digits = Vec::new();
+    let mut rest = n.unsigned_abs();
+    loop {
+        digits.push(std::char::from_digit((rest % base as u64) as u32, base as u32).unwrap());
+        rest /= base as u64;
+        if rest == 0 {
+            break
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    Ok(digits.into_iter().rev().collect::<String>().into())
+}
+
 pub fn run(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
     assert_val_length(&vals, 1)?;
 
@@ -69,32 +217,44 @@ pub fn run(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
     let s = s.borrow();
 
     println!("--> loading file {}", s);
-    let f = fs::read_to_string(&*s)
-        .expect("failed to load file");
+    let f: Rc<str> = std::fs::read_to_string(&*s)
+        .map_err(|e| RuntimeError::Io(format!("failed to load {}: {}", s, e)))?
+        .into();
+    vm.add_source(Path::new(&*s), &f);
 
-    let prog: Ast = match f.parse() {
+    let mut prog: Ast = match f.parse() {
         Ok(p) => p,
         Err(e) => {
             println!("encountered errors while running file");
             for e in e {
-                e.log(Some(&f));
+                e.log(Some(&s), Some(&f));
             }
             return Ok(Value::Bool(false))
         }
     };
+    vm.prepare(&mut prog);
 
-    vm.locals.push();
-    vm.execute(&prog)?;
-    vm.locals.pop();
+    vm.files.push(Path::new(&*s).into());
+    vm.push_scope();
+    let out = match bytecode::compile(&prog) {
+        Some(chunk) if !vm.tree_walk => bytecode::run(vm, &chunk),
+        _ => vm.execute(&prog)
+    };
+    let popped = vm.pop_scope();
+    vm.files.pop();
+    out.map_err(|e| e.with_source(&f))?;
+    popped?;
 
     Ok(Value::Bool(false))
 }
 
 pub fn sleep(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
     assert_val_length(&vals, 1)?;
-    let m = &vals[0];
-
-    thread::sleep(Duration::from_secs_f64(m.num()?));
+    let secs = vals[0].num()?;
+    let Ok(wait) = Duration::try_from_secs_f64(secs) else {
+        return Err(RuntimeError::InvalidArgument(format!("can't sleep for {} seconds", secs)))
+    };
+    std::thread::sleep(wait);
     Ok(Value::Nil)
 }
 
@@ -118,9 +278,9 @@ impl NativeLibModule {
     }
 }
 
-impl Into<Value> for NativeLibModule {
-    fn into(self) -> Value {
-        Value::Map(Handle::new(self.map))
+impl From<NativeLibModule> for Value {
+    fn from(module: NativeLibModule) -> Value {
+        Value::Map(Handle::new(module.map))
     }
 }
 
@@ -145,20 +305,152 @@ macro_rules! math_fns {
     };
 }
 
+/// like `math_fns!`, for two-argument methods. each is
+/// given as `name => method`, since the names can differ
+macro_rules! math_fns2 {
+    ($math:expr, $($name:ident => $f:ident),*) => {
+        mod __math_fns2 {
+            use super::*;
+            $(
+            pub(super) fn $name(_: &mut VM, args: Vec<Value>) -> Result<Value, RuntimeError> {
+                if args.len() != 2 {
+                    return Err(RuntimeError::IncorrectNumberOfArgs)
+                }
+                let a = args[0].num()?;
+                let b = args[1].num()?;
+                Ok(Value::Number(a.$f(b)))
+            }
+            )*
+        }
+
+        $(
+        $math.export_fn(stringify!($name), __math_fns2::$name)
+        );*
+    };
+}
+
 pub fn math() -> Value {
     let mut math = NativeLibModule::new();
 
     math_fns!(
         math,
         sin, cos, tan,
+        asin, acos, atan,
         sinh, cosh, tanh,
         floor, ceil,
-        ln,
+        sqrt, cbrt, exp,
+        ln, log2, log10,
         signum
     );
+    math_fns2!(
+        math,
+        atan2 => atan2,
+        pow => powf,
+        hypot => hypot
+    );
+
+    math.export_fn("abs", math_abs);
+    math.export_fn("min", math_min);
+    math.export_fn("max", math_max);
+    math.export_fn("clamp", math_clamp);
+    math.export_fn("log", math_log);
+    math.export_fn("round", math_round);
+    math.export_fn("is_nan", math_is_nan);
+    math.export_fn("is_inf", math_is_inf);
 
     math.export("pi", Value::Number(core::f64::consts::PI));
     math.export("e", Value::Number(core::f64::consts::E));
 
     math.into()
 }
+
+/// keeps ints as ints, where that doesn't overflow
+fn math_abs(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(match &vals[0] {
+        Value::Int(i) => match i.checked_abs() {
+            Some(i) => Value::Int(i),
+            None => Value::Number((*i as f64).abs())
+        },
+        v => Value::Number(v.num()?.abs())
+    })
+}
+
+/// whether the number is nan, which ints never are
+fn math_is_nan(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(Value::Bool(vals[0].num()?.is_nan()))
+}
+
+/// whether the number is infinite, either way
+fn math_is_inf(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    Ok(Value::Bool(vals[0].num()?.is_infinite()))
+}
+
+/// the argument that orders first (or last, for `max`), returned as it is
+fn extreme(vals: Vec<Value>, keep: Ordering) -> Result<Value, RuntimeError> {
+    let mut vals = vals.into_iter();
+    let Some(mut best) = vals.next() else {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    };
+    best.num()?;
+    for v in vals {
+        match v.num_cmp(&best)? {
+            Some(ord) if ord == keep => best = v,
+            Some(_) => {},
+            // nan wins, like it does in arithmetic
+            None => if v.num()?.is_nan() { best = v }
+        }
+    }
+    Ok(best)
+}
+
+/// the smallest argument. takes any number of them
+fn math_min(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    extreme(vals, Ordering::Less)
+}
+
+fn math_max(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    extreme(vals, Ordering::Greater)
+}
+
+/// `clamp(x, lo, hi)`
+fn math_clamp(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let (x, lo, hi) = (&vals[0], &vals[1], &vals[2]);
+    if lo.num_cmp(hi)? == Some(Ordering::Greater) {
+        return Err(RuntimeError::CannotCompare)
+    }
+    Ok(if x.num_cmp(lo)? == Some(Ordering::Less) {
+        lo.clone()
+    } else if x.num_cmp(hi)? == Some(Ordering::Greater) {
+        hi.clone()
+    } else {
+        x.num()?;
+        x.clone()
+    })
+}
+
+/// `log(x, base)`, where the base defaults to e
+fn math_log(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let x = vals[0].num()?;
+    Ok(Value::Number(match vals.get(1) {
+        Some(base) => x.log(base.num()?),
+        None => x.ln()
+    }))
+}
+
+/// `round(x, digits)` rounds to that many decimal places, or
+/// to a whole number when they're left out. halves round away from zero
+fn math_round(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let x = vals[0].num()?;
+    let digits = match vals.get(1) {
+        Some(d) => d.int()?.clamp(-308, 308) as i32,
+        None => 0
+    };
+    let scale = 10f64.powi(digits);
+    Ok(Value::Number((x * scale).round() / scale))
+}
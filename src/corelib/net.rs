@@ -0,0 +1,192 @@
+//! TCP and UDP sockets. a socket is a host object, whose methods are the
+//! functions here that take one, so `net.write(conn, s)` can also be written
+//! `conn.write(s)`. it's closed by `close`, or once nothing refers to it.
+//! every call blocks until it completes. data is received as strings, with
+//! invalid UTF-8 replaced, except by `read_bytes`, and bytes values are sent as is.
+
+use std::{cell::{RefCell, RefMut}, io::{self, Read}, net::{TcpListener, TcpStream, UdpSocket}};
+
+use crate::userdata::{methods, Methods, UserData};
+
+use super::*;
+
+/// the most bytes `read` and `recv_from` take at once by default
+const READ_SIZE: usize = 4096;
+
+enum Socket {
+    Stream(TcpStream),
+    Listener(TcpListener),
+    Udp(UdpSocket)
+}
+
+/// `None` once the socket is closed
+type Open = RefCell<Option<Socket>>;
+
+thread_local! {
+    static METHODS: Rc<Methods> = methods(&[
+        ("accept", accept),
+        ("read", read),
+        ("read_bytes", read_bytes),
+        ("write", write),
+        ("close", close),
+        ("send_to", send_to),
+        ("recv_from", recv_from)
+    ]);
+}
+
+fn new_socket(socket: Socket) -> Value {
+    METHODS.with(|methods| UserData::new("socket", methods.clone(), Open::new(Some(socket))).into())
+}
+
+fn get(val: &Value) -> Result<RefMut<'_, Socket>, RuntimeError> {
+    let open = val.user_data::<Open>("socket")?;
+    RefMut::filter_map(open.borrow_mut(), Option::as_mut)
+        .map_err(|_| RuntimeError::Io("the socket is closed".to_owned()))
+}
+
+pub fn net() -> Value {
+    let mut net = NativeLibModule::new();
+
+    net.export_fn("connect", connect);
+    net.export_fn("listen", listen);
+    net.export_fn("accept", accept);
+    net.export_fn("read", read);
+    net.export_fn("read_bytes", read_bytes);
+    net.export_fn("write", write);
+    net.export_fn("close", close);
+    net.export_fn("udp_bind", udp_bind);
+    net.export_fn("send_to", send_to);
+    net.export_fn("recv_from", recv_from);
+
+    net.into()
+}
+
+fn net_error(action: &str, e: io::Error) -> RuntimeError {
+    RuntimeError::Io(format!("failed to {}: {}", action, e))
+}
+
+fn wrong_kind(expected: &str) -> RuntimeError {
+    RuntimeError::Io(format!("expected a {} socket", expected))
+}
+
+/// `connect("host:port")` opens a TCP connection
+fn connect(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let addr = vals[0].string()?.borrow().clone();
+    let stream = TcpStream::connect(&addr)
+        .map_err(|e| net_error(&format!("connect to {}", addr), e))?;
+    Ok(new_socket(Socket::Stream(stream)))
+}
+
+/// `listen("host:port")` opens a TCP listener. port 0 picks a free one
+fn listen(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let addr = vals[0].string()?.borrow().clone();
+    let listener = TcpListener::bind(&addr)
+        .map_err(|e| net_error(&format!("listen on {}", addr), e))?;
+    Ok(new_socket(Socket::Listener(listener)))
+}
+
+/// waits for a connection on a listener, returning the new connection
+fn accept(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let Socket::Listener(listener) = &*get(&vals[0])? else {
+        return Err(wrong_kind("listening"))
+    };
+    let (stream, _) = listener.accept().map_err(|e| net_error("accept a connection", e))?;
+    Ok(new_socket(Socket::Stream(stream)))
+}
+
+/// reads up to `n` bytes, or none once the other side has closed the connection
+fn read_raw(vm: &mut VM, vals: &[Value]) -> Result<Option<Vec<u8>>, RuntimeError> {
+    assert_val_range(vals, 1, 2)?;
+    let size = match vals.get(1) {
+        Some(n) => n.int()?.max(1) as usize,
+        None => READ_SIZE
+    };
+    vm.reserve(size)?;
+    let Socket::Stream(stream) = &mut *get(&vals[0])? else {
+        return Err(wrong_kind("connected"))
+    };
+    let mut buf = vec![0; size];
+    let n = stream.read(&mut buf).map_err(|e| net_error("read", e))?;
+    buf.truncate(n);
+    Ok((n > 0).then_some(buf))
+}
+
+/// `read(conn, n)` reads up to `n` bytes, returning nil once the other side
+/// has closed the connection
+fn read(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(match read_raw(vm, &vals)? {
+        Some(buf) => String::from_utf8_lossy(&buf).into_owned().into(),
+        None => Value::Nil
+    })
+}
+
+/// like `read`, but returns the data as bytes
+fn read_bytes(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    Ok(match read_raw(vm, &vals)? {
+        Some(buf) => Value::Bytes(Handle::new(buf)),
+        None => Value::Nil
+    })
+}
+
+/// writes the whole string, or bytes value, to a connection
+fn write(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let data = payload(&vals[1]);
+    let Socket::Stream(stream) = &mut *get(&vals[0])? else {
+        return Err(wrong_kind("connected"))
+    };
+    stream.write_all(&data).map_err(|e| net_error("write", e))?;
+    Ok(Value::Nil)
+}
+
+fn close(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    // dropping the socket closes it
+    vals[0].user_data::<Open>("socket")?.borrow_mut().take()
+        .ok_or_else(|| RuntimeError::Io("the socket is already closed".to_owned()))?;
+    Ok(Value::Nil)
+}
+
+/// `udp_bind("host:port")` opens a UDP socket
+fn udp_bind(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let addr = vals[0].string()?.borrow().clone();
+    let socket = UdpSocket::bind(&addr)
+        .map_err(|e| net_error(&format!("bind {}", addr), e))?;
+    Ok(new_socket(Socket::Udp(socket)))
+}
+
+/// `send_to(sock, data, "host:port")` sends one datagram
+fn send_to(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let data = payload(&vals[1]);
+    let addr = vals[2].string()?.borrow().clone();
+    let Socket::Udp(socket) = &*get(&vals[0])? else {
+        return Err(wrong_kind("udp"))
+    };
+    socket.send_to(&data, &addr)
+        .map_err(|e| net_error(&format!("send to {}", addr), e))?;
+    Ok(Value::Nil)
+}
+
+/// waits for one datagram, returning a map with its `data` and the `from` address
+fn recv_from(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let size = match vals.get(1) {
+        Some(n) => n.int()?.max(1) as usize,
+        None => READ_SIZE
+    };
+    vm.reserve(size)?;
+    let Socket::Udp(socket) = &*get(&vals[0])? else {
+        return Err(wrong_kind("udp"))
+    };
+    let mut buf = vec![0; size];
+    let (n, from) = socket.recv_from(&mut buf).map_err(|e| net_error("receive", e))?;
+    let mut received = NativeLibModule::new();
+    received.export("data", String::from_utf8_lossy(&buf[..n]).into_owned().into());
+    received.export("from", from.to_string().into());
+    Ok(received.into())
+}
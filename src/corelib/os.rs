@@ -0,0 +1,76 @@
+use std::{env, process::Command};
+
+use super::*;
+
+pub fn os() -> Value {
+    let mut os = NativeLibModule::new();
+
+    os.export_fn("env", get_env);
+    os.export_fn("set_env", set_env);
+    os.export_fn("args", args);
+    os.export_fn("cwd", cwd);
+    os.export_fn("chdir", chdir);
+    os.export_fn("platform", platform);
+    os.export_fn("hostname", hostname);
+
+    os.into()
+}
+
+/// the environment variable, or nil if it isn't set (or isn't unicode)
+fn get_env(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let name = vals[0].string()?;
+    let var = env::var(name.borrow().as_str());
+    Ok(var.map(Value::from).unwrap_or(Value::Nil))
+}
+
+fn set_env(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let name = vals[0].string()?.borrow().clone();
+    let val = vals[1].to_string();
+    if name.is_empty() || name.contains(['=', '\0']) || val.contains('\0') {
+        return Err(RuntimeError::Io(format!("invalid environment variable `{}`", name)))
+    }
+    // threads from `thread.spawn` only touch the environment through std, which locks it
+    env::set_var(name, val);
+    Ok(Value::Nil)
+}
+
+fn args(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    let args = vm.script_args.iter().map(|a| a.as_str().into()).collect();
+    Ok(Value::Array(Handle::new(args)))
+}
+
+fn cwd(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    let dir = env::current_dir()
+        .map_err(|e| RuntimeError::Io(format!("failed to get the working directory: {}", e)))?;
+    Ok(dir.to_string_lossy().into_owned().into())
+}
+
+fn chdir(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let dir = vals[0].string()?.borrow().clone();
+    env::set_current_dir(&dir)
+        .map_err(|e| RuntimeError::Io(format!("failed to change directory to {}: {}", dir, e)))?;
+    Ok(Value::Nil)
+}
+
+/// the name of the operating system, like `linux`, `macos` or `windows`
+fn platform(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(env::consts::OS.into())
+}
+
+/// the machine's host name, or nil if it can't be found
+fn hostname(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    // std has no way to ask, but every major platform ships a `hostname` command
+    let name = Command::new("hostname").output().ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| String::from_utf8(out.stdout).ok())
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty());
+    Ok(name.map(Value::from).unwrap_or(Value::Nil))
+}
@@ -0,0 +1,106 @@
+//! Path helpers that use the platform's separator, so scripts
+//! don't need to build paths by concatenating strings.
+
+use std::path::{self, Component, Path, PathBuf};
+
+use super::*;
+
+pub fn path() -> Value {
+    let mut path = NativeLibModule::new();
+
+    path.export_fn("join", join);
+    path.export_fn("basename", basename);
+    path.export_fn("dirname", dirname);
+    path.export_fn("extension", extension);
+    path.export_fn("absolute", absolute);
+    path.export_fn("normalize", normalize);
+    path.export_fn("is_absolute", is_absolute);
+
+    path.into()
+}
+
+fn path_value(path: &Path) -> Value {
+    path.to_string_lossy().into_owned().into()
+}
+
+/// resolves `.` and `..` without looking at the file system,
+/// so symlinks aren't followed
+fn normalized(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => match out.components().next_back() {
+                Some(Component::Normal(_)) => { out.pop(); },
+                // `..` can't go above the root
+                Some(Component::RootDir | Component::Prefix(_)) => {},
+                _ => out.push(".."),
+            },
+            c => out.push(c)
+        }
+    }
+    if out.as_os_str().is_empty() {
+        out.push(".");
+    }
+    out
+}
+
+/// joins every argument, where an absolute one replaces what came before
+fn join(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let mut path = PathBuf::new();
+    for part in &vals {
+        path.push(part.string()?.borrow().as_str());
+    }
+    Ok(path_value(&path))
+}
+
+/// the last component of the path, or an empty string if there is none
+fn basename(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let p = vals[0].string()?;
+    let name = Path::new(p.borrow().as_str()).file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(name.into())
+}
+
+/// everything but the last component. the root is its own dirname
+fn dirname(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let p = vals[0].string()?;
+    let p = p.borrow();
+    let path = Path::new(p.as_str());
+    Ok(path_value(path.parent().unwrap_or(path)))
+}
+
+/// the extension without its dot, or nil if there isn't one
+fn extension(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let p = vals[0].string()?;
+    let ext = Path::new(p.borrow().as_str()).extension()
+        .map(|e| e.to_string_lossy().into_owned());
+    Ok(ext.map(Value::from).unwrap_or(Value::Nil))
+}
+
+/// the normalized path relative to the working directory. the path doesn't need to exist
+fn absolute(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let p = vals[0].string()?.borrow().clone();
+    let abs = path::absolute(&p)
+        .map_err(|e| RuntimeError::Io(format!("failed to make {} absolute: {}", p, e)))?;
+    Ok(path_value(&normalized(&abs)))
+}
+
+fn normalize(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let p = vals[0].string()?;
+    let norm = normalized(Path::new(p.borrow().as_str()));
+    Ok(path_value(&norm))
+}
+
+fn is_absolute(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let p = vals[0].string()?;
+    let abs = Path::new(p.borrow().as_str()).is_absolute();
+    Ok(Value::Bool(abs))
+}
@@ -0,0 +1,157 @@
+//! Runs other programs. `run` waits for the command and collects its
+//! output, while `spawn` starts it with piped stdin and stdout that the
+//! script talks to through a host object, like the sockets in `net`, whose
+//! methods are the functions here that take one.
+
+use std::{cell::{RefCell, RefMut}, io::{self, BufRead, BufReader}, process::{Child, ChildStdout, Command, Stdio}};
+
+use crate::userdata::{methods, Methods, UserData};
+
+use super::*;
+
+struct Running {
+    child: Child,
+    stdout: Option<BufReader<ChildStdout>>
+}
+
+/// `None` once the process has been waited on
+type Process = RefCell<Option<Running>>;
+
+thread_local! {
+    static METHODS: Rc<Methods> = methods(&[
+        ("read_line", read_line),
+        ("write", write),
+        ("close_stdin", close_stdin),
+        ("wait", wait),
+        ("kill", kill)
+    ]);
+}
+
+fn get(val: &Value) -> Result<RefMut<'_, Running>, RuntimeError> {
+    let proc = val.user_data::<Process>("process")?;
+    RefMut::filter_map(proc.borrow_mut(), Option::as_mut)
+        .map_err(|_| RuntimeError::Io("the process has already been waited on".to_owned()))
+}
+
+pub fn process() -> Value {
+    let mut process = NativeLibModule::new();
+
+    process.export_fn("run", run);
+    process.export_fn("spawn", spawn);
+    process.export_fn("read_line", read_line);
+    process.export_fn("write", write);
+    process.export_fn("close_stdin", close_stdin);
+    process.export_fn("wait", wait);
+    process.export_fn("kill", kill);
+
+    process.into()
+}
+
+fn process_error(action: &str, e: io::Error) -> RuntimeError {
+    RuntimeError::Io(format!("failed to {}: {}", action, e))
+}
+
+/// the command from `(cmd, args)`, where `args` is an optional array of arguments
+fn command(vals: &[Value]) -> Result<(String, Command), RuntimeError> {
+    assert_val_range(vals, 1, 2)?;
+    let program = vals[0].string()?.borrow().clone();
+    let mut cmd = Command::new(&program);
+    if let Some(args) = vals.get(1) {
+        cmd.args(args.array()?.borrow().iter().map(|a| a.to_string()));
+    }
+    Ok((program, cmd))
+}
+
+/// the exit code, or nil if the process was ended by a signal
+fn exit_code(status: std::process::ExitStatus) -> Value {
+    status.code().map(|c| Value::Int(c.into())).unwrap_or(Value::Nil)
+}
+
+/// `run(cmd, args)` waits for the command to finish, returning
+/// a map of its exit `code`, `stdout` and `stderr`
+fn run(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (program, mut cmd) = command(&vals)?;
+    let output = cmd.stdin(Stdio::null())
+        .output()
+        .map_err(|e| process_error(&format!("run {}", program), e))?;
+    let mut result = NativeLibModule::new();
+    result.export("code", exit_code(output.status));
+    result.export("stdout", String::from_utf8_lossy(&output.stdout).into_owned().into());
+    result.export("stderr", String::from_utf8_lossy(&output.stderr).into_owned().into());
+    Ok(result.into())
+}
+
+/// `spawn(cmd, args)` starts the command without waiting for it. its
+/// stderr goes straight to tram's own
+fn spawn(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let (program, mut cmd) = command(&vals)?;
+    let mut child = cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| process_error(&format!("spawn {}", program), e))?;
+    let stdout = child.stdout.take().map(BufReader::new);
+    let running = Process::new(Some(Running { child, stdout }));
+    Ok(METHODS.with(|methods| UserData::new("process", methods.clone(), running).into()))
+}
+
+/// the next line of the process's output, without the newline,
+/// or nil once it has closed its stdout
+fn read_line(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let mut proc = get(&vals[0])?;
+    let Some(stdout) = &mut proc.stdout else {
+        return Ok(Value::Nil)
+    };
+    let mut line = String::new();
+    let n = stdout.read_line(&mut line).map_err(|e| process_error("read from process", e))?;
+    if n == 0 {
+        return Ok(Value::Nil)
+    }
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(line.into())
+}
+
+fn write(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let data = vals[1].to_string();
+    let mut proc = get(&vals[0])?;
+    let Some(stdin) = &mut proc.child.stdin else {
+        return Err(RuntimeError::Io("the process's stdin is closed".to_string()))
+    };
+    stdin.write_all(data.as_bytes())
+        .and_then(|_| stdin.flush())
+        .map_err(|e| process_error("write to process", e))?;
+    Ok(Value::Nil)
+}
+
+/// closes the process's stdin, which tells most programs the input is over
+fn close_stdin(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let mut proc = get(&vals[0])?;
+    proc.child.stdin.take();
+    Ok(Value::Nil)
+}
+
+/// waits for the process to exit and returns its exit code. the process
+/// can't be used afterwards
+fn wait(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let mut proc = vals[0].user_data::<Process>("process")?.borrow_mut().take()
+        .ok_or_else(|| RuntimeError::Io("the process has already been waited on".to_owned()))?;
+    // close stdin first, or a process waiting on input would never exit
+    proc.child.stdin.take();
+    let status = proc.child.wait().map_err(|e| process_error("wait for process", e))?;
+    Ok(exit_code(status))
+}
+
+fn kill(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let mut proc = get(&vals[0])?;
+    proc.child.kill().map_err(|e| process_error("kill process", e))?;
+    Ok(Value::Nil)
+}
@@ -0,0 +1,115 @@
+//! Pseudo-random numbers from a per-VM generator, so seeding one VM
+//! makes its scripts deterministic without affecting any other.
+//! not suitable for cryptography.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+
+/// a splitmix64 generator
+#[derive(Debug)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// seeded from the clock, for when a script doesn't pick a seed
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// a float in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// a number in `[0, n)`, without the bias of a plain modulo
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            return 0
+        }
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let m = self.next_u64() as u128 * n as u128;
+            if m as u64 >= threshold {
+                return (m >> 64) as u64
+            }
+        }
+    }
+}
+
+pub fn random() -> Value {
+    let mut random = NativeLibModule::new();
+
+    random.export_fn("float", float);
+    random.export_fn("int", int);
+    random.export_fn("choice", choice);
+    random.export_fn("shuffle", shuffle);
+    random.export_fn("seed", seed);
+
+    random.into()
+}
+
+fn float(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(Value::Number(vm.rng.next_f64()))
+}
+
+/// `int(a, b)` gives an integer between `a` and `b`, inclusive
+fn int(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let (a, b) = (vals[0].int()?, vals[1].int()?);
+    let (low, high) = (a.min(b), a.max(b));
+    let span = high.abs_diff(low);
+    let n = match span.checked_add(1) {
+        Some(n) => vm.rng.below(n),
+        // the whole range of an i64
+        None => vm.rng.next_u64()
+    };
+    Ok(Value::Int(low.wrapping_add_unsigned(n)))
+}
+
+/// a random element of the array, or nil if it's empty
+fn choice(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let arr = arr.borrow();
+    if arr.is_empty() {
+        return Ok(Value::Nil)
+    }
+    let i = vm.rng.below(arr.len() as u64) as usize;
+    Ok(arr[i].clone())
+}
+
+/// shuffles the array in place
+fn shuffle(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let arr = vals[0].array()?;
+    let mut arr = arr.borrow_mut();
+    for i in (1..arr.len()).rev() {
+        let j = vm.rng.below(i as u64 + 1) as usize;
+        arr.swap(i, j);
+    }
+    Ok(Value::Nil)
+}
+
+fn seed(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    vm.rng = Rng::new(vals[0].int()? as u64);
+    Ok(Value::Nil)
+}
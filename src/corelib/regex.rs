@@ -0,0 +1,103 @@
+//! Patterns use the syntax of the `regex` crate. every function takes
+//! the pattern first, `regex.match(pattern, s)`, and compiled patterns
+//! are cached so calling one in a loop doesn't recompile it each time.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use ::regex::Regex;
+
+use super::*;
+
+/// the cache is dropped whenever it grows past this many patterns
+const CACHE_SIZE: usize = 64;
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+pub fn regex() -> Value {
+    let mut regex = NativeLibModule::new();
+
+    regex.export_fn("match", is_match);
+    regex.export_fn("find_all", find_all);
+    regex.export_fn("replace", replace);
+    regex.export_fn("split", split);
+    regex.export_fn("captures", captures);
+
+    regex.into()
+}
+
+fn compile(pattern: &Value) -> Result<Regex, RuntimeError> {
+    let pattern = pattern.string()?;
+    let pattern = pattern.borrow();
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some(re) = cache.get(pattern.as_str()) {
+            return Ok(re.clone())
+        }
+        let re = Regex::new(&pattern)
+            .map_err(|e| RuntimeError::InvalidFormat(format!("invalid regex: {}", e)))?;
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(pattern.clone(), re.clone());
+        Ok(re)
+    })
+}
+
+/// whether the pattern matches anywhere in the string
+fn is_match(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let re = compile(&vals[0])?;
+    let s = vals[1].string()?;
+    let found = re.is_match(&s.borrow());
+    Ok(Value::Bool(found))
+}
+
+/// every non-overlapping match, as an array of strings
+fn find_all(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let re = compile(&vals[0])?;
+    let s = vals[1].string()?;
+    let found = re.find_iter(&s.borrow())
+        .map(|m| m.as_str().into())
+        .collect();
+    Ok(Value::Array(Handle::new(found)))
+}
+
+/// `replace(pattern, s, with)` replaces every match. `with` may
+/// refer to groups as `$1` or `$name`
+fn replace(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let re = compile(&vals[0])?;
+    let s = vals[1].string()?;
+    let with = vals[2].string()?;
+    let replaced = re.replace_all(&s.borrow(), with.borrow().as_str()).into_owned();
+    Ok(replaced.into())
+}
+
+fn split(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let re = compile(&vals[0])?;
+    let s = vals[1].string()?;
+    let parts = re.split(&s.borrow())
+        .map(|p| p.into())
+        .collect();
+    Ok(Value::Array(Handle::new(parts)))
+}
+
+/// the groups of the first match, with the whole match first and nil
+/// for groups that didn't take part. nil if there's no match at all
+fn captures(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let re = compile(&vals[0])?;
+    let s = vals[1].string()?;
+    let s = s.borrow();
+    let Some(caps) = re.captures(&s) else {
+        return Ok(Value::Nil)
+    };
+    let groups = caps.iter()
+        .map(|g| g.map(|g| g.as_str().into()).unwrap_or(Value::Nil))
+        .collect();
+    Ok(Value::Array(Handle::new(groups)))
+}
@@ -0,0 +1,123 @@
+//! SQLite databases, enabled by the `sqlite` cargo feature. like sockets,
+//! open databases are host objects, with `exec`, `query` and `close` as
+//! methods. parameters are given as an array and bound to `?` placeholders,
+//! and rows come back as maps from column names to values.
+
+use std::cell::{Ref, RefCell};
+
+use rusqlite::{types::{Value as Sql, ValueRef}, Connection};
+
+use crate::userdata::{methods, Methods, UserData};
+
+use super::*;
+
+/// `None` once the database is closed
+type Database = RefCell<Option<Connection>>;
+
+thread_local! {
+    static METHODS: Rc<Methods> = methods(&[
+        ("exec", exec),
+        ("query", query),
+        ("close", close)
+    ]);
+}
+
+fn get(val: &Value) -> Result<Ref<'_, Connection>, RuntimeError> {
+    let db = val.user_data::<Database>("database")?;
+    Ref::filter_map(db.borrow(), Option::as_ref)
+        .map_err(|_| RuntimeError::Io("the database is closed".to_owned()))
+}
+
+pub fn sqlite() -> Value {
+    let mut sqlite = NativeLibModule::new();
+
+    sqlite.export_fn("open", open);
+    sqlite.export_fn("exec", exec);
+    sqlite.export_fn("query", query);
+    sqlite.export_fn("close", close);
+
+    sqlite.into()
+}
+
+fn sql_error(e: rusqlite::Error) -> RuntimeError {
+    RuntimeError::Io(format!("sqlite: {}", e))
+}
+
+/// the parameters to bind, from the optional array after the sql
+fn params(vals: &[Value]) -> Result<Vec<Sql>, RuntimeError> {
+    let Some(params) = vals.get(2) else {
+        return Ok(Vec::new())
+    };
+    let params = params.array()?;
+    let params = params.borrow();
+    params.iter()
+        .map(|v| Ok(match v {
+            Value::Nil => Sql::Null,
+            Value::Int(i) => Sql::Integer(*i),
+            Value::Number(n) => Sql::Real(*n),
+            Value::Bool(b) => Sql::Integer(*b as i64),
+            Value::String(s) => Sql::Text(s.borrow().clone()),
+            Value::Bytes(b) => Sql::Blob(b.borrow().clone()),
+            v => return Err(RuntimeError::CannotEncode(
+                format!("a {} can't be stored in sqlite", Type::of(v).name())))
+        }))
+        .collect()
+}
+
+/// `open(path)`, where `":memory:"` opens a database that's never saved
+fn open(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let path = vals[0].string()?.borrow().clone();
+    let conn = Connection::open(&path).map_err(sql_error)?;
+    Ok(METHODS.with(|methods| UserData::new("database", methods.clone(), Database::new(Some(conn))).into()))
+}
+
+/// `exec(db, sql, params)` runs a statement, returning how many rows it changed.
+/// without params, `sql` may hold several statements separated by `;`
+fn exec(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let conn = get(&vals[0])?;
+    let sql = vals[1].string()?.borrow().clone();
+    if vals.len() == 2 {
+        let before = conn.total_changes();
+        conn.execute_batch(&sql).map_err(sql_error)?;
+        return Ok(Value::Int((conn.total_changes() - before) as i64))
+    }
+    let params = params(&vals)?;
+    let changed = conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(sql_error)?;
+    Ok(Value::Int(changed as i64))
+}
+
+/// `query(db, sql, params)` returns the rows as an array of maps
+fn query(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let conn = get(&vals[0])?;
+    let sql = vals[1].string()?.borrow().clone();
+    let params = params(&vals)?;
+    let mut stmt = conn.prepare(&sql).map_err(sql_error)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt.query(rusqlite::params_from_iter(params)).map_err(sql_error)?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().map_err(sql_error)? {
+        let mut fields = Vec::with_capacity(columns.len());
+        for (i, name) in columns.iter().enumerate() {
+            let val = match row.get_ref(i).map_err(sql_error)? {
+                ValueRef::Null => Value::Nil,
+                ValueRef::Integer(i) => Value::Int(i),
+                ValueRef::Real(n) => Value::Number(n),
+                ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned().into(),
+                ValueRef::Blob(b) => Value::Bytes(Handle::new(b.to_vec()))
+            };
+            fields.push((Value::from(name.as_str()), val));
+        }
+        out.push(Value::Map(Handle::new(fields.into_iter().collect())));
+    }
+    Ok(Value::Array(Handle::new(out)))
+}
+
+fn close(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    vals[0].user_data::<Database>("database")?.borrow_mut().take()
+        .ok_or_else(|| RuntimeError::Io("the database is already closed".to_owned()))?;
+    Ok(Value::Nil)
+}
@@ -0,0 +1,155 @@
+//! Strings are treated as sequences of unicode scalar values (`char`s):
+//! lengths, indices and iteration all count those rather than bytes.
+
+use crate::executor::slice_range;
+
+use super::*;
+
+pub fn string() -> Value {
+    let mut string = NativeLibModule::new();
+
+    string.export_fn("len", len);
+    string.export_fn("chars", chars);
+    string.export_fn("upper", upper);
+    string.export_fn("lower", lower);
+    string.export_fn("trim", trim);
+    string.export_fn("split", split);
+    string.export_fn("join", join);
+    string.export_fn("replace", replace);
+    string.export_fn("contains", contains);
+    string.export_fn("starts_with", starts_with);
+    string.export_fn("ends_with", ends_with);
+    string.export_fn("find", find);
+    string.export_fn("sub", sub);
+
+    string.into()
+}
+
+fn len(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let len = s.borrow().chars().count();
+    Ok(Value::Int(len as i64))
+}
+
+fn chars(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let chars = s.borrow().chars().map(|c| c.to_string().into()).collect();
+    Ok(Value::Array(Handle::new(chars)))
+}
+
+fn upper(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let upper = s.borrow().to_uppercase();
+    Ok(upper.into())
+}
+
+fn lower(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let lower = s.borrow().to_lowercase();
+    Ok(lower.into())
+}
+
+fn trim(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let s = vals[0].string()?;
+    let trimmed = s.borrow().trim().to_owned();
+    Ok(trimmed.into())
+}
+
+/// `split(s, sep)`, or `split(s)` to split on runs of whitespace
+fn split(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let s = vals[0].string()?;
+    let s = s.borrow();
+    let parts: Vec<Value> = match vals.get(1) {
+        Some(sep) => {
+            let sep = sep.string()?;
+            let sep = sep.borrow();
+            if sep.is_empty() {
+                s.chars().map(|c| c.to_string().into()).collect()
+            } else {
+                s.split(sep.as_str()).map(|p| p.into()).collect()
+            }
+        },
+        None => s.split_whitespace().map(|p| p.into()).collect()
+    };
+    Ok(Value::Array(Handle::new(parts)))
+}
+
+/// `join(arr, sep)` joins the elements' printed forms, with no separator by default
+fn join(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let arr = vals[0].array()?;
+    let sep = match vals.get(1) {
+        Some(sep) => sep.string()?.borrow().clone(),
+        None => String::new()
+    };
+    let joined = arr.borrow().iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(&sep);
+    Ok(joined.into())
+}
+
+fn replace(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 3)?;
+    let s = vals[0].string()?;
+    let from = vals[1].string()?;
+    let to = vals[2].string()?;
+    let replaced = s.borrow().replace(from.borrow().as_str(), &to.borrow());
+    Ok(replaced.into())
+}
+
+fn contains(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let s = vals[0].string()?;
+    let needle = vals[1].string()?;
+    let found = s.borrow().contains(needle.borrow().as_str());
+    Ok(Value::Bool(found))
+}
+
+fn starts_with(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let s = vals[0].string()?;
+    let prefix = vals[1].string()?;
+    let starts = s.borrow().starts_with(prefix.borrow().as_str());
+    Ok(Value::Bool(starts))
+}
+
+fn ends_with(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let s = vals[0].string()?;
+    let suffix = vals[1].string()?;
+    let ends = s.borrow().ends_with(suffix.borrow().as_str());
+    Ok(Value::Bool(ends))
+}
+
+/// the char index of the first occurrence of the needle, or nil
+fn find(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let s = vals[0].string()?;
+    let s = s.borrow();
+    let needle = vals[1].string()?;
+    let byte = s.find(needle.borrow().as_str());
+    Ok(match byte {
+        Some(byte) => Value::Int(s[..byte].chars().count() as i64),
+        None => Value::Nil
+    })
+}
+
+/// `sub(s, start, end)`, which works like `s[start..end]`.
+/// the end may be left out to take the rest of the string
+fn sub(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    let s = vals[0].string()?;
+    let s = s.borrow();
+    let start = vals[1].int()?;
+    let end = vals.get(2).map(|e| e.int()).transpose()?;
+    let range = slice_range(Some(start), end, s.chars().count());
+    let sub: String = s.chars().skip(range.start).take(range.len()).collect();
+    Ok(sub.into())
+}
@@ -0,0 +1,167 @@
+//! ANSI styling and cursor control. when stdout isn't a terminal the
+//! styling functions return their text unchanged and the cursor
+//! functions do nothing, so output piped to a file stays plain.
+
+use std::io::{self, IsTerminal};
+
+use super::*;
+
+pub fn term() -> Value {
+    let mut term = NativeLibModule::new();
+
+    term.export_fn("is_tty", is_tty);
+    term.export_fn("size", size);
+    term.export_fn("style", style);
+    term.export_fn("clear", clear);
+    term.export_fn("clear_line", clear_line);
+    term.export_fn("move_to", move_to);
+    term.export_fn("move_by", move_by);
+    term.export_fn("hide_cursor", hide_cursor);
+    term.export_fn("show_cursor", show_cursor);
+
+    term.into()
+}
+
+fn tty() -> bool {
+    io::stdout().is_terminal()
+}
+
+/// writes an escape sequence, but only to a terminal
+fn emit(sequence: &str) -> Result<Value, RuntimeError> {
+    if tty() {
+        let mut out = io::stdout();
+        out.write_all(sequence.as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(|e| RuntimeError::Io(format!("failed to write to the terminal: {}", e)))?;
+    }
+    Ok(Value::Nil)
+}
+
+/// the SGR code for a style name
+fn code(name: &str) -> Option<u8> {
+    const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let color = |name: &str| COLORS.iter().position(|c| *c == name).map(|i| i as u8);
+    Some(match name {
+        "reset" => 0,
+        "bold" => 1,
+        "dim" => 2,
+        "italic" => 3,
+        "underline" => 4,
+        "inverse" => 7,
+        "strike" => 9,
+        _ => if let Some(name) = name.strip_prefix("bg_bright_") {
+            100 + color(name)?
+        } else if let Some(name) = name.strip_prefix("bg_") {
+            40 + color(name)?
+        } else if let Some(name) = name.strip_prefix("bright_") {
+            90 + color(name)?
+        } else {
+            30 + color(name)?
+        }
+    })
+}
+
+fn is_tty(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(Value::Bool(tty()))
+}
+
+/// a map of the terminal's `cols` and `rows`, or nil when it can't be found
+fn size(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(match terminal_size() {
+        Some((cols, rows)) => {
+            let mut size = NativeLibModule::new();
+            size.export("cols", Value::Int(cols as i64));
+            size.export("rows", Value::Int(rows as i64));
+            size.into()
+        },
+        None => Value::Nil
+    })
+}
+
+#[cfg(unix)]
+fn terminal_size() -> Option<(u16, u16)> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes a winsize through the pointer
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some((size.ws_col, size.ws_row))
+}
+
+/// without a way to ask the terminal, fall back to what the shell exports
+#[cfg(not(unix))]
+fn terminal_size() -> Option<(u16, u16)> {
+    let var = |name| std::env::var(name).ok()?.parse().ok();
+    Some((var("COLUMNS")?, var("LINES")?))
+}
+
+/// `style(text, "bold", "red", "bg_blue")` wraps the text in the named
+/// styles: `bold`, `dim`, `italic`, `underline`, `inverse`, `strike`, and
+/// the colors black, red, green, yellow, blue, magenta, cyan and white,
+/// each of which can be prefixed with `bright_`, `bg_` or `bg_bright_`
+fn style(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    if vals.is_empty() {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    }
+    let text = vals[0].to_string();
+    let mut codes = Vec::new();
+    for name in &vals[1..] {
+        let name = name.string()?;
+        let name = name.borrow();
+        let code = code(&name)
+            .ok_or_else(|| RuntimeError::InvalidArgument(format!("unknown style `{}`", name)))?;
+        codes.push(code.to_string());
+    }
+    if !tty() || codes.is_empty() {
+        return Ok(text.into())
+    }
+    Ok(format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text).into())
+}
+
+/// clears the screen and moves the cursor to the top left
+fn clear(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    emit("\x1b[2J\x1b[H")
+}
+
+fn clear_line(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    emit("\x1b[2K\r")
+}
+
+/// `move_to(row, col)`, counting from 1 at the top left
+fn move_to(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let row = vals[0].int()?.max(1);
+    let col = vals[1].int()?.max(1);
+    emit(&format!("\x1b[{};{}H", row, col))
+}
+
+/// `move_by(rows, cols)` moves relative to the cursor, down and right when positive
+fn move_by(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let rows = vals[0].int()?;
+    let cols = vals[1].int()?;
+    let mut sequence = String::new();
+    match rows {
+        0 => {},
+        r if r > 0 => sequence.push_str(&format!("\x1b[{}B", r)),
+        r => sequence.push_str(&format!("\x1b[{}A", r.unsigned_abs()))
+    }
+    match cols {
+        0 => {},
+        c if c > 0 => sequence.push_str(&format!("\x1b[{}C", c)),
+        c => sequence.push_str(&format!("\x1b[{}D", c.unsigned_abs()))
+    }
+    emit(&sequence)
+}
+
+fn hide_cursor(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    emit("\x1b[?25l")
+}
+
+fn show_cursor(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    emit("\x1b[?25h")
+}
@@ -0,0 +1,122 @@
+//! Self-tests for scripts. `test.case(name, func)` runs `func`, counting
+//! it as failed if it raises any error, and the VM prints a summary of
+//! the cases when it shuts down. the asserts raise errors, so they also
+//! work outside of a case.
+
+use crate::executor::caught;
+
+use super::*;
+
+/// the results of the cases run so far
+#[derive(Debug, Default)]
+pub struct TestReport {
+    passed: usize,
+    /// the names of the failed cases, with why they failed
+    failed: Vec<(String, String)>
+}
+
+impl TestReport {
+    pub fn ran(&self) -> bool {
+        self.passed > 0 || !self.failed.is_empty()
+    }
+
+    pub fn failed(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        if self.failed() {
+            out.push_str("\nfailures:\n");
+            for (name, reason) in &self.failed {
+                out.push_str(&format!("    {}: {}\n", name, reason));
+            }
+        }
+        out.push_str(&format!("\ntest result: {}. {} passed; {} failed",
+            if self.failed() { "FAILED" } else { "ok" }, self.passed, self.failed.len()));
+        out
+    }
+}
+
+impl Drop for TestReport {
+    fn drop(&mut self) {
+        if self.ran() {
+            println!("{}", self.summary());
+        }
+    }
+}
+
+pub fn test() -> Value {
+    let mut test = NativeLibModule::new();
+
+    test.export_fn("case", case);
+    test.export_fn("assert_eq", assert_eq);
+    test.export_fn("assert_ne", assert_ne);
+    test.export_fn("assert_error", assert_error);
+
+    test.into()
+}
+
+/// `case(name, func)` returns whether the case passed
+fn case(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let name = vals[0].to_string();
+    let func = vals[1].func()?;
+    match func.call(vm, vec![]) {
+        Ok(_) => {
+            println!("test {} ... ok", name);
+            vm.tests.passed += 1;
+            Ok(Value::Bool(true))
+        },
+        Err(e) => {
+            println!("test {} ... FAILED", name);
+            vm.tests.failed.push((name, describe(&e)));
+            Ok(Value::Bool(false))
+        }
+    }
+}
+
+/// a failure reason that reads better than the debug output
+fn describe(e: &RuntimeError) -> String {
+    match e.inner() {
+        RuntimeError::Thrown(v) => format!("threw {:?}", v),
+        RuntimeError::TestFailed(message) => message.clone(),
+        e => format!("{}: {}", e.kind(), e.message())
+    }
+}
+
+/// the optional last argument of an assert, put in front of its message
+fn with_message(vals: &[Value], at: usize, message: String) -> RuntimeError {
+    RuntimeError::TestFailed(match vals.get(at) {
+        Some(m) => format!("{}: {}", m, message),
+        None => message
+    })
+}
+
+/// `assert_eq(actual, expected)`, or `assert_eq(actual, expected, message)`
+fn assert_eq(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    if vals[0] != vals[1] {
+        return Err(with_message(&vals, 2, format!("expected {:?}, got {:?}", vals[1], vals[0])))
+    }
+    Ok(Value::Nil)
+}
+
+fn assert_ne(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 2, 3)?;
+    if vals[0] == vals[1] {
+        return Err(with_message(&vals, 2, format!("expected a value other than {:?}", vals[1])))
+    }
+    Ok(Value::Nil)
+}
+
+/// `assert_error(func)` calls `func` and returns the error it raises,
+/// failing if it returns normally
+fn assert_error(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let func = vals[0].func()?;
+    match func.call(vm, vec![]) {
+        Ok(v) => Err(with_message(&vals, 1, format!("expected an error, but returned {:?}", v))),
+        Err(e) => Ok(caught(e))
+    }
+}
@@ -0,0 +1,314 @@
+//! Running functions in parallel. `spawn(func, args...)` calls `func` on
+//! another OS thread, in a fresh VM with only the standard library, and
+//! returns an int handle that `join` waits on for the function's result.
+//! threads talk through channels, which are int handles that work in any
+//! VM. values are copied as they cross between VMs, and functions are
+//! copied as their source, since they can't capture variables anyway.
+//! iterators and errors can't be sent. a thread takes from the same fuel
+//! and memory limit as the VM that spawned it, and runs whatever its
+//! function left to the event loop before it finishes
+
+use std::{collections::{HashSet, VecDeque}, path::PathBuf, sync::{atomic::{AtomicI64, Ordering as AtomicOrdering}, Arc, Condvar, LazyLock, Mutex}, thread::JoinHandle};
+
+use crate::executor::{Budget, VmOptions};
+
+use super::*;
+
+/// a copy of a value that doesn't share anything with the VM it came from
+pub(super) enum Portable {
+    Int(i64),
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Array(Vec<Portable>),
+    Map(Vec<(Portable, Portable)>),
+    Bytes(Vec<u8>),
+    /// the function's source
+    Function(String),
+    Nil
+}
+
+impl Portable {
+    /// copies `val`, failing on anything that can't leave its VM
+    pub(super) fn of(val: &Value, seen: &mut HashSet<usize>) -> Result<Self, RuntimeError> {
+        // copying a value that contains itself would never finish
+        let addr = match val {
+            Value::Array(a) => Some(&**a as *const _ as *const () as usize),
+            Value::Map(m) => Some(&**m as *const _ as *const () as usize),
+            _ => None
+        };
+        if let Some(addr) = addr {
+            if !seen.insert(addr) {
+                return Err(RuntimeError::InvalidArgument(
+                    "a value that contains itself can't be sent to another thread".to_owned()))
+            }
+        }
+        let copy = match val {
+            Value::Int(i) => Self::Int(*i),
+            Value::Number(n) => Self::Number(*n),
+            Value::String(s) => Self::String(s.borrow().clone()),
+            Value::Bool(b) => Self::Bool(*b),
+            Value::Array(a) => Self::Array(a.borrow().iter()
+                .map(|v| Self::of(v, seen))
+                .collect::<Result<_, _>>()?),
+            Value::Map(m) => Self::Map(m.borrow().iter()
+                .map(|(k, v)| Ok((Self::of(k, seen)?, Self::of(v, seen)?)))
+                .collect::<Result<_, RuntimeError>>()?),
+            Value::Bytes(b) => Self::Bytes(b.borrow().clone()),
+            Value::Function(func) => match func.as_function() {
+                Some(f) => Self::Function(f.text()),
+                None => return Err(RuntimeError::InvalidArgument(
+                    "only functions written in tram can be sent to another thread".to_owned()))
+            },
+            Value::Nil => Self::Nil,
+            val => return Err(RuntimeError::InvalidArgument(
+                format!("a {} can't be sent to another thread", Type::of(val).name())))
+        };
+        if let Some(addr) = addr {
+            seen.remove(&addr);
+        }
+        Ok(copy)
+    }
+
+    /// makes the copy into a value of `vm`
+    pub(super) fn into_value(self, vm: &mut VM) -> Result<Value, RuntimeError> {
+        Ok(match self {
+            Self::Int(i) => Value::Int(i),
+            Self::Number(n) => Value::Number(n),
+            Self::String(s) => s.into(),
+            Self::Bool(b) => Value::Bool(b),
+            Self::Array(a) => {
+                let items = a.into_iter()
+                    .map(|v| v.into_value(vm))
+                    .collect::<Result<_, _>>()?;
+                Value::Array(Handle::new(items))
+            },
+            Self::Map(m) => {
+                let pairs = m.into_iter()
+                    .map(|(k, v)| Ok((k.into_value(vm)?, v.into_value(vm)?)))
+                    .collect::<Result<Vec<_>, RuntimeError>>()?;
+                Value::Map(Handle::new(pairs.into_iter().collect()))
+            },
+            Self::Bytes(b) => Value::Bytes(Handle::new(b)),
+            Self::Function(source) => {
+                let mut prog: Ast = source.parse()
+                    .map_err(|_| RuntimeError::InvalidFormat("a function sent from another thread didn't parse".to_owned()))?;
+                vm.prepare(&mut prog);
+                // a named function assigns itself, which shouldn't leak out
+                vm.push_scope();
+                let func = vm.execute(&prog);
+                vm.pop_scope()?;
+                func?
+            },
+            Self::Nil => Value::Nil
+        })
+    }
+}
+
+/// how a thread's function failed, since the error itself can't leave its VM
+pub(super) struct Failure {
+    pub(super) kind: String,
+    pub(super) message: String
+}
+
+impl Failure {
+    pub(super) fn of(e: &RuntimeError) -> Self {
+        Self { kind: e.kind().to_owned(), message: e.message() }
+    }
+
+    /// an error with the same kind and message as the one that failed
+    pub(super) fn into_error(self) -> RuntimeError {
+        RuntimeError::Custom { kind: self.kind, message: self.message, data: Value::Nil }
+    }
+}
+
+/// the threads started by `spawn` that haven't been joined
+#[derive(Default)]
+pub struct Threads {
+    running: HashMap<i64, JoinHandle<Result<Portable, Failure>>>,
+    next: i64
+}
+
+#[derive(Default)]
+struct Channel {
+    queue: Mutex<(VecDeque<Portable>, bool)>,
+    /// signalled when a value is sent or the channel is closed
+    ready: Condvar
+}
+
+/// every channel made, shared by all the VMs in the process. a channel is
+/// dropped once it's closed and empty, after which it acts as it did then
+static CHANNELS: LazyLock<Mutex<HashMap<i64, Arc<Channel>>>> = LazyLock::new(Default::default);
+static NEXT_CHANNEL: AtomicI64 = AtomicI64::new(0);
+
+fn channel_arg(val: &Value) -> Result<(i64, Arc<Channel>), RuntimeError> {
+    let id = val.int()?;
+    if let Some(channel) = CHANNELS.lock().expect("channel table poisoned").get(&id) {
+        return Ok((id, channel.clone()))
+    }
+    if (0..NEXT_CHANNEL.load(AtomicOrdering::Relaxed)).contains(&id) {
+        // dropped, so closed with nothing left
+        let channel = Channel { queue: Mutex::new((VecDeque::new(), true)), ready: Condvar::new() };
+        return Ok((id, Arc::new(channel)))
+    }
+    Err(RuntimeError::InvalidArgument(format!("{} is not a channel", id)))
+}
+
+/// drops the channel `id` from the table if it's closed and `queue`,
+/// its contents, is empty. nothing can be sent to it, so it stays that way
+fn retire(id: i64, queue: &(VecDeque<Portable>, bool)) {
+    if queue.1 && queue.0.is_empty() {
+        CHANNELS.lock().expect("channel table poisoned").remove(&id);
+    }
+}
+
+pub fn thread() -> Value {
+    let mut thread = NativeLibModule::new();
+
+    thread.export_fn("spawn", spawn);
+    thread.export_fn("join", join);
+    thread.export_fn("channel", channel);
+    thread.export_fn("send", send);
+    thread.export_fn("receive", receive);
+    thread.export_fn("try_receive", try_receive);
+    thread.export_fn("close", close);
+
+    thread.into()
+}
+
+/// runs the function with its `name`, `source` and `args` in a new VM
+fn run_thread(options: VmOptions, settings: (bool, bool, bool), budget: Budget, dir: Option<PathBuf>,
+    name: Option<String>, source: String, args: Vec<Portable>) -> Result<Portable, RuntimeError> {
+    let mut vm = VM::with_options(options);
+    vm.register_stdlib();
+    (vm.tree_walk, vm.optimize, vm.strict_math) = settings;
+    vm.share_budget(budget);
+    if let Some(dir) = dir {
+        vm.files.push(dir.into());
+    }
+    let func = Portable::Function(source).into_value(&mut vm)?;
+    // defined globally, so the function can call itself
+    if let Some(name) = name {
+        vm.set_global(&name, func.clone());
+    }
+    let args = args.into_iter()
+        .map(|a| a.into_value(&mut vm))
+        .collect::<Result<_, _>>()?;
+    let out = func.func()?.call(&mut vm, args)?;
+    // timers the function set go off before the thread is done
+    run_events(&mut vm)?;
+    Portable::of(&out, &mut HashSet::new())
+}
+
+fn spawn(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let Some(func) = vals.first() else {
+        return Err(RuntimeError::IncorrectNumberOfArgs)
+    };
+    let func = func.func()?;
+    let Some(f) = func.as_function() else {
+        return Err(RuntimeError::InvalidArgument("only functions written in tram can run on another thread".to_owned()))
+    };
+    let (name, source) = (f.name.clone(), f.text());
+    let args = vals[1..].iter()
+        .map(|a| Portable::of(a, &mut HashSet::new()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let options = *vm.options();
+    let settings = (vm.tree_walk, vm.optimize, vm.strict_math);
+    let budget = vm.budget();
+    // modules are found the same way they would be from the spawning file
+    let dir = vm.files.last().map(|f| f.to_path_buf());
+    let handle = std::thread::spawn(move || {
+        run_thread(options, settings, budget, dir, name, source, args)
+            .map_err(|e| Failure::of(&e))
+    });
+    let threads = &mut vm.threads;
+    let id = threads.next;
+    threads.next += 1;
+    threads.running.insert(id, handle);
+    Ok(Value::Int(id))
+}
+
+/// `join(thread)` waits for the thread to finish, returning what its function
+/// returned, or raising an error with the same kind and message as its failure
+fn join(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let id = vals[0].int()?;
+    let Some(handle) = vm.threads.running.remove(&id) else {
+        return Err(RuntimeError::InvalidArgument(format!("{} is not a running thread", id)))
+    };
+    match handle.join() {
+        Ok(Ok(out)) => out.into_value(vm),
+        Ok(Err(failure)) => Err(failure.into_error()),
+        Err(_) => Err(RuntimeError::Custom {
+            kind: "thread_panicked".to_owned(),
+            message: format!("thread {} panicked", id),
+            data: Value::Nil
+        })
+    }
+}
+
+fn channel(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    let id = NEXT_CHANNEL.fetch_add(1, AtomicOrdering::Relaxed);
+    CHANNELS.lock().expect("channel table poisoned").insert(id, Arc::default());
+    Ok(Value::Int(id))
+}
+
+/// `send(channel, value)` queues a copy of the value, failing if the channel is closed
+fn send(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let (_, channel) = channel_arg(&vals[0])?;
+    let val = Portable::of(&vals[1], &mut HashSet::new())?;
+    let mut queue = channel.queue.lock().expect("channel poisoned");
+    if queue.1 {
+        return Err(RuntimeError::InvalidArgument("can't send on a closed channel".to_owned()))
+    }
+    queue.0.push_back(val);
+    channel.ready.notify_one();
+    Ok(Value::Nil)
+}
+
+/// `receive(channel)` waits for the next value, or returns
+/// nil once the channel is closed and has nothing left
+fn receive(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let (id, channel) = channel_arg(&vals[0])?;
+    let mut queue = channel.queue.lock().expect("channel poisoned");
+    let val = loop {
+        match queue.0.pop_front() {
+            Some(val) => break val,
+            None if queue.1 => return Ok(Value::Nil),
+            None => queue = channel.ready.wait(queue).expect("channel poisoned")
+        }
+    };
+    retire(id, &queue);
+    drop(queue);
+    val.into_value(vm)
+}
+
+/// `try_receive(channel)` is the next value if one is waiting, or nil
+fn try_receive(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let (id, channel) = channel_arg(&vals[0])?;
+    let mut queue = channel.queue.lock().expect("channel poisoned");
+    let val = queue.0.pop_front();
+    retire(id, &queue);
+    drop(queue);
+    match val {
+        Some(val) => val.into_value(vm),
+        None => Ok(Value::Nil)
+    }
+}
+
+/// `close(channel)` stops values being sent. those already sent can
+/// still be received, after which `receive` returns nil
+fn close(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let (id, channel) = channel_arg(&vals[0])?;
+    let mut queue = channel.queue.lock().expect("channel poisoned");
+    queue.1 = true;
+    retire(id, &queue);
+    drop(queue);
+    channel.ready.notify_all();
+    Ok(Value::Nil)
+}
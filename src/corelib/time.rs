@@ -0,0 +1,134 @@
+//! Timestamps are unix seconds, as floats. `format` works in UTC,
+//! since std has no way to find the local time zone.
+
+use std::{sync::OnceLock, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+use super::*;
+
+pub fn time() -> Value {
+    let mut time = NativeLibModule::new();
+
+    time.export_fn("now", now);
+    time.export_fn("monotonic", monotonic);
+    time.export_fn("sleep_ms", sleep_ms);
+    time.export_fn("format", format);
+
+    time.into()
+}
+
+fn now(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    Ok(Value::Number(unix_now()?))
+}
+
+/// seconds since an arbitrary fixed point, which never goes backwards.
+/// only differences between two calls are meaningful
+fn monotonic(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    static START: OnceLock<Instant> = OnceLock::new();
+    let start = START.get_or_init(Instant::now);
+    Ok(Value::Number(start.elapsed().as_secs_f64()))
+}
+
+fn sleep_ms(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let ms = vals[0].num()?;
+    // a negative wait is no wait, but nan, or one too long to sleep for, is a mistake
+    let wait = Duration::try_from_secs_f64(ms.max(0.0) / 1000.0).ok().filter(|_| !ms.is_nan());
+    let Some(wait) = wait else {
+        return Err(RuntimeError::InvalidArgument(format!("can't sleep for {} milliseconds", ms)))
+    };
+    std::thread::sleep(wait);
+    Ok(Value::Nil)
+}
+
+const DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December"
+];
+
+/// the (year, month, day) of a count of days since 1970-01-01,
+/// using Howard Hinnant's `civil_from_days`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn is_leap(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// `format(ts, fmt)` with strftime-style specifiers: `%Y %m %d %H %M %S`,
+/// `%y`, `%j` (day of the year), `%a %A` (weekday), `%b %B` (month),
+/// `%f` (milliseconds), `%s` (unix seconds) and `%%`
+fn format(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 2)?;
+    let ts = vals[0].num()?;
+    let fmt = vals[1].string()?;
+    let out = strftime(ts, &fmt.borrow());
+    Ok(out.into())
+}
+
+/// the current time, as `now` returns it
+pub(super) fn unix_now() -> Result<f64, RuntimeError> {
+    let since = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map_err(|_| RuntimeError::Io("the system clock is set before 1970".to_string()))?;
+    Ok(since.as_secs_f64())
+}
+
+pub(super) fn strftime(ts: f64, fmt: &str) -> String {
+    let secs = ts.floor() as i64;
+    let millis = ((ts - ts.floor()) * 1000.0) as i64;
+    let days = secs.div_euclid(86400);
+    let in_day = secs.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (in_day / 3600, in_day / 60 % 60, in_day % 60);
+    // 1970-01-01 was a thursday
+    let weekday = (days + 3).rem_euclid(7) as usize;
+    let before_month: u32 = [31, if is_leap(year) { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]
+        .iter()
+        .take(month as usize - 1)
+        .sum();
+
+    let mut out = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue
+        }
+        match chars.next() {
+            Some('Y') => out += &year.to_string(),
+            Some('y') => out += &format!("{:02}", year.rem_euclid(100)),
+            Some('m') => out += &format!("{:02}", month),
+            Some('d') => out += &format!("{:02}", day),
+            Some('H') => out += &format!("{:02}", hour),
+            Some('M') => out += &format!("{:02}", minute),
+            Some('S') => out += &format!("{:02}", second),
+            Some('f') => out += &format!("{:03}", millis),
+            Some('j') => out += &format!("{:03}", before_month + day),
+            Some('a') => out += &DAYS[weekday][..3],
+            Some('A') => out += DAYS[weekday],
+            Some('b') => out += &MONTHS[month as usize - 1][..3],
+            Some('B') => out += MONTHS[month as usize - 1],
+            Some('s') => out += &secs.to_string(),
+            Some('%') => out.push('%'),
+            // unknown specifiers are left as they are
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            },
+            None => out.push('%')
+        }
+    }
+    out
+}
@@ -0,0 +1,41 @@
+//! TOML tables become maps. integers decode to ints, and dates and
+//! times, which tram has no type for, to their TOML text.
+
+use ::toml::{Table, Value as Toml};
+
+use super::*;
+
+pub fn toml() -> Value {
+    let mut toml = NativeLibModule::new();
+
+    toml.export_fn("parse", parse);
+
+    toml.into()
+}
+
+fn parse(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let source = vals[0].string()?;
+    let table: Table = source.borrow().parse()
+        .map_err(|e: ::toml::de::Error| RuntimeError::InvalidFormat(
+            format!("invalid toml: {}", e.message())))?;
+    Ok(table_value(table))
+}
+
+fn table_value(table: Table) -> Value {
+    Value::Map(Handle::new(table.into_iter()
+        .map(|(k, v)| (k.into(), value(v)))
+        .collect()))
+}
+
+fn value(toml: Toml) -> Value {
+    match toml {
+        Toml::String(s) => s.into(),
+        Toml::Integer(i) => Value::Int(i),
+        Toml::Float(n) => Value::Number(n),
+        Toml::Boolean(b) => Value::Bool(b),
+        Toml::Datetime(d) => d.to_string().into(),
+        Toml::Array(a) => Value::Array(Handle::new(a.into_iter().map(value).collect())),
+        Toml::Table(t) => table_value(t)
+    }
+}
@@ -0,0 +1,82 @@
+//! UUIDs in their canonical form: lowercase hex in groups of 8-4-4-4-12.
+//! `parse` reads the common spellings into 16 bytes, and `format` writes
+//! bytes back out. v4 UUIDs come from the VM's `random` generator.
+
+use super::*;
+
+pub fn uuid() -> Value {
+    let mut uuid = NativeLibModule::new();
+
+    uuid.export_fn("v4", v4);
+    uuid.export_fn("parse", parse);
+    uuid.export_fn("format", format);
+
+    uuid.into()
+}
+
+fn canonical(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(36);
+    for (i, b) in bytes.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            out.push('-');
+        }
+        out.push_str(&format!("{:02x}", b));
+    }
+    out
+}
+
+fn v4(vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 0)?;
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&vm.rng.next_u64().to_le_bytes());
+    bytes[8..].copy_from_slice(&vm.rng.next_u64().to_le_bytes());
+    // the version in the high nibble of byte 6, and the RFC 4122 variant
+    bytes[6] = bytes[6] & 0x0f | 0x40;
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    Ok(canonical(&bytes).into())
+}
+
+/// accepts the canonical form in either case, with or without the dashes,
+/// and wrapped in braces or prefixed with `urn:uuid:`
+fn parse(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let text = vals[0].string()?;
+    let text = text.borrow();
+    let invalid = || RuntimeError::InvalidFormat(format!("invalid uuid `{}`", text));
+
+    let mut s = text.trim();
+    s = s.strip_prefix("urn:uuid:").unwrap_or(s);
+    if let Some(inner) = s.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+        s = inner;
+    }
+    let hex: String = match s.len() {
+        32 => s.to_string(),
+        36 => {
+            let groups: Vec<&str> = s.split('-').collect();
+            if groups.iter().map(|g| g.len()).ne([8, 4, 4, 4, 12]) {
+                return Err(invalid())
+            }
+            groups.concat()
+        },
+        _ => return Err(invalid())
+    };
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid())
+    }
+    let bytes = (0..16)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    Ok(Value::Bytes(Handle::new(bytes)))
+}
+
+/// `format(b)` writes 16 bytes as a canonical UUID
+fn format(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let bytes = vals[0].bytes()?;
+    let bytes = bytes.borrow();
+    if bytes.len() != 16 {
+        return Err(RuntimeError::InvalidArgument(format!("a uuid is 16 bytes, not {}", bytes.len())))
+    }
+    Ok(canonical(&bytes).into())
+}
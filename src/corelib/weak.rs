@@ -0,0 +1,65 @@
+//! Weak references, which refer to a value without keeping it alive, for
+//! caches and observers that shouldn't hold on to what they watch. `weak(v)`
+//! makes one, and `deref(w)`, or `w.deref()`, gives the value back, or nil
+//! once nothing else refers to it. only values that are shared rather than
+//! copied can be referred to weakly. a string literal lives as long as the
+//! code it's written in
+
+use std::rc::Weak;
+
+use crate::{function::Callable, handle::WeakHandle, iterator::Iterate, userdata::{methods, Methods, UserData}};
+
+use super::*;
+
+enum Target {
+    String(WeakHandle<String>),
+    Array(WeakHandle<Vec<Value>>),
+    Map(WeakHandle<HashMap<Value, Value>>),
+    Bytes(WeakHandle<Vec<u8>>),
+    Function(Weak<dyn Callable>),
+    Error(Weak<RuntimeError>),
+    Iterator(Weak<dyn Iterate>),
+    UserData(Weak<UserData>)
+}
+
+impl Target {
+    fn upgrade(&self) -> Option<Value> {
+        Some(match self {
+            Self::String(s) => Value::String(s.upgrade()?),
+            Self::Array(a) => Value::Array(a.upgrade()?),
+            Self::Map(m) => Value::Map(m.upgrade()?),
+            Self::Bytes(b) => Value::Bytes(b.upgrade()?),
+            Self::Function(f) => Value::Function(f.upgrade()?),
+            Self::Error(e) => Value::Error(e.upgrade()?),
+            Self::Iterator(i) => Value::Iterator(i.upgrade()?),
+            Self::UserData(u) => Value::UserData(u.upgrade()?)
+        })
+    }
+}
+
+thread_local! {
+    static METHODS: Rc<Methods> = methods(&[("deref", deref)]);
+}
+
+pub fn weak(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let target = match &vals[0] {
+        Value::String(s) => Target::String(s.downgrade()),
+        Value::Array(a) => Target::Array(a.downgrade()),
+        Value::Map(m) => Target::Map(m.downgrade()),
+        Value::Bytes(b) => Target::Bytes(b.downgrade()),
+        Value::Function(f) => Target::Function(Rc::downgrade(f)),
+        Value::Error(e) => Target::Error(Rc::downgrade(e)),
+        Value::Iterator(i) => Target::Iterator(Rc::downgrade(i)),
+        Value::UserData(u) => Target::UserData(Rc::downgrade(u)),
+        v => return Err(RuntimeError::InvalidArgument(
+            format!("a {} is copied rather than shared, so it can't be referred to weakly", Type::of(v).name())))
+    };
+    Ok(METHODS.with(|methods| UserData::new("weak", methods.clone(), target).into()))
+}
+
+pub fn deref(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let target = vals[0].user_data::<Target>("weak")?;
+    Ok(target.upgrade().unwrap_or(Value::Nil))
+}
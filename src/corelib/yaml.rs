@@ -0,0 +1,67 @@
+//! YAML mappings become maps and sequences become arrays. a document
+//! holding several `---` separated documents parses to the first one,
+//! or use `parse_all` to get an array of all of them.
+
+use yaml_rust2::{Yaml, YamlLoader};
+
+use super::*;
+
+pub fn yaml() -> Value {
+    let mut yaml = NativeLibModule::new();
+
+    yaml.export_fn("parse", parse);
+    yaml.export_fn("parse_all", parse_all);
+
+    yaml.into()
+}
+
+fn load(vals: &[Value]) -> Result<Vec<Yaml>, RuntimeError> {
+    assert_val_length(vals, 1)?;
+    let source = vals[0].string()?;
+    let docs = YamlLoader::load_from_str(&source.borrow())
+        .map_err(|e| RuntimeError::InvalidFormat(format!("invalid yaml: {}", e)))?;
+    Ok(docs)
+}
+
+fn parse(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    match load(&vals)?.into_iter().next() {
+        Some(doc) => value(doc),
+        None => Ok(Value::Nil)
+    }
+}
+
+fn parse_all(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    let docs = load(&vals)?.into_iter()
+        .map(value)
+        .collect::<Result<_, _>>()?;
+    Ok(Value::Array(Handle::new(docs)))
+}
+
+fn value(yaml: Yaml) -> Result<Value, RuntimeError> {
+    Ok(match yaml {
+        Yaml::Real(s) => match s.parse() {
+            Ok(n) => Value::Number(n),
+            // `.inf` and `.nan` are reals that rust doesn't parse
+            Err(_) => Value::Number(match s.trim_start_matches(['+', '-']) {
+                ".nan" | ".NaN" | ".NAN" => f64::NAN,
+                _ if s.starts_with('-') => f64::NEG_INFINITY,
+                _ => f64::INFINITY
+            })
+        },
+        Yaml::Integer(i) => Value::Int(i),
+        Yaml::String(s) => s.into(),
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Array(a) => Value::Array(Handle::new(a.into_iter()
+            .map(value)
+            .collect::<Result<_, _>>()?)),
+        Yaml::Hash(h) => {
+            let pairs = h.into_iter()
+                .map(|(k, v)| Ok((value(k)?, value(v)?)))
+                .collect::<Result<Vec<_>, RuntimeError>>()?;
+            Value::Map(Handle::new(pairs.into_iter().collect()))
+        },
+        Yaml::Null => Value::Nil,
+        Yaml::Alias(_) => return Err(RuntimeError::InvalidFormat("yaml aliases are not supported".to_string())),
+        Yaml::BadValue => return Err(RuntimeError::InvalidFormat("invalid yaml value".to_string()))
+    })
+}
@@ -0,0 +1,33 @@
+//! zstd compression, which works like the `gzip` module.
+
+use super::*;
+
+pub fn zstd() -> Value {
+    let mut zstd = NativeLibModule::new();
+
+    zstd.export_fn("compress", compress);
+    zstd.export_fn("decompress", decompress);
+
+    zstd.into()
+}
+
+/// `compress(data)`, or `compress(data, level)` with a level from 1 to 22
+fn compress(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_range(&vals, 1, 2)?;
+    let data = vals[0].data()?;
+    let level = match vals.get(1) {
+        Some(level) => level.int()?.clamp(1, 22) as i32,
+        None => ::zstd::DEFAULT_COMPRESSION_LEVEL
+    };
+    let out = ::zstd::encode_all(&data[..], level)
+        .map_err(|e| RuntimeError::Io(format!("failed to compress: {}", e)))?;
+    Ok(Value::Bytes(Handle::new(out)))
+}
+
+fn decompress(_vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+    assert_val_length(&vals, 1)?;
+    let data = vals[0].data()?;
+    let out = ::zstd::decode_all(&data[..])
+        .map_err(|e| RuntimeError::InvalidFormat(format!("invalid zstd data: {}", e)))?;
+    Ok(Value::Bytes(Handle::new(out)))
+}
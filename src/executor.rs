@@ -1,29 +1,274 @@
 //! A basic, tree walking executor for the tram language
 
-use std::rc::Rc;
+use std::{cmp::Ordering, collections::HashMap, fmt::Display, fs, ops::Range, path::{Path, PathBuf}, rc::Rc, sync::{atomic::{AtomicU64, Ordering as AtomicOrdering}, Arc}, time::Instant};
 
-use crate::{corelib, fe::ast::{AstNode, BinOp, Statement, UnOp}, function::NativeFunction, handle::Handle, value::Value};
+use crate::{corelib::{self, Events, Logger, Rng, TestReport, Threads}, hooks::{address, native_names, Hooks}, memory, module, profile::{self, Profile}, fe::{ast::{AstNode, BinOp, Slot, Statement, Type, UnOp}, diagnostic::{self, Diagnostic, Level, Span}}, function::{Callable, NativeFunction}, handle::Handle, iterator::Iterate, userdata::Method, value::Value};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RuntimeError {
+    /// a value raised by `throw`
+    Thrown(Value),
+    AssertionFailed {
+        /// the source text of the failing condition
+        expr: String,
+        message: Option<String>,
+        span: Span
+    },
     CannotAdd,
     IncorrectNumberOfArgs,
     NotAFunction,
     NotANumber,
     NotAString,
-    NotAMap
+    NotAMap,
+    NotAnArray,
+    NotBytes,
+    NotAContainer,
+    NotIterable,
+    NotSliceable,
+    IndexOutOfRange,
+    /// an argument of the right type, but with a value the function can't take
+    InvalidArgument(String),
+    /// ordering values that have no order between them, like a string and a number
+    CannotCompare,
+    /// `%` with an int divisor of 0, which has no answer as an int, or
+    /// dividing by any zero with `VM::strict_math` on
+    DivisionByZero,
+    /// unwinds a generator's body back to `Generator::next`
+    Yield(Value),
+    /// `yield` was used somewhere a generator can't be resumed into
+    YieldInExpression,
+    GeneratorRunning,
+    /// an operating system error, with a description of what failed
+    Io(String),
+    /// malformed input given to a decoder like `json.parse`
+    InvalidFormat(String),
+    /// a value that an encoder like `json.stringify` has no representation for
+    CannotEncode(String),
+    /// no file was found for the path given to `use`
+    ModuleNotFound(String),
+    ModuleParseFailed(String),
+    /// a module imported itself, directly or through others. holds the
+    /// chain of files, like `a.tr → b.tr → a.tr`
+    ImportCycle(String),
+    /// an assert from the `test` module failed
+    TestFailed(String),
+    NotAnError,
+    /// an error made by a script with `error.new`
+    Custom {
+        kind: String,
+        message: String,
+        data: Value
+    },
+    /// the script ran for more steps than `VM::set_fuel` allowed
+    OutOfFuel,
+    /// the script used more memory than `VM::set_memory_limit` allowed
+    OutOfMemory,
+    /// something the VM's `VmOptions` don't allow, with what it needed
+    NotAllowed(String),
+    /// another error, along with where it happened
+    Located(Box<LocatedError>)
+}
+
+#[derive(Debug, Clone)]
+pub struct LocatedError {
+    pub error: RuntimeError,
+    pub span: Span,
+    /// the operation that failed and the kinds of values it was given, like `number + nil`
+    pub operation: Option<String>,
+    /// the code `span` points into, added by whatever ran it
+    pub source: Option<Rc<str>>,
+    /// whether the VM's `on_error` hook has seen the error
+    pub reported: bool
+}
+
+impl RuntimeError {
+    /// the name the `error` module gives this kind of error
+    pub fn kind(&self) -> &str {
+        use RuntimeError::*;
+        match self {
+            Thrown(_) => "thrown",
+            AssertionFailed { .. } => "assertion_failed",
+            CannotAdd => "cannot_add",
+            IncorrectNumberOfArgs => "incorrect_number_of_args",
+            NotAFunction => "not_a_function",
+            NotANumber => "not_a_number",
+            NotAString => "not_a_string",
+            NotAMap => "not_a_map",
+            NotAnArray => "not_an_array",
+            NotBytes => "not_bytes",
+            NotAContainer => "not_a_container",
+            NotIterable => "not_iterable",
+            NotSliceable => "not_sliceable",
+            IndexOutOfRange => "index_out_of_range",
+            InvalidArgument(_) => "invalid_argument",
+            CannotCompare => "cannot_compare",
+            DivisionByZero => "division_by_zero",
+            Yield(_) => "yield",
+            YieldInExpression => "yield_in_expression",
+            GeneratorRunning => "generator_running",
+            Io(_) => "io",
+            InvalidFormat(_) => "invalid_format",
+            CannotEncode(_) => "cannot_encode",
+            ModuleNotFound(_) => "module_not_found",
+            ModuleParseFailed(_) => "module_parse_failed",
+            ImportCycle(_) => "import_cycle",
+            TestFailed(_) => "test_failed",
+            NotAnError => "not_an_error",
+            Custom { kind, .. } => kind,
+            OutOfFuel => "out_of_fuel",
+            OutOfMemory => "out_of_memory",
+            NotAllowed(_) => "not_allowed",
+            Located(l) => l.error.kind()
+        }
+    }
+
+    /// a description of the error, without its kind
+    pub fn message(&self) -> String {
+        use RuntimeError::*;
+        match self {
+            Thrown(v) => v.to_string(),
+            AssertionFailed { expr, message: Some(message), .. } => format!("{} ({})", message, expr),
+            AssertionFailed { expr, message: None, .. } => format!("assertion failed: {}", expr),
+            InvalidArgument(m) | Io(m) | InvalidFormat(m) | CannotEncode(m)
+                | ImportCycle(m) | TestFailed(m) => m.clone(),
+            ModuleNotFound(path) => format!("no module found for `{}`", path),
+            ModuleParseFailed(path) => format!("module `{}` failed to parse", path),
+            NotAllowed(what) => format!("{} isn't allowed in this vm", what),
+            Custom { message, .. } => message.clone(),
+            Located(l) => l.error.message(),
+            e => e.kind().replace('_', " ")
+        }
+    }
+
+    /// whether the script has to stop, so `try` mustn't catch the error
+    pub fn fatal(&self) -> bool {
+        matches!(self.inner(), RuntimeError::OutOfFuel | RuntimeError::OutOfMemory)
+    }
+
+    /// the error without where it happened
+    pub fn inner(&self) -> &RuntimeError {
+        match self {
+            RuntimeError::Located(l) => &l.error,
+            e => e
+        }
+    }
+
+    /// notes that the error happened at `span`, unless it's
+    /// already known to have happened somewhere more specific
+    pub fn at(self, span: Span) -> Self {
+        self.locate(span, None::<fn() -> String>)
+    }
+
+    /// like `at`, also noting the operation that failed
+    pub fn during(self, span: Span, operation: impl FnOnce() -> String) -> Self {
+        self.locate(span, Some(operation))
+    }
+
+    fn locate(self, span: Span, operation: Option<impl FnOnce() -> String>) -> Self {
+        match self {
+            // a yield isn't a failure, and is caught by matching on it
+            e @ (RuntimeError::Located(_) | RuntimeError::Yield(_)) => e,
+            error => RuntimeError::Located(Box::new(LocatedError {
+                error,
+                span,
+                operation: operation.map(|o| o()),
+                source: None,
+                reported: false
+            }))
+        }
+    }
+
+    /// adds the code the error's span points into, if it doesn't have it yet.
+    /// errors are given the source of the innermost code they came from
+    pub fn with_source(mut self, source: &Rc<str>) -> Self {
+        if let RuntimeError::Located(l) = &mut self {
+            l.source.get_or_insert_with(|| source.clone());
+        }
+        self
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let RuntimeError::Located(l) = self else {
+            return write!(f, "{}", self.message())
+        };
+        write!(f, "{}", l.error)?;
+        if let Some(operation) = &l.operation {
+            write!(f, " in `{}`", operation)?;
+        }
+        if let Some(source) = &l.source {
+            let (line, col) = l.span.line_col(source);
+            write!(f, " at line {}, column {}\n{}", line, col, l.span.snippet(source, diagnostic::color()))?;
+        }
+        Ok(())
+    }
+}
+
+/// what a node is, for tracing
+fn describe(node: &AstNode) -> String {
+    match node {
+        AstNode::Call(..) => "call".to_owned(),
+        AstNode::Value(_) => "value".to_owned(),
+        AstNode::Ident(name, _, _) => format!("ident {}", name),
+        AstNode::Assign(name, _, _, _) => format!("assign {}", name),
+        AstNode::MultiAssign(names, _) => format!("assign {}", names.join(", ")),
+        AstNode::Binary(op, ..) => format!("binary {}", op.symbol()),
+        AstNode::Slice { .. } => "slice".to_owned(),
+        AstNode::Compare(_, chain, _) => {
+            let ops: Vec<_> = chain.iter().map(|(op, _)| op.symbol()).collect();
+            format!("compare {}", ops.join(" "))
+        },
+        AstNode::Unary(op, ..) => format!("unary {}", op.symbol()),
+        AstNode::If { .. } => "if".to_owned(),
+        AstNode::Block(..) => "block".to_owned(),
+        AstNode::Loop { .. } => "loop".to_owned(),
+        AstNode::For { binding, .. } => format!("for {}", binding),
+        AstNode::Break(_) => "break".to_owned(),
+        AstNode::Yield(_) => "yield".to_owned(),
+        AstNode::Try { .. } => "try".to_owned(),
+        AstNode::Throw(_) => "throw".to_owned(),
+        AstNode::Assert { .. } => "assert".to_owned(),
+        AstNode::Use { path, .. } => format!("use {}", path),
+        AstNode::Pub { name, .. } => format!("pub {}", name),
+        AstNode::Defer(_) => "defer".to_owned(),
+        AstNode::Error => "error".to_owned()
+    }
+}
+
+/// a traced result, cut short if it's long
+pub fn traced(out: Result<&Value, &RuntimeError>) -> String {
+    const MAX: usize = 60;
+    let text = match out {
+        Ok(v) => format!("{:?}", v),
+        Err(RuntimeError::Yield(_)) => "yielded".to_owned(),
+        Err(e) => format!("failed with {}", e.kind())
+    };
+    match text.char_indices().nth(MAX) {
+        Some((i, _)) => format!("{}...", &text[..i]),
+        None => text
+    }
 }
 
+/// where a `for` loop's variable is while its body runs,
+/// alone in the scope the loop opens for it
+pub const FOR_BINDING: Slot = Slot::Local { up: 0, index: 0 };
+
+/// the variables of every scope, innermost last. variables bound outside
+/// of any scope, like the standard library, are globals, which are kept
+/// apart so that looking one up doesn't mean searching every scope first
 pub struct LocalStack {
     markers: Vec<usize>,
-    locals: Vec<(String, Value)>
+    locals: Vec<(String, Value)>,
+    globals: HashMap<String, Value>
 }
 
 impl LocalStack {
     pub fn new() -> Self {
         Self {
             markers: Vec::new(),
-            locals: Vec::new()
+            locals: Vec::new(),
+            globals: HashMap::new()
         }
     }
 
@@ -33,16 +278,11 @@ impl LocalStack {
                 return l.1.clone();
             }
         }
-        return Value::Nil;
+        self.globals.get(key).cloned().unwrap_or(Value::Nil)
     }
 
     pub fn exists(&self, key: &str) -> bool {
-        for l in self.locals.iter().rev() {
-            if l.0 == key {
-                return true
-            }
-        }
-        return false
+        self.locals.iter().any(|l| l.0 == key) || self.globals.contains_key(key)
     }
 
     pub fn push(&mut self) {
@@ -51,11 +291,75 @@ impl LocalStack {
 
     pub fn pop(&mut self) {
         let pop = self.markers.pop().expect("popped nonexistant scope");
-        for i in (pop .. self.locals.len()).rev() {
-            self.locals.remove(i);
+        self.locals.truncate(pop);
+    }
+
+    /// the number of scopes currently pushed
+    pub fn depth(&self) -> usize {
+        self.markers.len()
+    }
+
+    /// pops scopes until only `depth` remain
+    pub fn unwind(&mut self, depth: usize) {
+        while self.depth() > depth {
+            self.pop();
+        }
+    }
+
+    /// binds `name` in the innermost scope, shadowing any outer variable
+    pub fn define(&mut self, name: &str, val: Value) {
+        if self.markers.is_empty() {
+            self.globals.insert(name.to_owned(), val);
+        } else {
+            self.locals.push((name.to_owned(), val))
         }
     }
 
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.globals
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// binds `name` outside of every scope, where anything can see it
+    /// that doesn't have a variable of its own by the same name
+    pub fn set_global(&mut self, name: &str, val: Value) {
+        self.globals.insert(name.to_owned(), val);
+    }
+
+    /// every variable that can be seen, innermost first. a name can
+    /// come up more than once, if one scope's variable shadows another's
+    pub fn visible(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.locals.iter().rev()
+            .map(|(name, val)| (name.as_str(), val))
+            .chain(self.globals.iter().map(|(name, val)| (name.as_str(), val)))
+    }
+
+    /// the variables bound in the innermost scope
+    pub fn innermost(&self) -> &[(String, Value)] {
+        let start = self.markers.last().copied().unwrap_or(0);
+        &self.locals[start..]
+    }
+
+    /// removes every scope above `depth`
+    pub fn split_off(&mut self, depth: usize) -> Scopes {
+        let start = self.markers.get(depth).copied().unwrap_or(self.locals.len());
+        let markers = self.markers.split_off(depth)
+            .into_iter()
+            .map(|m| m - start)
+            .collect();
+        Scopes { markers, locals: self.locals.split_off(start) }
+    }
+
+    /// pushes scopes previously removed by `split_off`
+    pub fn append(&mut self, scopes: Scopes) {
+        let start = self.locals.len();
+        self.markers.extend(scopes.markers.into_iter().map(|m| m + start));
+        self.locals.extend(scopes.locals);
+    }
+
     pub fn set(&mut self, name: &str, val: Value) {
         let mut idx: Option<usize> = None;
         for (i, (lname, _)) in self.locals.iter().enumerate().rev() {
@@ -64,17 +368,453 @@ impl LocalStack {
                 break;
             }
         }
-        if let Some(idx) = idx {
-            self.locals[idx] = (self.locals[idx].0.to_owned(), val);
-        } else {
-            self.locals.push((name.to_owned(), val))
+        match (idx, self.globals.get_mut(name)) {
+            (Some(idx), _) => self.locals[idx].1 = val,
+            (None, Some(global)) => *global = val,
+            (None, None) => self.define(name, val)
+        }
+    }
+
+    /// the position of `name` if it is where `slot` expects. a resolved
+    /// variable is never shadowed by a scope between its own and the innermost,
+    /// so finding it there means it's the one a search would find
+    fn find_slot(&self, name: &str, slot: Slot) -> Option<usize> {
+        let Slot::Local { up, index } = slot else {
+            return None
+        };
+        let scope = self.markers.len().checked_sub(1 + up as usize)?;
+        let end = self.markers.get(scope + 1).copied().unwrap_or(self.locals.len());
+        let at = self.markers[scope] + index as usize;
+        (at < end && self.locals[at].0 == name).then_some(at)
+    }
+
+    /// like `get`, but looks at `slot` before searching
+    pub fn get_slot(&self, name: &str, slot: Slot) -> Value {
+        match self.find_slot(name, slot) {
+            Some(at) => self.locals[at].1.clone(),
+            None => self.get(name)
         }
     }
+
+    /// the variable `name`, looking at `slot` before searching
+    pub fn get_slot_mut(&mut self, name: &str, slot: Slot) -> Option<&mut Value> {
+        match self.find_slot(name, slot).or_else(|| self.locals.iter().rposition(|l| l.0 == name)) {
+            Some(at) => Some(&mut self.locals[at].1),
+            None => self.globals.get_mut(name)
+        }
+    }
+
+    /// like `set`, but looks at `slot` before searching
+    pub fn set_slot(&mut self, name: &str, slot: Slot, val: Value) {
+        match self.find_slot(name, slot) {
+            Some(at) => self.locals[at].1 = val,
+            None => self.set(name, val)
+        }
+    }
+}
+
+/// scopes taken off of a `LocalStack`, with markers relative to the first
+#[derive(Debug)]
+pub struct Scopes {
+    markers: Vec<usize>,
+    locals: Vec<(String, Value)>
+}
+
+/// the scopes and deferred expressions of a suspended generator
+#[derive(Debug)]
+pub struct Frame {
+    scopes: Scopes,
+    deferred: Vec<(usize, Rc<AstNode>)>
+}
+
+/// where a suspended generator picks back up. each construct
+/// records one as a `yield` unwinds through it
+#[derive(Debug)]
+pub enum Resume {
+    /// the index of the statement to continue from
+    Block(usize),
+    /// whether the then branch was taken
+    If(bool),
+    Loop,
+    For(Rc<dyn Iterate>),
+    Try,
+    Catch
+}
+
+impl Default for LocalStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn compare(op: &BinOp, a: &Value, b: &Value) -> Result<bool, RuntimeError> {
+    let ord = a.num_cmp(b)?;
+    Ok(match op {
+        BinOp::Gt => ord == Some(Ordering::Greater),
+        BinOp::GtEq => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+        BinOp::Lt => ord == Some(Ordering::Less),
+        BinOp::LtEq => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+        _ => unreachable!("{:?} is not a comparison", op)
+    })
+}
+
+/// the most bytes repeating a string or array with `*` may make, whether or
+/// not there's a memory limit. anything bigger is surely a mistake, and
+/// would only fail to allocate
+const MAX_REPEAT_BYTES: usize = 1 << 32;
+
+/// the times to repeat the `kind` of `len` items, each `size` bytes,
+/// failing if that would be more than `MAX_REPEAT_BYTES`
+fn repeat_times(kind: &str, len: usize, size: usize, n: &Value) -> Result<usize, RuntimeError> {
+    let n = n.int()?.max(0) as usize;
+    match len.checked_mul(n).and_then(|items| items.checked_mul(size)) {
+        Some(bytes) if bytes <= MAX_REPEAT_BYTES => Ok(n),
+        _ => Err(RuntimeError::InvalidArgument(format!("{} is too many times to repeat {} of length {}", n, kind, len)))
+    }
+}
+
+/// applies a binary operator to two evaluated operands
+pub fn binary(op: &BinOp, a: Value, b: Value) -> Result<Value, RuntimeError> {
+    Ok(match op {
+        BinOp::Add => {
+            match (a, b) {
+                (Value::Array(a), Value::Array(b)) => {
+                    let mut new = a.borrow().clone();
+                    new.append(&mut b.borrow().clone());
+                    Value::Array(Handle::new(new))
+                },
+                (Value::String(a), Value::String(b)) => {
+                    let mut new = a.borrow().clone();
+                    new.push_str(&b.borrow());
+                    Value::String(Handle::new(new))
+                },
+                (Value::Bytes(a), Value::Bytes(b)) => {
+                    let mut new = a.borrow().clone();
+                    new.extend_from_slice(&b.borrow());
+                    Value::Bytes(Handle::new(new))
+                },
+                (a @ (Value::Int(_) | Value::Number(_)), b @ (Value::Int(_) | Value::Number(_))) => {
+                    a.arith(&b, i64::checked_add, |a, b| a + b)?
+                },
+                _ => return Err(RuntimeError::CannotAdd)
+            }
+        },
+        BinOp::Sub => a.arith(&b, i64::checked_sub, |a, b| a - b)?,
+        BinOp::Mul => match (a, b) {
+            (Value::String(s), n) | (n, Value::String(s)) => {
+                let s = s.borrow();
+                let n = repeat_times("a string", s.len(), 1, &n)?;
+                Value::String(Handle::new(s.repeat(n)))
+            },
+            (Value::Array(a), n) | (n, Value::Array(a)) => {
+                let a = a.borrow();
+                let n = repeat_times("an array", a.len(), std::mem::size_of::<Value>(), &n)?;
+                let repeated = a.iter().cycle().take(a.len() * n).cloned().collect();
+                Value::Array(Handle::new(repeated))
+            },
+            (a, b) => a.arith(&b, i64::checked_mul, |a, b| a * b)?
+        },
+        // division always produces a float
+        BinOp::Div => a.num_op(&b, |a, b| Ok(a / b))?,
+        BinOp::Pow => a.arith(
+            &b,
+            |a, b| u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            f64::powf
+        )?,
+        BinOp::Mod => match (&a, &b) {
+            (Value::Int(_), Value::Int(0)) => return Err(RuntimeError::DivisionByZero),
+            _ => a.arith(&b, i64::checked_rem, |a, b| a % b)?
+        },
+        BinOp::Eq => Value::Bool(a == b),
+        BinOp::NotEq => Value::Bool(a != b),
+        BinOp::Is => Value::Bool(a.same(&b)),
+        BinOp::In => Value::Bool(match &b {
+            Value::Array(arr) => arr.borrow().contains(&a),
+            Value::Map(map) => map.borrow().contains_key(&a),
+            Value::String(s) => s.borrow().contains(&*a.string()?.borrow()),
+            Value::Bytes(bytes) => {
+                let byte = a.int()?;
+                bytes.borrow().iter().any(|b| *b as i64 == byte)
+            },
+            _ => return Err(RuntimeError::NotAContainer)
+        }),
+        BinOp::Gt | BinOp::GtEq | BinOp::Lt | BinOp::LtEq => {
+            Value::Bool(compare(op, &a, &b)?)
+        },
+        BinOp::And => Value::Bool(a.truthy() && b.truthy()),
+        BinOp::Or => Value::Bool(a.truthy() || b.truthy()),
+        BinOp::BitAnd => a.int_op(&b, |a, b| a & b)?,
+        BinOp::BitOr => a.int_op(&b, |a, b| a | b)?,
+        BinOp::BitXor => a.int_op(&b, |a, b| a ^ b)?,
+        // shifting by a negative amount or past the width of
+        // an i64 shifts every bit out
+        BinOp::Shl => a.int_op(&b, |a, b| match u32::try_from(b) {
+            Ok(b) => a.checked_shl(b).unwrap_or(0),
+            Err(_) => 0
+        })?,
+        BinOp::Shr => a.int_op(&b, |a, b| match u32::try_from(b) {
+            Ok(b) if b < i64::BITS => a >> b,
+            _ => if a < 0 { -1 } else { 0 }
+        })?,
+        BinOp::Access => match &a {
+            Value::String(s) => {
+                let s = s.borrow();
+                let i = resolve_index(b.int()?, s.chars().count());
+                match i.and_then(|i| s.chars().nth(i)) {
+                    Some(c) => c.to_string().into(),
+                    None => Value::Nil
+                }
+            },
+            Value::Array(arr) => {
+                let arr = arr.borrow();
+                match resolve_index(b.int()?, arr.len()) {
+                    Some(i) => arr[i].clone(),
+                    None => Value::Nil
+                }
+            },
+            Value::Bytes(bytes) => {
+                let bytes = bytes.borrow();
+                match resolve_index(b.int()?, bytes.len()) {
+                    Some(i) => Value::Int(bytes[i] as i64),
+                    None => Value::Nil
+                }
+            },
+            Value::UserData(obj) => {
+                let name = b.string()?;
+                let name = name.borrow();
+                match obj.method(&name) {
+                    Some(func) => Value::Function(Rc::new(Method::new(obj.clone(), func))),
+                    None => return Err(RuntimeError::InvalidArgument(
+                        format!("a {} has no method `{}`", obj.type_name(), name)))
+                }
+            },
+            _ => {
+                let map = a.map()?;
+                let map = map.borrow();
+                match map.get(&b) {
+                    Some(v) => v.clone(),
+                    None => {
+                        Value::Nil
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// applies a unary operator to an evaluated operand
+pub fn unary(op: &UnOp, val: Value) -> Result<Value, RuntimeError> {
+    Ok(match op {
+        UnOp::Not => Value::Bool(!val.truthy()),
+        UnOp::Sub => match val {
+            Value::Int(i) => i.checked_neg()
+                .map(Value::Int)
+                .unwrap_or(Value::Number(-(i as f64))),
+            _ => Value::Number(-val.num()?)
+        },
+        UnOp::BitNot => Value::Int(!val.int()?)
+    })
+}
+
+/// the part of a string, array or bytes value between two bounds
+pub fn slice(target: Value, start: Option<i64>, end: Option<i64>) -> Result<Value, RuntimeError> {
+    Ok(match target {
+        Value::String(s) => {
+            let s = s.borrow();
+            let range = slice_range(start, end, s.chars().count());
+            let sliced: String = s.chars()
+                .skip(range.start)
+                .take(range.len())
+                .collect();
+            sliced.into()
+        },
+        Value::Array(arr) => {
+            let arr = arr.borrow();
+            let range = slice_range(start, end, arr.len());
+            Value::Array(Handle::new(arr[range].to_vec()))
+        },
+        Value::Bytes(bytes) => {
+            let bytes = bytes.borrow();
+            let range = slice_range(start, end, bytes.len());
+            Value::Bytes(Handle::new(bytes[range].to_vec()))
+        },
+        _ => return Err(RuntimeError::NotSliceable)
+    })
+}
+
+/// the error raised by `throw value`. rethrowing a caught
+/// error raises it again as it was
+pub fn throw(value: Value) -> RuntimeError {
+    match value {
+        Value::Error(e) => (*e).clone(),
+        thrown => RuntimeError::Thrown(thrown)
+    }
+}
+
+/// the value a `catch` binds for an error. caught errors
+/// keep their location, so rethrowing them keeps it too
+pub fn caught(e: RuntimeError) -> Value {
+    match e.inner() {
+        RuntimeError::Thrown(v) => v.clone(),
+        _ => Value::Error(Rc::new(e))
+    }
+}
+
+/// `binary`, noting where it failed and the kinds of values it was given
+pub fn binary_at(op: &BinOp, a: Value, b: Value, span: Span) -> Result<Value, RuntimeError> {
+    let kinds = (Type::of(&a).name(), Type::of(&b).name());
+    binary(op, a, b).map_err(|e| e.during(span, || match op {
+        BinOp::Access => format!("{}[{}]", kinds.0, kinds.1),
+        op => format!("{} {} {}", kinds.0, op.symbol(), kinds.1)
+    }))
+}
+
+/// `unary`, noting where it failed and the kind of value it was given
+pub fn unary_at(op: &UnOp, val: Value, span: Span) -> Result<Value, RuntimeError> {
+    let kind = Type::of(&val).name();
+    unary(op, val).map_err(|e| e.during(span, || format!("{}{}", op.symbol(), kind)))
+}
+
+/// turns a possibly negative index, which counts back from the end,
+/// into a position within `len`
+pub fn resolve_index(i: i64, len: usize) -> Option<usize> {
+    let i = if i < 0 { len as i64 + i } else { i };
+    usize::try_from(i).ok().filter(|i| *i < len)
+}
+
+/// the range selected by `[start..end]`, clamped to `len`
+pub fn slice_range(start: Option<i64>, end: Option<i64>, len: usize) -> Range<usize> {
+    let clamp = |i: i64| {
+        let i = if i < 0 { len as i64 + i } else { i };
+        i.clamp(0, len as i64) as usize
+    };
+    let start = start.map(clamp).unwrap_or(0);
+    let end = end.map(clamp).unwrap_or(len);
+    start..end.max(start)
+}
+
+/// the result of evaluating an expression in tail position
+pub enum Tail {
+    Value(Value),
+    Call(Rc<dyn Callable>, Vec<Value>, Span)
+}
+
+/// a call that hasn't returned yet
+pub struct CallSite {
+    pub func: Rc<dyn Callable>,
+    pub span: Span,
+    /// the file the call was made from, or `None` for the repl
+    pub file: Option<Rc<Path>>
 }
 
 pub struct VM {
     pub locals: LocalStack,
-    exit_flag: ExitFlag
+    /// expressions from `defer`, tagged with the scope depth they belong to
+    deferred: Vec<(usize, Rc<AstNode>)>,
+    /// built up as a generator suspends, and consumed as it resumes
+    pub resume: Vec<Resume>,
+    exit_flag: ExitFlag,
+    /// the files being run, innermost last, which `use` resolves paths against
+    pub files: Vec<Rc<Path>>,
+    /// the calls underway, innermost last. a call that fails is
+    /// left on the stack, so the trace can be printed later
+    pub calls: Vec<CallSite>,
+    /// the exports of every module that has been imported, by canonical path
+    pub modules: HashMap<PathBuf, Value>,
+    /// every file that's been run or imported, and its source, which
+    /// is how an error knows which file it happened in
+    sources: Vec<(Rc<Path>, Rc<str>)>,
+    /// directories searched, in order, for modules that
+    /// aren't found next to the importing file
+    search_path: Vec<PathBuf>,
+    /// the arguments given to the script, which `os.args()` returns
+    pub script_args: Vec<String>,
+    /// the state behind the `random` module
+    pub rng: Rng,
+    /// the threads started by `thread.spawn`
+    pub threads: Threads,
+    /// the timers and background work the event loop is waiting on
+    pub events: Events,
+    /// the cases run by `test.case`, summarized when the VM is dropped
+    pub tests: TestReport,
+    /// the level and format of the `log` module
+    pub logger: Logger,
+    /// runs scripts with the tree-walker even when they could be compiled
+    pub tree_walk: bool,
+    /// whether parsed code is simplified with `AstNode::optimize` before it runs
+    pub optimize: bool,
+    /// fails with `DivisionByZero` on `/` or `%` by zero, rather than giving
+    /// inf or nan as floats otherwise do
+    pub strict_math: bool,
+    /// how many more steps scripts may take, or `None` for no limit.
+    /// shared with the threads scripts spawn, which draw on the same fuel
+    fuel: Option<Arc<AtomicU64>>,
+    /// whether each node evaluated or instruction run is printed
+    trace: bool,
+    /// the timings of every call, while profiling
+    profile: Option<Profile>,
+    hooks: Hooks,
+    /// the names of the standard library's natives, for the hooks and profile
+    natives: HashMap<usize, String>,
+    /// whether anything is tracing, profiling or hooked into the VM,
+    /// without which running a script doesn't need to check for any of them
+    watched: bool,
+    /// how deep in the tree the node being traced is
+    trace_depth: usize,
+    memory_limit: Option<MemoryLimit>,
+    options: VmOptions
+}
+
+/// what scripts run by a VM may do, decided when it's made. anything not
+/// allowed is left out of the standard library, so a host can run code it
+/// doesn't trust without giving it access to the machine
+#[derive(Debug, Clone, Copy)]
+pub struct VmOptions {
+    /// the `fs` and `sqlite` modules, `run`, and importing files with `use`
+    pub allow_fs: bool,
+    /// the `net` and `http` modules
+    pub allow_net: bool,
+    /// the `process` and `os` modules, and `exit`
+    pub allow_process: bool,
+    /// the terminal: `print`, `prompt`, and the `io`, `term`, `log` and `test` modules
+    pub allow_stdout: bool
+}
+
+impl VmOptions {
+    /// allows nothing but computing
+    pub fn sandboxed() -> Self {
+        Self { allow_fs: false, allow_net: false, allow_process: false, allow_stdout: false }
+    }
+}
+
+/// allows everything
+impl Default for VmOptions {
+    fn default() -> Self {
+        Self { allow_fs: true, allow_net: true, allow_process: true, allow_stdout: true }
+    }
+}
+
+/// the most memory scripts may use, on top of what
+/// was already in use when the limit was set
+#[derive(Clone, Copy)]
+struct MemoryLimit {
+    base: usize,
+    max: usize
+}
+
+/// the fuel and memory a VM's scripts may use, as handed to the threads they
+/// spawn. the threads take from the same fuel and count against the same
+/// memory limit, so spawning one doesn't get around either
+#[derive(Clone)]
+pub struct Budget {
+    fuel: Option<Arc<AtomicU64>>,
+    memory_limit: Option<MemoryLimit>
+}
+
+impl Default for VM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 enum ExitFlag {
@@ -84,160 +824,929 @@ enum ExitFlag {
 
 impl VM {
     pub fn new() -> Self {
+        Self::with_options(VmOptions::default())
+    }
+
+    pub fn with_options(options: VmOptions) -> Self {
         Self {
             locals: LocalStack::new(),
-            exit_flag: ExitFlag::Continue
+            deferred: Vec::new(),
+            resume: Vec::new(),
+            exit_flag: ExitFlag::Continue,
+            files: Vec::new(),
+            calls: Vec::new(),
+            modules: HashMap::new(),
+            sources: Vec::new(),
+            search_path: Vec::new(),
+            script_args: Vec::new(),
+            rng: Rng::from_time(),
+            threads: Threads::default(),
+            events: Events::default(),
+            tests: TestReport::default(),
+            logger: Logger::default(),
+            tree_walk: false,
+            optimize: false,
+            strict_math: false,
+            fuel: None,
+            trace: false,
+            profile: None,
+            hooks: Hooks::default(),
+            natives: HashMap::new(),
+            watched: false,
+            trace_depth: 0,
+            memory_limit: None,
+            options
+        }
+    }
+
+    pub fn options(&self) -> &VmOptions {
+        &self.options
+    }
+
+    /// records a call made at `span` as underway
+    pub fn push_call(&mut self, func: Rc<dyn Callable>, span: Span) {
+        let file = self.files.last().cloned();
+        self.calls.push(CallSite { func, span, file });
+    }
+
+    /// tells the profile and hooks about a call to `func` starting,
+    /// returning when it did if anything is watching calls
+    #[inline]
+    pub fn enter_call(&mut self, func: &dyn Callable, args: &[Value]) -> Option<Instant> {
+        if !self.watched || (self.profile.is_none() && self.hooks.on_call.is_none() && self.hooks.on_return.is_none()) {
+            return None
         }
+        let name = self.call_name(func);
+        if let Some(hook) = &mut self.hooks.on_call {
+            hook(&name, args);
+        }
+        Some(match &mut self.profile {
+            Some(profile) => profile.enter(profile::key(func), || name),
+            None => Instant::now()
+        })
+    }
+
+    /// tells the profile and hooks about the call to `func` that `enter_call`
+    /// saw start having finished, with what it returned unless it failed.
+    /// a function that ends in a tail call returns through the one it calls
+    #[inline]
+    pub fn leave_call(&mut self, func: &dyn Callable, start: Option<Instant>, returned: Option<&Value>) {
+        let Some(start) = start else {
+            return
+        };
+        if let Some(profile) = &mut self.profile {
+            profile.leave(&profile::key(func), start);
+        }
+        if let (Some(val), true) = (returned, self.hooks.on_return.is_some()) {
+            let name = self.call_name(func);
+            if let Some(hook) = &mut self.hooks.on_return {
+                hook(&name, val);
+            }
+        }
+    }
+
+    /// tells the `on_statement` hook about a statement at `span` starting
+    #[inline]
+    pub fn enter_statement(&mut self, span: Span) {
+        if !self.watched || span.is_empty() {
+            return
+        }
+        if let Some(hook) = &mut self.hooks.on_statement {
+            hook(span);
+        }
+    }
+
+    /// tells the `on_error` hook about `e` if it hasn't seen it yet. errors
+    /// are reported once they're located, which is where they happened
+    pub fn report_error(&mut self, e: &mut RuntimeError) {
+        if let (RuntimeError::Located(l), Some(hook)) = (e, &mut self.hooks.on_error) {
+            if !l.reported {
+                l.reported = true;
+                hook(&RuntimeError::Located(l.clone()));
+            }
+        }
+    }
+
+    /// the name of a function, as the hooks and profile give it
+    pub fn call_name(&self, func: &dyn Callable) -> String {
+        match func.as_function() {
+            Some(f) => {
+                let (line, _) = f.span.line_col(&f.source);
+                format!("{} (line {})", f.name.as_deref().unwrap_or("<anonymous>"), line)
+            },
+            None => self.natives.get(&address(func)).cloned().unwrap_or_else(|| func.display())
+        }
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.locals.global(name).cloned()
+    }
+
+    /// binds `name` for every script the VM runs, unless
+    /// one has a variable of its own by the same name
+    pub fn set_global(&mut self, name: &str, val: Value) {
+        self.locals.set_global(name, val);
+    }
+
+    /// starts timing every call, or stops and throws away the timings
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profile = profiling.then(Profile::default);
+        self.update_watched();
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn on_call(&mut self, hook: impl FnMut(&str, &[Value]) + 'static) {
+        self.hooks.on_call = Some(Box::new(hook));
+        self.update_watched();
     }
 
+    pub fn on_return(&mut self, hook: impl FnMut(&str, &Value) + 'static) {
+        self.hooks.on_return = Some(Box::new(hook));
+        self.update_watched();
+    }
+
+    pub fn on_statement(&mut self, hook: impl FnMut(Span) + 'static) {
+        self.hooks.on_statement = Some(Box::new(hook));
+        self.update_watched();
+    }
+
+    pub fn on_error(&mut self, hook: impl FnMut(&RuntimeError) + 'static) {
+        self.hooks.on_error = Some(Box::new(hook));
+        self.update_watched();
+    }
+
+    /// removes every hook
+    pub fn clear_hooks(&mut self) {
+        self.hooks = Hooks::default();
+        self.update_watched();
+    }
+
+    fn update_watched(&mut self) {
+        self.watched = self.trace || self.profile.is_some() || self.hooks.any();
+        // natives are named by where they are in the standard library,
+        // which is taken to have been registered by now
+        if self.watched && self.natives.is_empty() {
+            self.natives = native_names(self.locals.globals());
+        }
+    }
+
+    /// calls `func`, keeping track of it in the call stack
+    pub fn call_at(&mut self, func: Rc<dyn Callable>, args: Vec<Value>, span: Span) -> Result<Value, RuntimeError> {
+        let depth = self.calls.len();
+        self.push_call(func.clone(), span);
+        let out = func.call(self, args).map_err(|e| e.at(span))?;
+        self.calls.truncate(depth);
+        Ok(out)
+    }
+
+    /// notes that `source` was loaded from `file`
+    pub fn add_source(&mut self, file: &Path, source: &Rc<str>) {
+        self.sources.push((file.into(), source.clone()));
+    }
+
+    /// the error as a diagnostic, with the calls that were underway as notes.
+    /// `source` is used for code that wasn't loaded from a file
+    pub fn diagnostic(&self, e: &RuntimeError, source: Option<&str>) -> Diagnostic {
+        let (message, span, file) = match e {
+            RuntimeError::Located(l) => {
+                let mut message = l.error.to_string();
+                if let Some(operation) = &l.operation {
+                    message.push_str(&format!(" in `{}`", operation));
+                }
+                // functions keep the text they were parsed from, not its path
+                let file = l.source.as_ref().and_then(|text| self.sources.iter().find(|(_, s)| s == text))
+                    .map(|(f, _)| f.display().to_string());
+                (message, Some(l.span), file)
+            },
+            e => (e.to_string(), None, None)
+        };
+        Diagnostic {
+            level: Level::Error,
+            message,
+            span,
+            notes: self.backtrace(source),
+            help: Vec::new(),
+            file
+        }
+    }
+
+    /// writes an error that ended a script to stderr as a diagnostic
+    pub fn emit_error(&self, e: &RuntimeError, source: Option<&str>) {
+        let text = match e {
+            RuntimeError::Located(l) => l.source.as_deref(),
+            _ => None
+        };
+        self.diagnostic(e, source).emit(text.or(source));
+    }
+
+    /// the calls that were underway when the last uncaught error happened,
+    /// innermost first. `source` is used for calls made outside of any file
+    pub fn backtrace(&self, source: Option<&str>) -> Vec<String> {
+        let mut sources: HashMap<&Path, Option<String>> = HashMap::new();
+        self.calls.iter().rev().map(|site| {
+            let name = match site.func.as_function() {
+                Some(f) => f.name.clone().unwrap_or_else(|| "<anonymous>".to_owned()),
+                None => site.func.display()
+            };
+            let (path, text) = match &site.file {
+                Some(f) => {
+                    let text = sources.entry(f).or_insert_with(|| fs::read_to_string(f).ok());
+                    (f.display().to_string(), text.as_deref())
+                },
+                None => ("<repl>".to_owned(), source)
+            };
+            match text {
+                Some(text) => {
+                    let (line, col) = site.span.line_col(text);
+                    format!("in {} at {}:{}:{}", name, path, line, col)
+                },
+                None => format!("in {} at {}", name, path)
+            }
+        }).collect()
+    }
+
+    /// adds a directory for `use` to look for modules in
+    pub fn add_search_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.search_path.push(dir.into());
+    }
+
+    /// limits how many more steps scripts may take before failing with
+    /// `OutOfFuel`. a step is an expression evaluated by the tree-walker or
+    /// an instruction run by the bytecode machine. `None` removes the limit
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel.map(|fuel| Arc::new(AtomicU64::new(fuel)));
+    }
+
+    /// the steps left before scripts run out of fuel
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel.as_ref().map(|fuel| fuel.load(AtomicOrdering::Relaxed))
+    }
+
+    /// the fuel and memory limit, for a VM that should share them
+    pub fn budget(&self) -> Budget {
+        Budget { fuel: self.fuel.clone(), memory_limit: self.memory_limit }
+    }
+
+    /// makes this VM take from the same fuel and memory limit as the one
+    /// `budget` came from, replacing any of its own
+    pub fn share_budget(&mut self, budget: Budget) {
+        (self.fuel, self.memory_limit) = (budget.fuel, budget.memory_limit);
+    }
+
+    /// prints every node the tree-walker evaluates, and every instruction
+    /// the bytecode machine runs, to stderr along with its result. the
+    /// spans printed are character offsets into the code being run
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+        self.update_watched();
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.trace
+    }
+
+    /// whether anything is tracing, profiling or hooked into the VM
+    #[inline]
+    pub fn watched(&self) -> bool {
+        self.watched
+    }
+
+    /// limits how much more memory scripts may use, counting from what's in
+    /// use now, before failing with `OutOfMemory`. everything the process
+    /// allocates counts, on any thread, so this is only roughly what the
+    /// script's values take up. `None` removes the limit
+    pub fn set_memory_limit(&mut self, bytes: Option<usize>) {
+        self.memory_limit = bytes.map(|max| MemoryLimit { base: memory::live(), max });
+    }
+
+    /// the most memory scripts may use, as given to `set_memory_limit`
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit.as_ref().map(|limit| limit.max)
+    }
+
+    /// fails if using `bytes` more memory would go over the limit. the
+    /// limit is checked at every step, but anything that makes a value of
+    /// a size the script picks should check before it allocates
+    pub fn reserve(&self, bytes: usize) -> Result<(), RuntimeError> {
+        match &self.memory_limit {
+            Some(limit) if memory::live().saturating_add(bytes) > limit.base.saturating_add(limit.max) => {
+                Err(RuntimeError::OutOfMemory)
+            },
+            _ => Ok(())
+        }
+    }
+
+    /// takes a step, failing if there's no fuel left for it
+    /// or the script is using too much memory
+    #[inline]
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        if let Some(fuel) = &self.fuel {
+            if fuel.fetch_update(AtomicOrdering::Relaxed, AtomicOrdering::Relaxed, |f| f.checked_sub(1)).is_err() {
+                return Err(RuntimeError::OutOfFuel)
+            }
+        }
+        if self.memory_limit.is_some() {
+            self.reserve(0)?;
+        }
+        Ok(())
+    }
+
+    /// `name += b`, where `a` is the value `name` had. if `a` and the variable
+    /// hold the only handles to a string, array or bytes, weak ones included,
+    /// `b` is appended to it in place, since nothing else could see the change.
+    /// that makes building up a value one piece at a time take linear time
+    /// rather than quadratic
+    pub fn add_assign(&mut self, name: &str, slot: Slot, a: Value, b: Value, span: Span) -> Result<(), RuntimeError> {
+        if matches!(a, Value::String(_) | Value::Array(_) | Value::Bytes(_)) {
+            if let Some(var) = self.locals.get_slot_mut(name, slot) {
+                // with only two handles, `b` can't be the same value, nor contain it
+                let appended = match (&*var, &a, &b) {
+                    (Value::String(v), Value::String(a), Value::String(b)) if v.ptr_eq(a) && v.refs() == 2 => {
+                        v.borrow_mut().push_str(&b.borrow());
+                        true
+                    },
+                    (Value::Array(v), Value::Array(a), Value::Array(b)) if v.ptr_eq(a) && v.refs() == 2 => {
+                        v.borrow_mut().extend(b.borrow().iter().cloned());
+                        true
+                    },
+                    (Value::Bytes(v), Value::Bytes(a), Value::Bytes(b)) if v.ptr_eq(a) && v.refs() == 2 => {
+                        v.borrow_mut().extend_from_slice(&b.borrow());
+                        true
+                    },
+                    _ => false
+                };
+                if appended {
+                    return Ok(())
+                }
+            }
+        }
+        let val = binary_at(&BinOp::Add, a, b, span)?;
+        self.locals.set_slot(name, slot, val);
+        Ok(())
+    }
+
+    /// fails if `op` would repeat a string or array past the memory limit,
+    /// or divide by zero with `strict_math` on
+    pub fn check_binary(&self, op: &BinOp, a: &Value, b: &Value) -> Result<(), RuntimeError> {
+        if self.strict_math && matches!(op, BinOp::Div | BinOp::Mod) && a.num().is_ok() && b.num().is_ok_and(|n| n == 0.0) {
+            return Err(RuntimeError::DivisionByZero)
+        }
+        if self.memory_limit.is_none() || *op != BinOp::Mul {
+            return Ok(())
+        }
+        let (bytes, n) = match (a, b) {
+            (Value::String(s), n) | (n, Value::String(s)) => (s.borrow().len(), n),
+            (Value::Array(arr), n) | (n, Value::Array(arr)) => {
+                (arr.borrow().len() * std::mem::size_of::<Value>(), n)
+            },
+            _ => return Ok(())
+        };
+        let n = n.int().unwrap_or(0).max(0) as usize;
+        self.reserve(bytes.saturating_mul(n))
+    }
+
+    pub fn search_path(&self) -> &[PathBuf] {
+        &self.search_path
+    }
+
+    /// readies freshly parsed code to be run, optimizing it if that's
+    /// turned on, then resolving its variables
+    pub fn prepare(&self, prog: &mut AstNode) {
+        if self.optimize {
+            prog.optimize();
+        }
+        prog.resolve();
+    }
+
+    pub fn push_scope(&mut self) {
+        self.locals.push();
+    }
+
+    /// runs the current scope's deferred expressions, then pops it
+    pub fn pop_scope(&mut self) -> Result<(), RuntimeError> {
+        let out = self.run_deferred();
+        self.locals.pop();
+        out
+    }
+
+    /// runs, most recent first, every expression deferred in the current scope
+    /// (or a deeper one). all of them run, and the first error is returned
+    pub fn run_deferred(&mut self) -> Result<(), RuntimeError> {
+        let depth = self.locals.depth();
+        let mut out = Ok(());
+        while let Some((d, _)) = self.deferred.last() {
+            if *d < depth {
+                break
+            }
+            let (_, expr) = self.deferred.pop().unwrap();
+            if let Err(e) = self.execute(&expr) {
+                out = out.and(Err(e));
+            }
+        }
+        out
+    }
+
+    /// runs `expr` when the current scope is popped
+    pub fn defer(&mut self, expr: Rc<AstNode>) {
+        self.deferred.push((self.locals.depth(), expr));
+    }
+
+    /// removes every scope above `depth` along with their deferred
+    /// expressions, so a suspended generator can take them with it
+    pub fn save_frame(&mut self, depth: usize) -> Frame {
+        let scopes = self.locals.split_off(depth);
+        let split = self.deferred.iter()
+            .position(|(d, _)| *d > depth)
+            .unwrap_or(self.deferred.len());
+        let deferred = self.deferred.split_off(split)
+            .into_iter()
+            .map(|(d, e)| (d - depth, e))
+            .collect();
+        Frame { scopes, deferred }
+    }
+
+    /// pushes a frame taken by `save_frame` on top of the current scopes
+    pub fn restore_frame(&mut self, frame: Frame) {
+        let depth = self.locals.depth();
+        self.locals.append(frame.scopes);
+        self.deferred.extend(frame.deferred.into_iter().map(|(d, e)| (d + depth, e)));
+    }
+
+    /// records where to resume if `out` is a generator suspending
+    fn suspend<T>(&mut self, out: Result<T, RuntimeError>, point: Resume) -> Result<T, RuntimeError> {
+        if let Err(RuntimeError::Yield(_)) = &out {
+            self.resume.push(point);
+        }
+        out
+    }
+
+    pub(crate) fn breaking(&self) -> bool {
+        matches!(self.exit_flag, ExitFlag::Break(_))
+    }
+
+    /// starts a break out of the loop with `label`, or the innermost loop
+    pub(crate) fn start_break(&mut self, label: Option<String>) {
+        self.exit_flag = ExitFlag::Break(label);
+    }
+
+    /// ends a break aimed at a loop with `label`
+    pub(crate) fn take_break(&mut self, label: &Option<String>) -> bool {
+        let mut should_break = false;
+        if let ExitFlag::Break(elabel) = &self.exit_flag {
+            if let (Some(l1), Some(l2)) = (label, elabel) {
+                should_break = l1 == l2
+            } else { should_break = true }
+        }
+        if should_break {
+            self.exit_flag = ExitFlag::Continue;
+        }
+        should_break
+    }
+
+    fn args(&mut self, args: &[AstNode]) -> Result<Vec<Value>, RuntimeError> {
+        let mut vargs = Vec::with_capacity(args.len());
+        for a in args {
+            let computed = self.execute(a)?;
+            vargs.push(computed);
+        }
+        Ok(vargs)
+    }
+
+    /// like `execute`, but a call in tail position is returned unmade,
+    /// so the caller can make it without growing the stack
+    pub fn execute_tail(&mut self, a: &AstNode) -> Result<Tail, RuntimeError> {
+        Ok(match a {
+            AstNode::Call(func, args, span) => {
+                let func = self.execute(func)?.func()?;
+                let vargs = self.args(args)?;
+                Tail::Call(func, vargs, *span)
+            },
+            AstNode::If { cond, then, or, .. } => {
+                let cond = self.execute(cond)?;
+                if cond.truthy() {
+                    self.execute_tail(then)?
+                } else if let Some(or) = or {
+                    self.execute_tail(or)?
+                } else {
+                    Tail::Value(Value::Nil)
+                }
+            },
+            AstNode::Block(stmts, scoped) => {
+                if *scoped {
+                    self.push_scope();
+                }
+                let out = match stmts.split_last() {
+                    Some((Statement::Expression(last, span), init)) => self.statements(init, 0)
+                        .and_then(|_| if self.breaking() {
+                            Ok(Tail::Value(Value::Nil))
+                        } else {
+                            self.enter_statement(*span);
+                            self.execute_tail(last)
+                        })
+                        .and_then(|t| self.settle_tail(t)),
+                    None => Ok(Tail::Value(Value::Nil))
+                };
+                if *scoped {
+                    let popped = self.pop_scope();
+                    out.and_then(|v| popped.map(|_| v))?
+                } else {
+                    out?
+                }
+            },
+            _ => Tail::Value(self.execute(a)?)
+        })
+    }
+
+    /// makes a pending tail call right away if the current scope has deferred
+    /// expressions, since those must only run once the call has returned
+    pub fn settle_tail(&mut self, tail: Tail) -> Result<Tail, RuntimeError> {
+        let depth = self.locals.depth();
+        match tail {
+            Tail::Call(func, args, span) if self.deferred.last().is_some_and(|(d, _)| *d >= depth) => {
+                Ok(Tail::Value(self.call_at(func, args, span)?))
+            },
+            tail => Ok(tail)
+        }
+    }
+
+    /// runs `stmts` from `start`, stopping early on a break
+    fn statements(&mut self, stmts: &[Statement], start: usize) -> Result<Value, RuntimeError> {
+        let mut out = Value::Nil;
+        for (i, Statement::Expression(x, span)) in stmts.iter().enumerate().skip(start) {
+            self.enter_statement(*span);
+            match self.execute(x) {
+                Ok(v) => out = v,
+                Err(RuntimeError::Yield(v)) => {
+                    let next = match **x {
+                        AstNode::Yield(_) => i + 1,
+                        AstNode::Block(..) | AstNode::If { .. } | AstNode::Loop { .. }
+                            | AstNode::For { .. } | AstNode::Try { .. } => i,
+                        _ => return Err(RuntimeError::YieldInExpression)
+                    };
+                    self.resume.push(Resume::Block(next));
+                    return Err(RuntimeError::Yield(v))
+                },
+                Err(e) => return Err(e)
+            }
+            if self.breaking() {
+                break
+            }
+        }
+        Ok(out)
+    }
+
+    /// runs a catch handler in the scope holding its binding
+    fn handle(&mut self, handler: &AstNode) -> Result<Value, RuntimeError> {
+        let out = self.execute(handler);
+        if let Err(RuntimeError::Yield(_)) = out {
+            self.resume.push(Resume::Catch);
+            return out
+        }
+        let popped = self.pop_scope();
+        out.and_then(|v| popped.map(|_| v))
+    }
+
+    fn for_loop(&mut self, binding: &str, iter: &Rc<dyn Iterate>, run: &AstNode, mut resumed: bool)
+        -> Result<(), RuntimeError> {
+        loop {
+            if !resumed {
+                match iter.next(self)? {
+                    Some(v) => self.locals.set_slot(binding, FOR_BINDING, v),
+                    None => break
+                }
+            }
+            resumed = false;
+            self.execute(run)?;
+            if self.take_break(&None) {
+                break
+            }
+        }
+        Ok(())
+    }
+
+    /// defines the standard library's globals, leaving out the
+    /// ones that do something the VM's options don't allow
     pub fn register_stdlib(&mut self) {
-        let funcs: &[(&str, NativeFunction)] = &[
-            ("print", corelib::print),
-            ("prompt", corelib::prompt),
-            ("exit", corelib::exit),
-            ("type", corelib::corelib_type),
-            ("run", corelib::run),
-            ("sleep", corelib::sleep),
+        let VmOptions { allow_fs, allow_net, allow_process, allow_stdout } = self.options;
+        let funcs: &[(&str, NativeFunction, bool)] = &[
+            ("print", corelib::print, allow_stdout),
+            ("prompt", corelib::prompt, allow_stdout),
+            ("exit", corelib::exit, allow_process),
+            ("type", corelib::corelib_type, true),
+            ("run", corelib::run, allow_fs),
+            ("sleep", corelib::sleep, true),
+            ("after", corelib::after, true),
+            ("await", corelib::await_task, true),
+            ("num", corelib::num, true),
+            ("str", corelib::str, true),
+            ("to_fixed", corelib::to_fixed, true),
+            ("to_base", corelib::to_base, true),
+            ("format", corelib::format, true),
+            ("clone", corelib::clone, true),
+            ("deep_eq", corelib::deep_eq, true),
+            ("weak", corelib::weak, true),
+            ("deref", corelib::deref, true),
         ];
-        let funcs = funcs.into_iter()
-            .map(|(n, f)| (*n, Value::Function(Rc::new(*f))));
+        let funcs = funcs.iter()
+            .filter(|(_, _, allowed)| *allowed)
+            .map(|(n, f, _)| (*n, Value::Function(Rc::new(*f))));
 
-        let objs = [
-            ("math", corelib::math())
+        // without the optional modules nothing is pushed
+        #[allow(unused_mut)]
+        let mut objs = vec![
+            ("math", corelib::math()),
+            ("string", corelib::string()),
+            ("array", corelib::array()),
+            ("map", corelib::map()),
+            ("iter", corelib::iter()),
+            ("coroutine", corelib::coroutine()),
+            ("thread", corelib::thread()),
+            ("bytes", corelib::bytes()),
+            ("time", corelib::time()),
+            ("random", corelib::random()),
+            ("uuid", corelib::uuid()),
+            ("json", corelib::json()),
+            ("regex", corelib::regex()),
+            ("path", corelib::path()),
+            ("encoding", corelib::encoding()),
+            ("hash", corelib::hash()),
+            ("gzip", corelib::gzip()),
+            ("csv", corelib::csv()),
+            ("fn", corelib::func()),
+            ("error", corelib::error())
         ];
+        #[cfg(feature = "toml")]
+        objs.push(("toml", corelib::toml()));
+        #[cfg(feature = "yaml")]
+        objs.push(("yaml", corelib::yaml()));
+        #[cfg(feature = "zstd")]
+        objs.push(("zstd", corelib::zstd()));
+        #[cfg(feature = "sqlite")]
+        if allow_fs {
+            objs.push(("sqlite", corelib::sqlite()));
+        }
+        if allow_fs {
+            objs.push(("fs", corelib::fs()));
+        }
+        if allow_net {
+            objs.extend([("net", corelib::net()), ("http", corelib::http())]);
+        }
+        if allow_process {
+            objs.extend([("os", corelib::os()), ("process", corelib::process())]);
+        }
+        if allow_stdout {
+            // `test` prints each case, and the summary once the VM is dropped
+            objs.extend([("io", corelib::io()), ("term", corelib::term()), ("log", corelib::log()), ("test", corelib::test())]);
+        }
 
         let globals = objs.into_iter()
             .chain(funcs);
 
         for (name, global) in globals {
-            self.locals.set(name, global);
+            self.set_global(name, global);
         }
     }
 
     pub fn execute(&mut self, a: &AstNode) -> Result<Value, RuntimeError> {
+        if !self.watched {
+            return self.evaluate(a)
+        }
+        self.trace_depth += 1;
+        let mut out = self.evaluate(a);
+        self.trace_depth -= 1;
+        if let Err(e) = &mut out {
+            self.report_error(e);
+        }
+        if self.trace {
+            let indent = "  ".repeat(self.trace_depth);
+            let span = a.span().map(|s| format!(" at {}..{}", s.start, s.end)).unwrap_or_default();
+            eprintln!("[trace] {}{}{} => {}", indent, describe(a), span, traced(out.as_ref()));
+        }
+        out
+    }
+
+    fn evaluate(&mut self, a: &AstNode) -> Result<Value, RuntimeError> {
+        self.step()?;
         Ok(match a {
-            AstNode::Call(func, args) => {
+            AstNode::Call(func, args, span) => {
                 let func = self.execute(func)?;
-                let mut vargs = Vec::with_capacity(args.len());
-                for a in args {
-                    let computed = self.execute(a)?;
-                    vargs.push(computed);
-                }
-                func.func()?.call(self, vargs)?
+                let vargs = self.args(args)?;
+                self.call_at(func.func()?, vargs, *span)?
             },
             AstNode::Value(v) => (**v).clone(),
-            AstNode::Ident(i) => {
-                self.locals.get(&i)
+            AstNode::Ident(i, slot, _) => {
+                self.locals.get_slot(i, *slot)
+            },
+            AstNode::Assign(n, slot, v, _) => {
+                match &**v {
+                    AstNode::Binary(BinOp::Add, lhs, rhs, span) if matches!(&**lhs, AstNode::Ident(m, _, _) if m == n) => {
+                        let a = self.execute(lhs)?;
+                        let b = self.execute(rhs)?;
+                        self.add_assign(n, *slot, a, b, *span)?;
+                    },
+                    v => {
+                        let val = self.execute(v)?;
+                        self.locals.set_slot(n, *slot, val);
+                    }
+                }
+                Value::Nil
             },
-            AstNode::Assign(n, v) => {
-                let val = self.execute(v)?;
-                self.locals.set(&n, val);
+            AstNode::MultiAssign(names, values) => {
+                let values = self.args(values)?;
+                for (name, val) in names.iter().zip(values) {
+                    self.locals.set(name, val);
+                }
                 Value::Nil
             },
-            AstNode::Binary(op, a, b) => {
+            AstNode::Binary(op, a, b, span) => {
                 let a = self.execute(a)?;
                 let b = self.execute(b)?;
-                match op {
-                    BinOp::Add => {
-                        match (a, b) {
-                            (Value::Array(a), Value::Array(b)) => {
-                                let mut new = a.borrow().clone();
-                                new.append(&mut b.borrow().clone());
-                                Value::Array(Handle::new(new))
-                            },
-                            (Value::String(a), Value::String(b)) => {
-                                let mut new = a.borrow().clone();
-                                new.push_str(&b.borrow());
-                                Value::String(Handle::new(new))
-                            }
-                            (Value::Number(a), Value::Number(b)) => {
-                                Value::Number(a + b)
-                            },
-                            _ => return Err(RuntimeError::CannotAdd)
-                        }
-                    },
-                    BinOp::Sub => a.num_op(&b, |a, b| Ok(a - b))?,
-                    BinOp::Mul => a.num_op(&b, |a, b| Ok(a * b))?,
-                    BinOp::Div => a.num_op(&b, |a, b| Ok(a / b))?,
-                    BinOp::Pow => a.num_op(&b, |a, b| Ok(a.powf(b)))?,
-                    BinOp::Mod => a.num_op(&b, |a, b| Ok(a % b))?,
-                    BinOp::Eq => Value::Bool(a == b),
-                    BinOp::NotEq => Value::Bool(a != b),
-                    BinOp::Gt => Value::Bool(a.num()? > b.num()?),
-                    BinOp::GtEq => Value::Bool(a.num()? >= b.num()?),
-                    BinOp::Lt => Value::Bool(a.num()? < b.num()?),
-                    BinOp::LtEq => Value::Bool(a.num()? <= b.num()?),
-                    BinOp::And => Value::Bool(a.truthy() && b.truthy()),
-                    BinOp::Or => Value::Bool(a.truthy() || b.truthy()),
-                    BinOp::Access => {
-                        let map = a.map()?;
-                        let map = map.borrow();
-                        match map.get(&b) {
-                            Some(v) => v.clone(),
-                            None => {
-                                Value::Nil
-                            }
-                        }
+                self.check_binary(op, &a, &b).map_err(|e| e.at(*span))?;
+                binary_at(op, a, b, *span)?
+            },
+            AstNode::Slice { target, start, end, span } => {
+                let target = self.execute(target)?;
+                let start = match start {
+                    Some(s) => Some(self.execute(s)?.int()?),
+                    None => None
+                };
+                let end = match end {
+                    Some(e) => Some(self.execute(e)?.int()?),
+                    None => None
+                };
+                slice(target, start, end).map_err(|e| e.at(*span))?
+            },
+            AstNode::Compare(first, chain, span) => {
+                let mut lhs = self.execute(first)?;
+                for (op, rhs) in chain {
+                    let rhs = self.execute(rhs)?;
+                    let holds = compare(op, &lhs, &rhs).map_err(|e| e.during(*span, || {
+                        format!("{} {} {}", Type::of(&lhs).name(), op.symbol(), Type::of(&rhs).name())
+                    }))?;
+                    if !holds {
+                        return Ok(Value::Bool(false))
                     }
+                    lhs = rhs;
                 }
+                Value::Bool(true)
             },
-            AstNode::Unary(op, a) => {
+            AstNode::Unary(op, a, span) => {
                 let val = self.execute(a)?;
-                match op {
-                    UnOp::Not => Value::Bool(!val.truthy()),
-                    UnOp::Sub => Value::Number(-val.num()?)
-                }
+                unary_at(op, val, *span)?
             },
-            AstNode::If { cond, then, or } => {
-                let cond = self.execute(cond)?;
-                if cond.truthy() {
-                    self.execute(then)?
+            AstNode::If { cond, then, or, .. } => {
+                let take_then = match self.resume.pop() {
+                    Some(Resume::If(then)) => then,
+                    Some(r) => unreachable!("resumed into an if at {:?}", r),
+                    None => self.execute(cond)?.truthy()
+                };
+                if take_then {
+                    let out = self.execute(then);
+                    self.suspend(out, Resume::If(true))?
                 } else if let Some(or) = or {
-                    self.execute(or)?
+                    let out = self.execute(or);
+                    self.suspend(out, Resume::If(false))?
                 } else {
                     Value::Nil
                 }
             },
             AstNode::Block(stmts, scoped) => {
-                if *scoped {
-                    self.locals.push();
-                }
-                let mut out = Value::Nil;
-                for stmt in stmts {
-                    match stmt {
-                        Statement::Expression(x) => { out = self.execute(x)?; }
+                let start = match self.resume.pop() {
+                    // the scope was restored along with the generator
+                    Some(Resume::Block(i)) => i,
+                    Some(r) => unreachable!("resumed into a block at {:?}", r),
+                    None => {
+                        if *scoped {
+                            self.push_scope();
+                        }
+                        0
                     }
+                };
+                let out = self.statements(stmts, start);
+                if let Err(RuntimeError::Yield(_)) = out {
+                    // a suspending generator keeps its scopes
+                    return out
                 }
                 if *scoped {
-                    self.locals.pop();
+                    // deferred expressions run even when the block failed,
+                    // but the block's own error takes priority
+                    let popped = self.pop_scope();
+                    out.and_then(|v| popped.map(|_| v))?
+                } else {
+                    out?
                 }
-                out
             },
             AstNode::Loop { label, cond, run } => {
+                let mut resumed = match self.resume.pop() {
+                    Some(Resume::Loop) => true,
+                    Some(r) => unreachable!("resumed into a loop at {:?}", r),
+                    None => false
+                };
                 loop {
-                    let mut should_break = false;
-                    if let ExitFlag::Break(elabel) = &self.exit_flag {
-                        if let (Some(l1), Some(l2)) = (label, elabel) {
-                            should_break = l1 == l2 
-                        } else { should_break = true }
-                    }
-                    if should_break {
-                        self.exit_flag = ExitFlag::Continue;
+                    if self.take_break(label) {
                         break
                     }
-                    if let Some(c) = cond {
-                        let v = self.execute(c)?;
-                        if v.truthy() {
-                            self.execute(run)?;
-                        }
+                    let out = if resumed {
+                        resumed = false;
+                        self.execute(run).map(|_| ())
+                    } else if let Some(c) = cond {
+                        self.execute(c).and_then(|v| if v.truthy() {
+                            self.execute(run).map(|_| ())
+                        } else { Ok(()) })
                     } else {
-                        self.execute(run)?;
+                        self.execute(run).map(|_| ())
+                    };
+                    self.suspend(out, Resume::Loop)?;
+                }
+                Value::Nil
+            },
+            AstNode::For { binding, iterable, run } => {
+                let (iter, resumed) = match self.resume.pop() {
+                    Some(Resume::For(iter)) => (iter, true),
+                    Some(r) => unreachable!("resumed into a for loop at {:?}", r),
+                    None => {
+                        let iter = self.execute(iterable)?.iter()?;
+                        // holds the loop variable
+                        self.push_scope();
+                        self.locals.define(binding, Value::Nil);
+                        (iter, false)
                     }
+                };
+                let out = self.for_loop(binding, &iter, run, resumed);
+                if let Err(RuntimeError::Yield(v)) = out {
+                    self.resume.push(Resume::For(iter));
+                    return Err(RuntimeError::Yield(v))
                 }
+                let popped = self.pop_scope();
+                out.and(popped)?;
                 Value::Nil
             },
+            AstNode::Try { body, binding, handler } => {
+                let resumed = self.resume.pop();
+                match resumed {
+                    Some(Resume::Catch) => self.handle(handler)?,
+                    Some(Resume::Try) | None => {
+                        let depth = self.locals.depth();
+                        let calls = self.calls.len();
+                        match self.execute(body) {
+                            Ok(v) => v,
+                            Err(RuntimeError::Yield(v)) => {
+                                self.resume.push(Resume::Try);
+                                return Err(RuntimeError::Yield(v))
+                            },
+                            Err(e) if e.fatal() => return Err(e),
+                            Err(e) => {
+                                // scopes left open by the failed code
+                                self.locals.unwind(depth);
+                                self.calls.truncate(calls);
+                                let caught = caught(e);
+                                self.push_scope();
+                                if let Some(binding) = binding {
+                                    self.locals.define(binding, caught);
+                                }
+                                self.handle(handler)?
+                            }
+                        }
+                    },
+                    Some(r) => unreachable!("resumed into a try at {:?}", r)
+                }
+            },
+            AstNode::Yield(v) => {
+                let v = self.execute(v)?;
+                return Err(RuntimeError::Yield(v))
+            },
+            AstNode::Assert { cond, message, source, span } => {
+                if !self.execute(cond)?.truthy() {
+                    let message = match message {
+                        Some(m) => Some(self.execute(m)?.to_string()),
+                        None => None
+                    };
+                    return Err(RuntimeError::AssertionFailed {
+                        expr: source.clone(),
+                        message,
+                        span: *span
+                    }.at(*span))
+                }
+                Value::Nil
+            },
+            AstNode::Use { path, binding } => {
+                let module = module::import(self, path)?;
+                self.locals.set(binding, module);
+                Value::Nil
+            },
+            AstNode::Pub { value, .. } => self.execute(value)?,
+            AstNode::Defer(expr) => {
+                self.defer(expr.clone());
+                Value::Nil
+            },
+            AstNode::Throw(thrown) => {
+                return Err(throw(self.execute(thrown)?))
+            },
             AstNode::Break(label) => {
-                self.exit_flag = ExitFlag::Break(label.clone());
+                self.start_break(label.clone());
                 Value::Nil
             },
             AstNode::Error => {
@@ -1,6 +1,8 @@
-use crate::value::Value;
+use std::rc::Rc;
 
-#[derive(Debug)]
+use crate::{fe::diagnostic::Span, value::Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BinOp {
     Add,
     Sub,
@@ -10,35 +12,189 @@ pub enum BinOp {
     Mod,
     Eq,
     NotEq,
+    In,
+    Is,
     Gt,
     GtEq,
     Lt,
     LtEq,
     And,
     Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Access
 }
 
-#[derive(Debug)]
+impl BinOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Pow => "**",
+            Self::Mod => "%",
+            Self::Eq => "==",
+            Self::NotEq => "!=",
+            Self::In => "in",
+            Self::Is => "is",
+            Self::Gt => ">",
+            Self::GtEq => ">=",
+            Self::Lt => "<",
+            Self::LtEq => "<=",
+            Self::And => "&&",
+            Self::Or => "||",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::BitXor => "^",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+            Self::Access => "."
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnOp {
     Not,
-    Sub
+    Sub,
+    BitNot
+}
+
+impl UnOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Not => "!",
+            Self::Sub => "-",
+            Self::BitNot => "~"
+        }
+    }
+}
+
+/// a type named in an annotation. the names match the ones `type()` gives,
+/// and annotations are only used by `tram check`, never at runtime
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Type {
+    Any,
+    Number,
+    String,
+    Bool,
+    Array,
+    Map,
+    Bytes,
+    Func,
+    Error,
+    Iterator,
+    /// a host object, see [`crate::userdata::UserData`]
+    UserData,
+    Nil
+}
+
+impl Type {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "any" => Self::Any,
+            "number" => Self::Number,
+            "string" => Self::String,
+            "bool" => Self::Bool,
+            "array" => Self::Array,
+            "map" => Self::Map,
+            "bytes" => Self::Bytes,
+            "func" => Self::Func,
+            "error" => Self::Error,
+            "iterator" => Self::Iterator,
+            "userdata" => Self::UserData,
+            "nil" => Self::Nil,
+            _ => return None
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Bool => "bool",
+            Self::Array => "array",
+            Self::Map => "map",
+            Self::Bytes => "bytes",
+            Self::Func => "func",
+            Self::Error => "error",
+            Self::Iterator => "iterator",
+            Self::UserData => "userdata",
+            Self::Nil => "nil"
+        }
+    }
+
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::Int(_) | Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Bool(_) => Self::Bool,
+            Value::Array(_) => Self::Array,
+            Value::Map(_) => Self::Map,
+            Value::Bytes(_) => Self::Bytes,
+            Value::Function(_) => Self::Func,
+            Value::Error(_) => Self::Error,
+            Value::Iterator(_) => Self::Iterator,
+            Value::UserData(_) => Self::UserData,
+            Value::Nil => Self::Nil
+        }
+    }
+
+    /// whether a value of type `other` fits where `self` is expected
+    pub fn accepts(&self, other: Type) -> bool {
+        *self == Self::Any || other == Self::Any || *self == other
+    }
+}
+
+/// where a variable is expected to be, filled in by `AstNode::resolve`.
+/// scoping is dynamic, so this is only a guess that's checked when it's used
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    /// searched for through every scope
+    Unresolved,
+    /// at `index` in the scope `up` scopes out from the innermost
+    Local { up: u32, index: u32 }
 }
 
 pub type Ast = Box<AstNode>;
 
 #[derive(Debug)]
 pub enum AstNode {
-    Call(Ast, Vec<Self>),
+    /// a call, with the span of the whole expression for stack traces
+    Call(Ast, Vec<Self>, Span),
+    /// a literal. evaluating it clones the value, which for strings and
+    /// functions only shares the handle, so constants never allocate.
+    /// scripts can't change a string in place, so sharing one is safe
     Value(Box<Value>),
-    Ident(String),
-    Assign(String, Ast),
-    Binary(BinOp, Ast, Ast),
-    Unary(UnOp, Ast),
+    /// a variable, and where its name is
+    Ident(String, Slot, Span),
+    /// `name = value`, with the span of the whole assignment
+    Assign(String, Slot, Ast, Span),
+    /// `a, b = b, a`. every value is evaluated before any name is assigned
+    MultiAssign(Vec<String>, Vec<Self>),
+    Binary(BinOp, Ast, Ast, Span),
+    /// `target[start..end]`, where either bound may be left out
+    Slice {
+        target: Ast,
+        start: Option<Ast>,
+        end: Option<Ast>,
+        span: Span
+    },
+    /// a chain of comparisons like `a < b <= c`, which holds when every
+    /// adjacent pair does. each operand is evaluated at most once
+    Compare(Ast, Vec<(BinOp, Self)>, Span),
+    Unary(UnOp, Ast, Span),
     If {
         cond: Ast,
         then: Ast,
-        or: Option<Ast>
+        or: Option<Ast>,
+        /// from the `if` to the end of the condition
+        span: Span
     },
     /// a list of statements and a
     /// bool describing if a new scope should be created
@@ -48,11 +204,72 @@ pub enum AstNode {
         cond: Option<Ast>,
         run: Ast
     },
+    For {
+        binding: String,
+        iterable: Ast,
+        run: Ast
+    },
     Break(Option<String>),
+    /// suspends the enclosing generator, handing out the value
+    Yield(Ast),
+    /// runs `body`, and if it fails runs `handler` with the
+    /// thrown value (or the runtime error) bound to `binding`
+    Try {
+        body: Ast,
+        binding: Option<String>,
+        handler: Ast
+    },
+    Throw(Ast),
+    /// fails with the condition's source text
+    /// and the optional message when `cond` is falsy
+    Assert {
+        cond: Ast,
+        message: Option<Ast>,
+        source: String,
+        span: Span
+    },
+    /// imports the module at `path`, binding its exports to `binding`
+    Use {
+        path: String,
+        binding: String
+    },
+    /// a top-level assignment, function or `use` marked `pub`,
+    /// which makes `name` visible to importers of the module
+    Pub {
+        name: String,
+        value: Ast
+    },
+    /// runs the expression when the enclosing scope exits
+    Defer(Rc<AstNode>),
     Error
 }
 
+impl AstNode {
+    /// where the node is in the source, if it keeps a span
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            AstNode::Call(_, _, span) | AstNode::Binary(_, _, _, span) | AstNode::Slice { span, .. }
+                | AstNode::Compare(_, _, span) | AstNode::Unary(_, _, span)
+                | AstNode::Ident(_, _, span) | AstNode::Assign(_, _, _, span)
+                | AstNode::Assert { span, .. } => Some(*span),
+            _ => None
+        }
+    }
+
+    /// where the node is in the source, for the nodes that
+    /// can fail at runtime in a way worth pointing at
+    pub fn span_mut(&mut self) -> Option<&mut Span> {
+        match self {
+            AstNode::Call(_, _, span) | AstNode::Binary(_, _, _, span) | AstNode::Slice { span, .. }
+                | AstNode::Compare(_, _, span) | AstNode::Unary(_, _, span)
+                | AstNode::Ident(_, _, span) | AstNode::Assign(_, _, _, span) => Some(span),
+            _ => None
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Statement {
-    Expression(Ast),
+    /// an expression, and where the whole statement is
+    Expression(Ast, Span),
 }
@@ -0,0 +1,382 @@
+//! an optional static pass over the type annotations, run by `tram check`.
+//! types are only tracked where they're obvious from the source, and
+//! anything unknown is treated as `any` so the pass never rejects a
+//! program because of what it can't see
+
+use std::collections::HashMap;
+
+use crate::{function::Function, value::Value};
+
+use super::{ast::{AstNode, BinOp, Statement, Type, UnOp}, diagnostic::{Diagnostic, Level, Span}};
+
+pub struct TypeError {
+    pub message: String,
+    /// the code that has the wrong type, if it knows where it is
+    pub span: Option<Span>
+}
+
+impl TypeError {
+    /// writes the error to stderr, `file` being where `source` came from
+    pub fn log(&self, file: Option<&str>, source: Option<&str>) {
+        Diagnostic {
+            level: Level::Error,
+            message: format!("type error: {}", self.message),
+            span: self.span,
+            notes: Vec::new(),
+            help: Vec::new(),
+            file: file.map(str::to_owned)
+        }.emit(source);
+    }
+}
+
+/// the annotations of a named function, used to check calls to it
+#[derive(Clone)]
+struct Signature {
+    params: Vec<(Option<Type>, bool)>,
+    rest: bool,
+    ret: Type
+}
+
+impl Signature {
+    fn of(func: &Function) -> Self {
+        Self {
+            params: func.params.iter().map(|p| (p.ty, p.default.is_some())).collect(),
+            rest: func.rest.is_some(),
+            ret: if func.generator { Type::Iterator } else { func.ret.unwrap_or(Type::Any) }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    vars: HashMap<String, Type>,
+    funcs: HashMap<String, Signature>
+}
+
+pub fn check(ast: &AstNode) -> Vec<TypeError> {
+    let mut checker = Checker {
+        scopes: vec![Scope::default()],
+        func: vec![],
+        boundary: 0,
+        errors: vec![]
+    };
+    checker.expr(ast);
+    checker.errors
+}
+
+struct Checker {
+    scopes: Vec<Scope>,
+    /// the names of the functions being checked, for messages
+    func: Vec<String>,
+    /// the first scope of the function being checked. scoping is dynamic,
+    /// so the types of variables from outside it aren't known
+    boundary: usize,
+    errors: Vec<TypeError>
+}
+
+impl Checker {
+    fn error(&mut self, span: Option<Span>, message: String) {
+        let message = match self.func.last() {
+            Some(name) => format!("in `{}`: {}", name, message),
+            None => message
+        };
+        self.errors.push(TypeError { message, span });
+    }
+
+    fn var(&self, name: &str) -> Type {
+        self.scopes[self.boundary..].iter().rev()
+            .find_map(|s| s.vars.get(name))
+            .copied()
+            .unwrap_or(Type::Any)
+    }
+
+    fn signature(&self, name: &str) -> Option<&Signature> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(sig) = scope.funcs.get(name) {
+                return Some(sig);
+            }
+            // a later plain assignment shadows the function
+            if scope.vars.get(name).is_some_and(|t| *t != Type::Func) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// records an assignment. a variable that is assigned
+    /// different types over its lifetime becomes `any`
+    fn assign(&mut self, name: &str, ty: Type, sig: Option<Signature>) {
+        let at = self.scopes[self.boundary..].iter()
+            .rposition(|s| s.vars.contains_key(name))
+            .map_or(self.scopes.len() - 1, |i| self.boundary + i);
+        let scope = &mut self.scopes[at];
+        let ty = match scope.vars.get(name) {
+            Some(old) if *old != ty => Type::Any,
+            _ => ty
+        };
+        scope.vars.insert(name.to_string(), ty);
+        match sig {
+            Some(sig) if ty == Type::Func => { scope.funcs.insert(name.to_string(), sig); },
+            _ => { scope.funcs.remove(name); }
+        }
+    }
+
+    fn block(&mut self, stmts: &[Statement], scoped: bool) -> Type {
+        if scoped {
+            self.scopes.push(Scope::default());
+        }
+        let mut ty = Type::Nil;
+        for Statement::Expression(e, _) in stmts {
+            ty = self.expr(e);
+        }
+        if scoped {
+            self.scopes.pop();
+        }
+        ty
+    }
+
+    fn function(&mut self, func: &Function) {
+        let name = func.name.clone().unwrap_or_else(|| "< lambda >".to_string());
+        self.func.push(name);
+        self.scopes.push(Scope::default());
+        let outer = std::mem::replace(&mut self.boundary, self.scopes.len() - 1);
+        for p in &func.params {
+            if let Some(default) = &p.default {
+                let found = self.expr(default);
+                if let Some(ty) = p.ty {
+                    if !ty.accepts(found) {
+                        self.error(default.span(), format!("the default for `{}` is a {}, but it is annotated as {}",
+                            p.name, found.name(), ty.name()));
+                    }
+                }
+            }
+            self.scopes.last_mut().unwrap().vars.insert(p.name.clone(), p.ty.unwrap_or(Type::Any));
+        }
+        if let Some(rest) = &func.rest {
+            self.scopes.last_mut().unwrap().vars.insert(rest.clone(), Type::Array);
+        }
+        let found = self.expr(&func.ast);
+        match func.ret {
+            Some(ret) if func.generator && !ret.accepts(Type::Iterator) => {
+                self.error(Some(func.span), format!("generators return an iterator, but it is annotated -> {}", ret.name()));
+            },
+            Some(ret) if !func.generator && !ret.accepts(found) => {
+                self.error(Some(func.span), format!("returns a {}, but it is annotated -> {}", found.name(), ret.name()));
+            },
+            _ => {}
+        }
+        self.boundary = outer;
+        self.scopes.pop();
+        self.func.pop();
+    }
+
+    fn call(&mut self, callee: &AstNode, args: &[AstNode], span: Span) -> Type {
+        let found: Vec<Type> = args.iter().map(|a| self.expr(a)).collect();
+        let (name, sig) = match callee {
+            AstNode::Ident(name, _, _) => match self.signature(name) {
+                Some(sig) => (name.clone(), sig.clone()),
+                None => return Type::Any
+            },
+            AstNode::Value(v) => match v.as_ref() {
+                Value::Function(f) => match f.as_function() {
+                    Some(f) => {
+                        self.function(f);
+                        ("< lambda >".to_string(), Signature::of(f))
+                    },
+                    None => return Type::Any
+                },
+                _ => {
+                    self.error(Some(span), format!("a {} is not callable", Type::of(v).name()));
+                    return Type::Any
+                }
+            },
+            _ => {
+                self.expr(callee);
+                return Type::Any
+            }
+        };
+
+        let required = sig.params.iter().filter(|(_, default)| !default).count();
+        if found.len() < required || (!sig.rest && found.len() > sig.params.len()) {
+            self.error(Some(span), format!("`{}` takes {} arguments, but is called with {}",
+                name, sig.params.len(), found.len()));
+        }
+        for (i, ((ty, _), found)) in sig.params.iter().zip(&found).enumerate() {
+            if let Some(ty) = ty {
+                if !ty.accepts(*found) {
+                    self.error(args[i].span().or(Some(span)), format!("argument {} to `{}` is a {}, but it is annotated as {}",
+                        i + 1, name, found.name(), ty.name()));
+                }
+            }
+        }
+        sig.ret
+    }
+
+    fn binary(&mut self, op: &BinOp, lhs: Type, rhs: Type, span: Span) -> Type {
+        use BinOp::*;
+        use Type::{Any, Number};
+        let numeric = |t: Type| matches!(t, Number | Any);
+        match op {
+            Add => match (lhs, rhs) {
+                (Number, Number) => Number,
+                (Type::String, Type::String) => Type::String,
+                (Type::Array, Type::Array) => Type::Array,
+                (Type::Bytes, Type::Bytes) => Type::Bytes,
+                (Any, _) | (_, Any) => Any,
+                _ => {
+                    self.error(Some(span), format!("cannot add a {} and a {}", lhs.name(), rhs.name()));
+                    Any
+                }
+            },
+            Mul if matches!((lhs, rhs), (Type::String | Type::Array, Number)) => lhs,
+            Sub | Mul | Div | Pow | Mod | BitAnd | BitOr | BitXor | Shl | Shr => {
+                if !numeric(lhs) || !numeric(rhs) {
+                    self.error(Some(span), format!("arithmetic on a {} and a {}", lhs.name(), rhs.name()));
+                    return Any
+                }
+                if lhs == Any || rhs == Any { Any } else { Number }
+            },
+            Gt | GtEq | Lt | LtEq => {
+                if !numeric(lhs) || !numeric(rhs) {
+                    self.error(Some(span), format!("cannot compare a {} and a {}", lhs.name(), rhs.name()));
+                }
+                Type::Bool
+            },
+            Eq | NotEq | In | Is | And | Or => Type::Bool,
+            Access => Any
+        }
+    }
+
+    fn expr(&mut self, node: &AstNode) -> Type {
+        match node {
+            AstNode::Value(v) => {
+                if let Value::Function(f) = v.as_ref() {
+                    if let Some(f) = f.as_function() {
+                        self.function(f);
+                    }
+                }
+                Type::of(v)
+            },
+            AstNode::Ident(name, _, _) => self.var(name),
+            AstNode::Assign(name, _, value, _) => {
+                let ty = self.expr(value);
+                let sig = match value.as_ref() {
+                    AstNode::Value(v) => match v.as_ref() {
+                        Value::Function(f) => f.as_function().map(Signature::of),
+                        _ => None
+                    },
+                    _ => None
+                };
+                self.assign(name, ty, sig);
+                Type::Nil
+            },
+            AstNode::MultiAssign(names, values) => {
+                let types: Vec<Type> = values.iter().map(|v| self.expr(v)).collect();
+                for (name, ty) in names.iter().zip(types) {
+                    self.assign(name, ty, None);
+                }
+                Type::Nil
+            },
+            AstNode::Call(callee, args, span) => self.call(callee, args, *span),
+            AstNode::Binary(op, lhs, rhs, span) => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                self.binary(op, lhs, rhs, *span)
+            },
+            AstNode::Compare(first, rest, span) => {
+                let mut lhs = self.expr(first);
+                for (op, operand) in rest {
+                    let rhs = self.expr(operand);
+                    self.binary(op, lhs, rhs, *span);
+                    lhs = rhs;
+                }
+                Type::Bool
+            },
+            AstNode::Slice { target, start, end, .. } => {
+                let ty = self.expr(target);
+                for bound in [start, end].into_iter().flatten() {
+                    let found = self.expr(bound);
+                    if !Type::Number.accepts(found) {
+                        self.error(bound.span(), format!("slice bounds must be numbers, not {}", found.name()));
+                    }
+                }
+                match ty {
+                    Type::String | Type::Array | Type::Bytes => ty,
+                    _ => Type::Any
+                }
+            },
+            AstNode::Unary(op, operand, span) => {
+                let ty = self.expr(operand);
+                match op {
+                    UnOp::Not => Type::Bool,
+                    UnOp::Sub | UnOp::BitNot => {
+                        if !Type::Number.accepts(ty) {
+                            self.error(Some(*span), format!("cannot negate a {}", ty.name()));
+                        }
+                        Type::Number
+                    }
+                }
+            },
+            AstNode::If { cond, then, or, .. } => {
+                self.expr(cond);
+                let then = self.expr(then);
+                match or {
+                    Some(or) if self.expr(or) == then => then,
+                    _ => Type::Any
+                }
+            },
+            AstNode::Block(stmts, scoped) => self.block(stmts, *scoped),
+            AstNode::Loop { cond, run, .. } => {
+                if let Some(cond) = cond {
+                    self.expr(cond);
+                }
+                self.expr(run);
+                Type::Nil
+            },
+            AstNode::For { binding, iterable, run } => {
+                self.expr(iterable);
+                self.scopes.push(Scope::default());
+                self.scopes.last_mut().unwrap().vars.insert(binding.clone(), Type::Any);
+                self.expr(run);
+                self.scopes.pop();
+                Type::Nil
+            },
+            AstNode::Yield(value) => {
+                self.expr(value);
+                Type::Any
+            },
+            AstNode::Try { body, binding, handler } => {
+                let body = self.expr(body);
+                self.scopes.push(Scope::default());
+                if let Some(binding) = binding {
+                    self.scopes.last_mut().unwrap().vars.insert(binding.clone(), Type::Any);
+                }
+                let handler = self.expr(handler);
+                self.scopes.pop();
+                if body == handler { body } else { Type::Any }
+            },
+            AstNode::Throw(value) => {
+                self.expr(value);
+                Type::Any
+            },
+            AstNode::Assert { cond, message, .. } => {
+                self.expr(cond);
+                if let Some(message) = message {
+                    self.expr(message);
+                }
+                Type::Nil
+            },
+            AstNode::Use { binding, .. } => {
+                self.assign(binding, Type::Map, None);
+                Type::Nil
+            },
+            AstNode::Pub { value, .. } => self.expr(value),
+            AstNode::Defer(deferred) => {
+                self.expr(deferred);
+                Type::Nil
+            },
+            AstNode::Break(_) => Type::Nil,
+            AstNode::Error => Type::Any
+        }
+    }
+}
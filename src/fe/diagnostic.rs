@@ -1,4 +1,6 @@
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, env, io::{self, IsTerminal}, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::{corelib, handle::Handle, value::Value};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Span {
@@ -18,13 +20,209 @@ impl Span {
         Self { start, end }
     }
 
-    pub fn exact_range(&self, source: &str) -> Range<usize> {
-        let end = self.end.min(source.len() - 1);
-        self.start.min(end - 1)..end
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// the line and column the span starts at, both counted from 1
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let mut line_col = (1, 1);
+        for c in source.chars().take(self.start) {
+            line_col = if c == '\n' { (line_col.0 + 1, 1) } else { (line_col.0, line_col.1 + 1) };
+        }
+        line_col
+    }
+
+    /// how wide the line numbers in a snippet of the span are
+    fn gutter_width(&self, source: &str) -> usize {
+        let last = self.end.saturating_sub(1).max(self.start);
+        Span::new(last, last).line_col(source).0.to_string().len()
+    }
+
+    /// the lines of source the span covers, numbered, with the span
+    /// underlined. spans count chars, not bytes, and may run past the
+    /// end of the source, which is where an unexpected end of file is
+    pub fn snippet(&self, source: &str, color: bool) -> String {
+        self.underline(source, color, Level::Error)
+    }
+
+    fn underline(&self, source: &str, color: bool, level: Level) -> String {
+        let chars: Vec<char> = source.chars().collect();
+        let start = self.start.min(chars.len());
+        let end = self.end.clamp(start, chars.len());
+        // the chars each line covers, not counting its newline
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        for (i, c) in chars.iter().enumerate() {
+            if *c == '\n' {
+                lines.push(line_start..i);
+                line_start = i + 1;
+            }
+        }
+        lines.push(line_start..chars.len());
+        let last = end.saturating_sub(1).max(start);
+        let first_line = lines.iter().position(|l| start <= l.end).unwrap_or(lines.len() - 1);
+        let last_line = lines.iter().position(|l| last <= l.end).unwrap_or(lines.len() - 1).max(first_line);
+
+        let paint = Paint(color);
+        let width = self.gutter_width(source);
+        let gutter = paint.blue(&format!("{} |", " ".repeat(width)));
+        let mut out = gutter.clone();
+        for (n, line) in lines.iter().enumerate().take(last_line + 1).skip(first_line) {
+            // a long span shows its first two lines and its last
+            if last_line - first_line > 3 && n > first_line + 1 && n < last_line {
+                if n == first_line + 2 {
+                    out.push_str(&format!("\n{}", paint.blue("...")));
+                }
+                continue
+            }
+            let text: String = chars[line.clone()].iter().collect();
+            out.push_str(&format!("\n{} {}", paint.blue(&format!("{:>width$} |", n + 1)), text));
+            let from = start.max(line.start);
+            let to = end.min(line.end).max(from + 1);
+            // tabs are kept, so the carets line up however wide they're shown
+            let pad: String = chars[line.start..from].iter()
+                .map(|c| if *c == '\t' { '\t' } else { ' ' })
+                .collect();
+            out.push_str(&format!("\n{} {}{}", gutter, pad, paint.level(level, &"^".repeat(to - from))));
+        }
+        out
+    }
+}
+
+/// ansi colours, or nothing when `self.0` is false
+#[derive(Clone, Copy)]
+struct Paint(bool);
+
+impl Paint {
+    fn code(self, code: &str, text: &str) -> String {
+        if self.0 { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text.to_owned() }
+    }
+
+    fn level(self, level: Level, text: &str) -> String {
+        match level {
+            Level::Error => self.code("1;31", text),
+            Level::Warning => self.code("1;33", text)
+        }
+    }
+
+    fn blue(self, text: &str) -> String {
+        self.code("1;34", text)
     }
 
-    pub fn surrounding_range(&self, source: &str) -> Range<usize> {
-        self.start.saturating_sub(10) .. (self.end + 10).min(source.len() - 1)
+    fn bold(self, text: &str) -> String {
+        self.code("1", text)
+    }
+}
+
+/// set by `--diagnostics=json`, for editors and the like
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// writes diagnostics as JSON, one to a line, instead of for people to read
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// whether diagnostics written to stderr should be coloured, which they
+/// are when it's a terminal, unless `NO_COLOR` is set
+pub fn color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Error,
+    /// something that runs, but likely isn't what was meant
+    Warning
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning"
+        }
+    }
+}
+
+/// a problem with some source, rendered the way rustc does it:
+/// the message, where it is, the lines it's on, and any notes and help
+pub struct Diagnostic {
+    pub level: Level,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    /// the path of the file the source is from, if it is from one
+    pub file: Option<String>
+}
+
+impl Diagnostic {
+    /// writes the diagnostic to stderr, in whichever form was asked for
+    pub fn emit(&self, source: Option<&str>) {
+        if json() {
+            eprintln!("{}", self.to_json(source));
+        } else {
+            eprintln!("{}\n", self.render(source, color()));
+        }
+    }
+
+    /// the diagnostic as a JSON object, with its `severity`, `message`,
+    /// `file`, `span`, `notes` and `help`. the span's `start` and `end`
+    /// count chars, and it has a `line` and `column` if `source` is known
+    pub fn to_json(&self, source: Option<&str>) -> String {
+        let strings = |s: &[String]| Value::Array(Handle::new(s.iter().map(|s| s.as_str().into()).collect()));
+        let span = match self.span {
+            Some(span) => {
+                let mut fields = vec![("start", Value::Int(span.start as i64)), ("end", Value::Int(span.end as i64))];
+                if let Some(source) = source {
+                    let (line, col) = span.line_col(source);
+                    fields.push(("line", Value::Int(line as i64)));
+                    fields.push(("column", Value::Int(col as i64)));
+                }
+                Value::Map(Handle::new(fields.into_iter().map(|(k, v)| (k.into(), v)).collect()))
+            },
+            None => Value::Nil
+        };
+        let fields = [
+            ("severity", self.level.name().into()),
+            ("message", self.message.as_str().into()),
+            ("file", self.file.as_deref().map_or(Value::Nil, Value::from)),
+            ("span", span),
+            ("notes", strings(&self.notes)),
+            ("help", strings(&self.help))
+        ];
+        let val = Value::Map(Handle::new(fields.into_iter().map(|(k, v)| (k.into(), v)).collect()));
+        // only strings, ints and nil, which always encode
+        corelib::to_json(&val, false).unwrap_or_default()
+    }
+
+    /// the diagnostic as text, pointing into `source` if it's known
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
+        let paint = Paint(color);
+        let mut out = format!("{}{}", paint.level(self.level, self.level.name()), paint.bold(&format!(": {}", self.message)));
+        let mut indent = 1;
+        if let (Some(span), Some(source)) = (self.span, source) {
+            let (line, col) = span.line_col(source);
+            indent = span.gutter_width(source) + 1;
+            let at = match &self.file {
+                Some(file) => format!("{}:{}:{}", file, line, col),
+                None => format!("line {}, column {}", line, col)
+            };
+            out.push_str(&format!("\n{}{} {}\n{}",
+                " ".repeat(indent - 1), paint.blue("-->"), at, span.underline(source, color, self.level)));
+        }
+        for (label, lines) in [("note", &self.notes), ("help", &self.help)] {
+            for line in lines {
+                out.push_str(&format!("\n{}{} {}", " ".repeat(indent), paint.blue("="), paint.bold(label)));
+                out.push_str(&format!(": {}", line));
+            }
+        }
+        out
     }
 }
 
@@ -36,22 +234,39 @@ impl Default for Span {
 
 pub struct ParseError {
     pub span: Span,
-    pub message: Cow<'static, str>
+    pub message: Cow<'static, str>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>
 }
 
 impl ParseError {
-    pub fn log(&self, source: Option<&str>) {
-        eprintln!("== Parse Error: {}", self.message);
-        if let Some(source) = source {
-            eprintln!("problem at:");
-            let surrounding = self.span.surrounding_range(source);
-            let exact = self.span.exact_range(source);
-            eprintln!(
-                ">| {}\x1b[31m\x1b[4:3m{}\x1b[0m{}",
-                &source[surrounding.start .. exact.start],
-                &source[exact.clone()],
-                &source[exact.end .. surrounding.end]
-            );
+    pub fn new(span: Span, message: impl Into<Cow<'static, str>>) -> Self {
+        Self { span, message: message.into(), notes: Vec::new(), help: Vec::new() }
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            level: Level::Error,
+            message: self.message.to_string(),
+            span: Some(self.span),
+            notes: self.notes.clone(),
+            help: self.help.clone(),
+            file: None
         }
     }
+
+    /// writes the error to stderr, `file` being where `source` came from
+    pub fn log(&self, file: Option<&str>, source: Option<&str>) {
+        Diagnostic { file: file.map(str::to_owned), ..self.diagnostic() }.emit(source);
+    }
 }
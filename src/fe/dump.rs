@@ -0,0 +1,150 @@
+//! printing the tree a script parses into, for `--ast` and the repl's
+//! `:ast`, either indented or as JSON. each node is shown with what it is,
+//! anything it holds that isn't another node, and where it is, if it knows
+
+use crate::{corelib, executor::RuntimeError, function::Function, handle::Handle, value::Value};
+
+use super::ast::{AstNode, Statement};
+
+/// what a node is, and the detail that sets it apart from others like it
+fn label(node: &AstNode) -> (&'static str, Option<String>) {
+    match node {
+        AstNode::Call(..) => ("call", None),
+        AstNode::Value(v) => match function(v) {
+            Some(f) => ("func", Some(signature(f))),
+            None => ("value", Some(format!("{:?}", v)))
+        },
+        AstNode::Ident(name, _, _) => ("ident", Some(name.clone())),
+        AstNode::Assign(name, _, _, _) => ("assign", Some(name.clone())),
+        AstNode::MultiAssign(names, _) => ("assign", Some(names.join(", "))),
+        AstNode::Binary(op, ..) => ("binary", Some(op.symbol().to_owned())),
+        AstNode::Slice { .. } => ("slice", None),
+        AstNode::Compare(_, chain, _) => {
+            let ops: Vec<_> = chain.iter().map(|(op, _)| op.symbol()).collect();
+            ("compare", Some(ops.join(" ")))
+        },
+        AstNode::Unary(op, ..) => ("unary", Some(op.symbol().to_owned())),
+        AstNode::If { .. } => ("if", None),
+        AstNode::Block(_, scoped) => ("block", (!scoped).then(|| "unscoped".to_owned())),
+        AstNode::Loop { label, .. } => ("loop", label.clone()),
+        AstNode::For { binding, .. } => ("for", Some(binding.clone())),
+        AstNode::Break(label) => ("break", label.clone()),
+        AstNode::Yield(_) => ("yield", None),
+        AstNode::Try { binding, .. } => ("try", binding.as_ref().map(|b| format!("catch {}", b))),
+        AstNode::Throw(_) => ("throw", None),
+        AstNode::Assert { source, .. } => ("assert", Some(source.clone())),
+        AstNode::Use { path, binding } => ("use", Some(format!("{} as {}", path, binding))),
+        AstNode::Pub { name, .. } => ("pub", Some(name.clone())),
+        AstNode::Defer(_) => ("defer", None),
+        AstNode::Error => ("error", None)
+    }
+}
+
+fn function(val: &Value) -> Option<&Function> {
+    match val {
+        Value::Function(f) => f.as_function(),
+        _ => None
+    }
+}
+
+/// `name(a: number, b = ..., ...rest) -> type`
+fn signature(func: &Function) -> String {
+    let mut params: Vec<String> = func.params.iter().map(|p| {
+        let mut param = p.name.clone();
+        if let Some(ty) = p.ty {
+            param.push_str(&format!(": {}", ty.name()));
+        }
+        if p.default.is_some() {
+            param.push_str(" = ...");
+        }
+        param
+    }).collect();
+    if let Some(rest) = &func.rest {
+        params.push(format!("...{}", rest));
+    }
+    let mut sig = format!("{}({})", func.name.as_deref().unwrap_or(""), params.join(", "));
+    if let Some(ret) = func.ret {
+        sig.push_str(&format!(" -> {}", ret.name()));
+    }
+    sig
+}
+
+/// the nodes directly under `node`, in the order they're written
+fn children(node: &AstNode) -> Vec<&AstNode> {
+    match node {
+        AstNode::Call(func, args, _) => std::iter::once(&**func).chain(args).collect(),
+        AstNode::Value(v) => match function(v) {
+            Some(f) => f.params.iter()
+                .filter_map(|p| p.default.as_deref())
+                .chain(std::iter::once(&*f.ast))
+                .collect(),
+            None => vec![]
+        },
+        AstNode::Assign(_, _, value, _) | AstNode::Unary(_, value, _) | AstNode::Yield(value)
+            | AstNode::Throw(value) | AstNode::Pub { value, .. } => vec![value],
+        AstNode::MultiAssign(_, values) => values.iter().collect(),
+        AstNode::Binary(_, a, b, _) => vec![a, b],
+        AstNode::Slice { target, start, end, .. } => std::iter::once(&**target)
+            .chain(start.as_deref())
+            .chain(end.as_deref())
+            .collect(),
+        AstNode::Compare(first, chain, _) => std::iter::once(&**first)
+            .chain(chain.iter().map(|(_, operand)| operand))
+            .collect(),
+        AstNode::If { cond, then, or, .. } => [Some(&**cond), Some(&**then), or.as_deref()].into_iter().flatten().collect(),
+        AstNode::Block(stmts, _) => stmts.iter().map(|Statement::Expression(e, _)| &**e).collect(),
+        AstNode::Loop { cond, run, .. } => cond.as_deref().into_iter().chain(std::iter::once(&**run)).collect(),
+        AstNode::For { iterable, run, .. } => vec![iterable, run],
+        AstNode::Try { body, handler, .. } => vec![body, handler],
+        AstNode::Assert { cond, message, .. } => std::iter::once(&**cond).chain(message.as_deref()).collect(),
+        AstNode::Defer(deferred) => vec![deferred],
+        AstNode::Ident(..) | AstNode::Break(_) | AstNode::Use { .. } | AstNode::Error => vec![]
+    }
+}
+
+/// the tree under `node`, one node to a line, indented by how deep it is
+pub fn tree(node: &AstNode) -> String {
+    let mut out = String::new();
+    write_tree(&mut out, node, 0);
+    out
+}
+
+fn write_tree(out: &mut String, node: &AstNode, depth: usize) {
+    let (kind, detail) = label(node);
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(kind);
+    if let Some(detail) = detail {
+        out.push(' ');
+        out.push_str(&detail);
+    }
+    if let Some(span) = node.span() {
+        out.push_str(&format!(" at {}..{}", span.start, span.end));
+    }
+    out.push('\n');
+    for child in children(node) {
+        write_tree(out, child, depth + 1);
+    }
+}
+
+/// the tree under `node` as JSON. each node is an object with its `kind`,
+/// and its `detail`, `start`, `end` and `children` where it has them
+pub fn json(node: &AstNode) -> Result<String, RuntimeError> {
+    corelib::to_json(&value(node), true)
+}
+
+fn value(node: &AstNode) -> Value {
+    let (kind, detail) = label(node);
+    let mut fields = vec![("kind", kind.into())];
+    if let Some(detail) = detail {
+        fields.push(("detail", detail.into()));
+    }
+    if let Some(span) = node.span() {
+        fields.push(("start", Value::Int(span.start as i64)));
+        fields.push(("end", Value::Int(span.end as i64)));
+    }
+    let children: Vec<Value> = children(node).into_iter().map(value).collect();
+    if !children.is_empty() {
+        fields.push(("children", Value::Array(Handle::new(children))));
+    }
+    Value::Map(Handle::new(fields.into_iter().map(|(k, v)| (k.into(), v)).collect()))
+}
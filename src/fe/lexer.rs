@@ -1,6 +1,6 @@
 use crate::fe::diagnostic::Span;
 
-use super::token::Token;
+use super::token::{keywords, Token};
 
 pub struct Lexer {
     at: usize,
@@ -8,6 +8,39 @@ pub struct Lexer {
     source: Vec<char>
 }
 
+/// checks that every `_` in a number literal sits between two digits
+fn valid_separators(lexeme: &str, radix: u32) -> bool {
+    let chars: Vec<char> = lexeme.chars().collect();
+    chars.iter().enumerate()
+        .filter(|(_, c)| **c == '_')
+        .all(|(i, _)| {
+            let before = i.checked_sub(1).map(|i| chars[i]);
+            let after = chars.get(i + 1);
+            before.is_some_and(|c| c.is_digit(radix))
+                && after.is_some_and(|c| c.is_digit(radix))
+        })
+}
+
+/// every token in `source` and where it is, up to but not including the
+/// `Eof`. anything that doesn't lex is a `Token::Error` saying why, and the
+/// tokens after it carry on from there, so this never fails. for tools
+/// like highlighters that want to read source the way tram does
+pub fn tokenize(source: &str) -> Vec<(Token, Span)> {
+    Lexer::new(source).collect()
+}
+
+/// the tokens up to the `Eof`
+impl Iterator for Lexer {
+    type Item = (Token, Span);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            (Token::Eof, _) => None,
+            token => Some(token)
+        }
+    }
+}
+
 // lexer that does two passes over the source program
 // could be made more efficient
 impl Lexer {
@@ -22,7 +55,9 @@ impl Lexer {
         }
     }
 
-    pub fn next(&mut self) -> (Token, Span) {
+    /// the next token and where it is. once the source runs
+    /// out, every call gives an `Eof` token at its end
+    pub fn next_token(&mut self) -> (Token, Span) {
         use Token::*;
 
         self.skip_whitespace();
@@ -34,9 +69,11 @@ impl Lexer {
             '0'..='9' => self.number(),
             '-' => if self.pick('>') { Arrow } else { self.eq_or(Sub, SubEq) },
             '=' => self.eq_or(Assign, Eq),
-            '>' => self.eq_or(Gt, GtEq),
-            '<' => self.eq_or(Lt, LtEq),
-            '.' => Dot,
+            '>' => if self.pick('>') { Shr } else { self.eq_or(Gt, GtEq) },
+            '<' => if self.pick('<') { Shl } else { self.eq_or(Lt, LtEq) },
+            '.' => if self.pick('.') {
+                if self.pick('.') { Ellipsis } else { DotDot }
+            } else { Dot },
             '?' => Question,
             ':' => Colon,
             '@' => At,
@@ -49,8 +86,14 @@ impl Lexer {
             } else { self.eq_or(Mul, MulEq) },
             '/' => self.eq_or(Div, DivEq),
             '%' => self.eq_or(Mod, ModEq),
-            '&' if self.pick('&') => And,
-            '|' if self.pick('|') => Or,
+            '&' => if self.pick('&') { And } else { BitAnd },
+            '|' => if self.pick('|') {
+                Or
+            } else if self.pick('>') {
+                Pipe
+            } else { BitOr },
+            '^' => BitXor,
+            '~' => BitNot,
 
             '(' => LParen,
             ')' => RParen,
@@ -62,10 +105,17 @@ impl Lexer {
 
             '"' => self.string(),
 
-            n => panic!("unknown character {}", n)
+            n => Error(format!("unexpected character `{}`", n))
         }, Span::new(self.tok_start - 1, self.at))
     }
 
+    /// the source text covered by `span`
+    pub fn slice(&self, span: Span) -> String {
+        // offset by the space added at the front
+        let end = (span.end + 1).min(self.source.len());
+        self.source[(span.start + 1).min(end)..end].iter().collect()
+    }
+
     fn eq_or(&mut self, without: Token, with: Token) -> Token {
         if self.pick('=') { with } else { without }
     }
@@ -102,20 +152,19 @@ impl Lexer {
         } else { false }
     }
 
-    fn eat_through(&mut self, ch: char) {
-        while !self.pick(ch) {
-            self.advance();
-        }
-    }
-
     fn lexeme(&self) -> String {
         self.source[self.tok_start..=self.at.min(self.source.len())]
-            .into_iter()
+            .iter()
             .collect()
     }
 
     fn string(&mut self) -> Token {
-        self.eat_through('"');
+        while !self.pick('"') {
+            if self.at + 1 >= self.source.len() {
+                return Token::Error("unterminated string, missing a closing `\"`".to_owned())
+            }
+            self.advance();
+        }
         let string = self.lexeme();
         let string = string[1..string.len() - 1]
             .replace("\\n", "\n")
@@ -125,48 +174,86 @@ impl Lexer {
     }
 
     fn number(&mut self) -> Token {
-        loop {
-            match self.peek() {
-                '0'..='9' | '.' => {
-                    self.advance();
-                },
-                _ => break
+        if self.at() == '0' {
+            let radix = match self.peek() {
+                'x' | 'X' => Some(16),
+                'b' | 'B' => Some(2),
+                'o' | 'O' => Some(8),
+                _ => None
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                return self.radix_number(radix);
+            }
+        }
+        // a `.` only continues the number when a digit follows,
+        // so ranges like `1..3` lex as three tokens
+        while matches!(self.peek(), '0'..='9' | '_')
+            || (self.peek() == '.' && self.peek_n(2).is_ascii_digit()) {
+            self.advance();
+        }
+        // only treat `e` as an exponent when digits follow,
+        // otherwise it is left for the next token
+        let exponent = match (self.peek(), self.peek_n(2)) {
+            ('e' | 'E', '0'..='9') => true,
+            ('e' | 'E', '+' | '-') => self.peek_n(3).is_ascii_digit(),
+            _ => false
+        };
+        if exponent {
+            self.advance();
+            self.pick('+');
+            self.pick('-');
+            while let '0'..='9' | '_' = self.peek() {
+                self.advance();
             }
         }
-        Token::Number(self.lexeme().parse().expect("failed to parse number"))
+
+        let lexeme = self.lexeme();
+        if !valid_separators(&lexeme, 10) {
+            return Token::Error(format!("misplaced `_` in number literal `{}`", lexeme));
+        }
+        let digits = lexeme.replace('_', "");
+        if !digits.contains(['.', 'e', 'E']) {
+            // whole literals too large for an int fall through to a float
+            if let Ok(i) = digits.parse() {
+                return Token::Int(i);
+            }
+        }
+        match digits.parse() {
+            Ok(n) => Token::Number(n),
+            Err(_) => Token::Error(format!("malformed number literal `{}`", lexeme))
+        }
+    }
+
+    /// lexes the digits of a `0x`, `0b` or `0o` prefixed integer
+    fn radix_number(&mut self, radix: u32) -> Token {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        let lexeme = self.lexeme();
+        let digits = &lexeme[2..];
+        if digits.is_empty() {
+            return Token::Error(format!("expected digits after `{}`", lexeme));
+        }
+        if let Some(c) = digits.chars().find(|c| *c != '_' && !c.is_digit(radix)) {
+            return Token::Error(format!("invalid digit `{}` in number literal `{}`", c, lexeme));
+        }
+        if !valid_separators(digits, radix) {
+            return Token::Error(format!("misplaced `_` in number literal `{}`", lexeme));
+        }
+        match i64::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(n) => Token::Int(n),
+            Err(_) => Token::Error(format!("number literal `{}` is too large", lexeme))
+        }
     }
 
     fn identifier(&mut self) -> Token {
-        loop {
-            match self.peek() {
-                '0'..'9'
-                | 'a'..'z'
-                | 'A'..'Z'
-                | '_' => self.advance(),
-                _ => break
-            };
-        };
-        let str = self.lexeme();
-        use Token::*;
-        match str.as_str() {
-            "let" => Let,
-            "const" => Const,
-            "pub" => Pub,
-            "use" => Use,
-            "func" => Func,
-            "enum" => Enum,
-            "struct" => Struct,
-            "if" => If,
-            "else" => Else,
-            "loop" => Loop,
-            "break" => Break,
-            "continue" => Continue,
-
-            "true" => True,
-            "false" => False,
-            "nil" => Nil,
-
-            _ => Token::Identifier(str)
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            self.advance();
         }
+        let str = self.lexeme();
+        keywords().into_iter()
+            .find(|(keyword, _)| *keyword == str)
+            .map_or(Token::Identifier(str), |(_, token)| token)
     }
 }
@@ -0,0 +1,241 @@
+//! a pass over the tree that warns about code that runs, but likely isn't
+//! what was meant, run by `tram check` and the repl. scoping is dynamic, so
+//! a variable counts as read if any code anywhere reads a variable of that
+//! name, since a function could be reading its caller's
+
+use std::collections::HashSet;
+
+use crate::{executor::{binary, compare, unary}, function::Function, value::Value};
+
+use super::{ast::{AstNode, BinOp, Statement, UnOp}, diagnostic::{Diagnostic, Level, Span}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lint {
+    /// assigned, but never read
+    UnusedVariable,
+    /// after a `break` or `throw` that always happens
+    Unreachable,
+    /// an `if` that always takes the same branch
+    ConstantCondition
+}
+
+pub struct Warning {
+    pub lint: Lint,
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<String>,
+    pub help: Vec<String>
+}
+
+impl Warning {
+    fn new(lint: Lint, span: Span, message: String) -> Self {
+        Self { lint, span, message, notes: Vec::new(), help: Vec::new() }
+    }
+
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic {
+            level: Level::Warning,
+            message: self.message.clone(),
+            span: Some(self.span),
+            notes: self.notes.clone(),
+            help: self.help.clone(),
+            file: None
+        }
+    }
+
+    /// writes the warning to stderr, `file` being where `source` came from
+    pub fn log(&self, file: Option<&str>, source: Option<&str>) {
+        Diagnostic { file: file.map(str::to_owned), ..self.diagnostic() }.emit(source);
+    }
+}
+
+pub fn lint(ast: &AstNode) -> Vec<Warning> {
+    let mut linter = Linter { assigned: vec![], read: HashSet::new(), exported: HashSet::new(), warnings: vec![] };
+    linter.expr(ast);
+    let Linter { assigned, read, exported, mut warnings } = linter;
+    let mut seen = HashSet::new();
+    for (name, span) in assigned {
+        if read.contains(&name) || exported.contains(&name) || !seen.insert(name.clone()) {
+            continue
+        }
+        warnings.push(Warning::new(Lint::UnusedVariable, span, format!("`{}` is assigned, but never read", name)));
+    }
+    warnings.sort_by_key(|w| w.span.start);
+    warnings
+}
+
+struct Linter {
+    /// every assignment, in order, and where it is
+    assigned: Vec<(String, Span)>,
+    read: HashSet<String>,
+    /// made visible to importers by `pub`, who may read them
+    exported: HashSet<String>,
+    warnings: Vec<Warning>
+}
+
+/// what makes a statement always leave the code after it behind, if anything
+fn diverges(node: &AstNode) -> Option<&'static str> {
+    match node {
+        AstNode::Break(_) => Some("break"),
+        AstNode::Throw(_) => Some("throw"),
+        AstNode::Block(stmts, _) => stmts.iter().find_map(|Statement::Expression(e, _)| diverges(e)),
+        AstNode::If { then, or: Some(or), .. } => diverges(or).and(diverges(then)),
+        _ => None
+    }
+}
+
+/// the value of an expression made only of literals, if it can be
+/// worked out without running anything
+fn constant(node: &AstNode) -> Option<Value> {
+    match node {
+        AstNode::Value(v) => match v.as_ref() {
+            Value::Int(_) | Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Nil => Some((**v).clone()),
+            _ => None
+        },
+        AstNode::Unary(op, a, _) => unary(op, constant(a)?).ok(),
+        AstNode::Binary(BinOp::And | BinOp::Or | BinOp::Access, ..) => None,
+        AstNode::Binary(op, a, b, _) => {
+            let (a, b) = (constant(a)?, constant(b)?);
+            // repeating a string could make it huge
+            if *op == BinOp::Mul && matches!((&a, &b), (Value::String(_), _) | (_, Value::String(_))) {
+                return None
+            }
+            binary(op, a, b).ok()
+        },
+        AstNode::Compare(first, chain, _) => {
+            let mut lhs = constant(first)?;
+            let mut holds = true;
+            for (op, rhs) in chain {
+                let rhs = constant(rhs)?;
+                holds = holds && compare(op, &lhs, &rhs).ok()?;
+                lhs = rhs;
+            }
+            Some(Value::Bool(holds))
+        },
+        _ => None
+    }
+}
+
+/// whether a condition always holds or never does
+fn truthiness(node: &AstNode) -> Option<bool> {
+    match node {
+        AstNode::Binary(BinOp::And, a, b, _) => if truthiness(a)? { truthiness(b) } else { Some(false) },
+        AstNode::Binary(BinOp::Or, a, b, _) => if truthiness(a)? { Some(true) } else { truthiness(b) },
+        AstNode::Unary(UnOp::Not, a, _) => truthiness(a).map(|t| !t),
+        node => constant(node).map(|v| v.truthy())
+    }
+}
+
+impl Linter {
+    fn block(&mut self, stmts: &[Statement]) {
+        let mut after = None;
+        // one warning covers the rest of the block
+        let mut warned = false;
+        for Statement::Expression(e, span) in stmts {
+            if let (Some(cause), false) = (after, warned) {
+                let mut warning = Warning::new(Lint::Unreachable, *span, "unreachable code".to_owned());
+                warning.notes.push(format!("the `{}` before it always happens", cause));
+                self.warnings.push(warning);
+                warned = true;
+            }
+            self.expr(e);
+            after = after.or_else(|| diverges(e));
+        }
+    }
+
+    fn function(&mut self, func: &Function) {
+        for p in &func.params {
+            if let Some(default) = &p.default {
+                self.expr(default);
+            }
+        }
+        self.expr(&func.ast);
+    }
+
+    fn expr(&mut self, node: &AstNode) {
+        match node {
+            AstNode::Value(v) => {
+                if let Value::Function(f) = v.as_ref() {
+                    if let Some(f) = f.as_function() {
+                        self.function(f);
+                    }
+                }
+            },
+            AstNode::Ident(name, _, _) => {
+                self.read.insert(name.clone());
+            },
+            AstNode::Assign(name, _, value, span) => {
+                self.assigned.push((name.clone(), *span));
+                match value.as_ref() {
+                    // updating a variable with its own value isn't a use of it
+                    AstNode::Binary(BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Pow | BinOp::Mod, lhs, rhs, _)
+                        if matches!(lhs.as_ref(), AstNode::Ident(n, _, _) if n == name) => self.expr(rhs),
+                    value => self.expr(value)
+                }
+            },
+            AstNode::MultiAssign(_, values) => values.iter().for_each(|v| self.expr(v)),
+            AstNode::Call(callee, args, _) => {
+                self.expr(callee);
+                args.iter().for_each(|a| self.expr(a));
+            },
+            AstNode::Binary(_, lhs, rhs, _) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            },
+            AstNode::Compare(first, rest, _) => {
+                self.expr(first);
+                rest.iter().for_each(|(_, operand)| self.expr(operand));
+            },
+            AstNode::Slice { target, start, end, .. } => {
+                self.expr(target);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            },
+            AstNode::Unary(_, operand, _) => self.expr(operand),
+            AstNode::If { cond, then, or, span } => {
+                if let Some(holds) = truthiness(cond) {
+                    let (always, branch) = if holds { ("holds", "`else`") } else { ("fails", "body") };
+                    let mut warning = Warning::new(Lint::ConstantCondition, *span, format!("this condition always {}", always));
+                    if !holds || or.is_some() {
+                        warning.notes.push(format!("so the {} never runs", branch));
+                    }
+                    self.warnings.push(warning);
+                }
+                self.expr(cond);
+                self.expr(then);
+                if let Some(or) = or {
+                    self.expr(or);
+                }
+            },
+            AstNode::Block(stmts, _) => self.block(stmts),
+            AstNode::Loop { cond, run, .. } => {
+                if let Some(cond) = cond {
+                    self.expr(cond);
+                }
+                self.expr(run);
+            },
+            AstNode::For { iterable, run, .. } => {
+                self.expr(iterable);
+                self.expr(run);
+            },
+            AstNode::Yield(value) | AstNode::Throw(value) => self.expr(value),
+            AstNode::Try { body, handler, .. } => {
+                self.expr(body);
+                self.expr(handler);
+            },
+            AstNode::Assert { cond, message, .. } => {
+                self.expr(cond);
+                if let Some(message) = message {
+                    self.expr(message);
+                }
+            },
+            AstNode::Pub { name, value } => {
+                self.exported.insert(name.clone());
+                self.expr(value);
+            },
+            AstNode::Defer(deferred) => self.expr(deferred),
+            AstNode::Use { .. } | AstNode::Break(_) | AstNode::Error => {}
+        }
+    }
+}
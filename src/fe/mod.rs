@@ -3,3 +3,8 @@ pub mod token;
 pub mod ast;
 pub mod lexer;
 pub mod diagnostic;
+pub mod check;
+pub mod lint;
+pub mod dump;
+pub mod optimize;
+pub mod resolve;
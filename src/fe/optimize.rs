@@ -0,0 +1,179 @@
+//! an optional pass between parsing and running that simplifies the
+//! tree without changing what it does. constant expressions are folded,
+//! `if` on a constant is replaced by the branch it takes, and nested
+//! blocks are collapsed where the scoping allows it
+
+use std::rc::Rc;
+
+use crate::{executor::{binary, compare, unary}, value::Value};
+
+use super::ast::{AstNode, BinOp, Statement};
+
+/// the value of a literal that operators can be applied to ahead of time.
+/// anything holding a handle other than a string could be changed later
+fn constant(node: &AstNode) -> Option<&Value> {
+    match node {
+        AstNode::Value(v) => match v.as_ref() {
+            Value::Int(_) | Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Nil => Some(v),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// whether a statement could be dropped if its value isn't used
+fn pure(node: &AstNode) -> bool {
+    matches!(node, AstNode::Value(_) | AstNode::Ident(..))
+}
+
+fn literal(val: Value) -> AstNode {
+    AstNode::Value(Box::new(val))
+}
+
+impl AstNode {
+    /// simplifies the tree in place, including the bodies of the functions in it
+    pub fn optimize(&mut self) {
+        match self {
+            AstNode::Call(func, args, _) => {
+                func.optimize();
+                args.iter_mut().for_each(AstNode::optimize);
+            },
+            AstNode::Value(v) => {
+                // a function only has one owner until the program runs
+                if let Value::Function(func) = v.as_mut() {
+                    if let Some(func) = Rc::get_mut(func).and_then(|f| f.as_function_mut()) {
+                        for p in &mut func.params {
+                            if let Some(default) = &mut p.default {
+                                default.optimize();
+                            }
+                        }
+                        if let Some(body) = Rc::get_mut(&mut func.ast) {
+                            body.optimize();
+                        }
+                    }
+                }
+            },
+            AstNode::Assign(_, _, value, _) | AstNode::Unary(_, value, _) | AstNode::Yield(value)
+                | AstNode::Throw(value) | AstNode::Pub { value, .. } => value.optimize(),
+            AstNode::MultiAssign(_, values) => values.iter_mut().for_each(AstNode::optimize),
+            AstNode::Binary(_, a, b, _) => {
+                a.optimize();
+                b.optimize();
+            },
+            AstNode::Slice { target, start, end, .. } => {
+                target.optimize();
+                for bound in [start, end].into_iter().flatten() {
+                    bound.optimize();
+                }
+            },
+            AstNode::Compare(first, chain, _) => {
+                first.optimize();
+                chain.iter_mut().for_each(|(_, rhs)| rhs.optimize());
+            },
+            AstNode::If { cond, then, or, .. } => {
+                cond.optimize();
+                then.optimize();
+                if let Some(or) = or {
+                    or.optimize();
+                }
+            },
+            AstNode::Block(stmts, _) => {
+                for Statement::Expression(e, _) in stmts.iter_mut() {
+                    e.optimize();
+                }
+            },
+            AstNode::Loop { cond, run, .. } => {
+                if let Some(cond) = cond {
+                    cond.optimize();
+                }
+                run.optimize();
+            },
+            AstNode::For { iterable, run, .. } => {
+                iterable.optimize();
+                run.optimize();
+            },
+            AstNode::Try { body, handler, .. } => {
+                body.optimize();
+                handler.optimize();
+            },
+            AstNode::Assert { cond, message, .. } => {
+                cond.optimize();
+                if let Some(message) = message {
+                    message.optimize();
+                }
+            },
+            AstNode::Defer(expr) => {
+                if let Some(expr) = Rc::get_mut(expr) {
+                    expr.optimize();
+                }
+            },
+            AstNode::Ident(..) | AstNode::Break(_) | AstNode::Use { .. } | AstNode::Error => {}
+        }
+        if let Some(simpler) = self.fold() {
+            *self = simpler;
+        }
+    }
+
+    /// a simpler node to replace this one with, once its children are optimized
+    fn fold(&mut self) -> Option<AstNode> {
+        match self {
+            AstNode::Binary(op, a, b, _) => {
+                let (a, b) = (constant(a)?, constant(b)?);
+                // repeating a string could make it huge, so it's left for runtime
+                if *op == BinOp::Mul && matches!((a, b), (Value::String(_), _) | (_, Value::String(_))) {
+                    return None
+                }
+                // whether dividing by zero fails depends on the vm it runs in
+                if matches!(op, BinOp::Div | BinOp::Mod) && b.num().is_ok_and(|n| n == 0.0) {
+                    return None
+                }
+                // anything that fails is left to fail when it runs
+                binary(op, a.clone(), b.clone()).ok().map(literal)
+            },
+            AstNode::Unary(op, a, _) => unary(op, constant(a)?.clone()).ok().map(literal),
+            AstNode::Compare(first, chain, _) => {
+                let mut lhs = constant(first)?;
+                let mut holds = true;
+                for (op, rhs) in chain.iter() {
+                    let rhs = constant(rhs)?;
+                    holds = holds && compare(op, lhs, rhs).ok()?;
+                    lhs = rhs;
+                }
+                Some(literal(Value::Bool(holds)))
+            },
+            AstNode::If { cond, then, or, .. } => {
+                let branch = if constant(cond)?.truthy() { Some(then) } else { or.as_mut() };
+                Some(match branch {
+                    Some(branch) => std::mem::replace(branch.as_mut(), AstNode::Error),
+                    None => literal(Value::Nil)
+                })
+            },
+            AstNode::Block(stmts, scoped) => {
+                // a block without a scope is just its statements
+                let mut flat = Vec::with_capacity(stmts.len());
+                let last = stmts.len().saturating_sub(1);
+                for (i, Statement::Expression(e, span)) in std::mem::take(stmts).into_iter().enumerate() {
+                    match *e {
+                        AstNode::Block(inner, false) if i != last => flat.extend(inner),
+                        e => flat.push(Statement::Expression(Box::new(e), span))
+                    }
+                }
+                let last = flat.len().saturating_sub(1);
+                flat = flat.into_iter()
+                    .enumerate()
+                    .filter(|(i, Statement::Expression(e, _))| *i == last || !pure(e))
+                    .map(|(_, stmt)| stmt)
+                    .collect();
+                *stmts = flat;
+                // a block holding a single block needs only one of their scopes
+                if let [Statement::Expression(inner, _)] = stmts.as_mut_slice() {
+                    if let AstNode::Block(inner, inner_scoped) = inner.as_mut() {
+                        return Some(AstNode::Block(std::mem::take(inner), *scoped || *inner_scoped))
+                    }
+                }
+                None
+            },
+            _ => None
+        }
+    }
+}
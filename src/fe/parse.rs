@@ -1,8 +1,8 @@
 use std::{borrow::Cow, rc::Rc, str::FromStr};
 
-use crate::{fe::{ast::{BinOp, UnOp}, diagnostic::{ParseError, Span}}, function::Function, handle::Handle, value::Value};
+use crate::{fe::{ast::{BinOp, Type, UnOp}, diagnostic::{ParseError, Span}}, function::{Function, Param}, handle::Handle, value::Value};
 
-use super::{ast::{Ast, AstNode, Statement}, lexer::Lexer, token::Token};
+use super::{ast::{Ast, AstNode, Slot, Statement}, lexer::Lexer, token::Token};
 
 impl FromStr for Ast {
     type Err = Vec<ParseError>;
@@ -29,18 +29,23 @@ macro_rules! precs {
 precs!(
     NONE: 0,
     ASSIGN: 1,
-    OR: 2,
-    AND: 3,
-    EQ: 4,
-    COMP: 5,
-    TERM: 6,
-    FACTOR: 7,
-    POW: 8,
-    UNARY: 9,
-    CALL: 10,
-    DOT: 11,
-    INDEX: 12,
-    PRIMARY: 13
+    PIPE: 2,
+    OR: 3,
+    AND: 4,
+    EQ: 5,
+    COMP: 6,
+    BIT_OR: 7,
+    BIT_XOR: 8,
+    BIT_AND: 9,
+    SHIFT: 10,
+    TERM: 11,
+    FACTOR: 12,
+    POW: 13,
+    UNARY: 14,
+    CALL: 15,
+    DOT: 16,
+    INDEX: 17,
+    PRIMARY: 18
 );
 
 impl Token {
@@ -51,9 +56,14 @@ impl Token {
             Mul | Div | Mod => prec::FACTOR,
             Pow => prec::POW,
             Gt | GtEq | Lt | LtEq => prec::COMP,
-            Eq | NotEq => prec::EQ,
+            BitOr => prec::BIT_OR,
+            BitXor => prec::BIT_XOR,
+            BitAnd => prec::BIT_AND,
+            Shl | Shr => prec::SHIFT,
+            Eq | NotEq | In | Is => prec::EQ,
             And => prec::AND,
             Or => prec::OR,
+            Pipe => prec::PIPE,
             Assign | AddEq | SubEq | MulEq | DivEq | PowEq | ModEq => prec::ASSIGN,
             LParen => prec::CALL,
             Dot => prec::DOT,
@@ -69,6 +79,7 @@ impl Token {
             prec::DOT => Some(Parser::dot_expr),
             prec::INDEX => Some(Parser::access_expr),
             prec::ASSIGN => Some(Parser::assign),
+            prec::PIPE => Some(Parser::pipe),
             _ => Some(Parser::binary)
         }
     }
@@ -76,29 +87,60 @@ impl Token {
 
 pub struct Parser {
     lexer: Lexer,
+    /// shared with the functions parsed from it
+    source: Rc<str>,
     current: Token,
     next: Token,
     next_span: Span,
     current_span: Span,
-    errors: Vec<ParseError>
+    errors: Vec<ParseError>,
+    /// set by an error until the parser has skipped to where the next
+    /// statement starts. errors in between are left out, since they're
+    /// most likely the first one cascading
+    recovering: bool,
+    /// how many function bodies deep the parser is
+    func_depth: usize,
+    /// whether the function body being parsed contains a `yield`
+    saw_yield: bool,
+    /// how many `{ }` blocks deep the parser is
+    block_depth: usize,
+    /// how many expressions deep the parser is, and how deep they may go
+    depth: usize,
+    max_depth: usize
 }
 
+/// how deeply expressions may nest by default, which is well within
+/// the stack of a spawned thread, even in a debug build
+pub const MAX_DEPTH: usize = 256;
+
 impl Parser {
     /// The start of `lexed` must be a `Start` token,
     /// and the end must be an `Eof` token.
     pub fn new(source: &str) -> Self {
         let mut lexer = Lexer::new(source);
-        let (next, span) = lexer.next();
+        let (next, span) = lexer.next_token();
         Self {
             current: Token::Start,
             next,
             errors: vec![],
+            recovering: false,
             lexer,
+            source: source.into(),
             current_span: Span::empty(),
-            next_span: span
+            next_span: span,
+            func_depth: 0,
+            saw_yield: false,
+            block_depth: 0,
+            depth: 0,
+            max_depth: MAX_DEPTH
         }
     }
 
+    /// limits how deeply expressions may nest, past which parsing fails
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
     pub fn parse_all(&mut self) -> (Ast, Vec<ParseError>) {
         let block = self.block(false, false);
         let errors = std::mem::take(&mut self.errors);
@@ -106,7 +148,61 @@ impl Parser {
     }
 
     pub fn statement(&mut self) -> Statement {
-        Statement::Expression(self.expression())
+        let start = self.next_span.start;
+        let expr = if self.block_depth == 0 && self.pick(&Token::Pub) {
+            self.pub_stmt()
+        } else {
+            let expr = self.expression();
+            match (expr.as_ref(), &self.next) {
+                (AstNode::Ident(first, _, _), Token::Comma) => {
+                    let first = first.clone();
+                    self.multi_assign(first)
+                },
+                _ => expr
+            }
+        };
+        Statement::Expression(expr, Span::new(start, self.current_span.end))
+    }
+
+    /// parses the rest of `a, b = b, a`, after the first name
+    fn multi_assign(&mut self, first: String) -> Ast {
+        let mut names = vec![first];
+        while self.pick(&Token::Comma) {
+            let Token::Identifier(name) = &self.next else {
+                return self.error("expected identifier in assignment targets")
+            };
+            names.push(name.clone());
+            self.advance();
+        }
+        if !self.pick(&Token::Assign) {
+            return self.error("expected `=` after assignment targets")
+        }
+        let mut values = vec![*self.expression()];
+        while self.pick(&Token::Comma) {
+            values.push(*self.expression());
+        }
+        if names.len() != values.len() {
+            return self.error(format!("assigning {} values to {} names", values.len(), names.len()))
+        }
+        Ast::new(AstNode::MultiAssign(names, values))
+    }
+
+    /// parses what follows `pub`, which has to bind a name
+    fn pub_stmt(&mut self) -> Ast {
+        let value = self.expression();
+        let name = match value.as_ref() {
+            AstNode::Assign(name, ..) | AstNode::Use { binding: name, .. } => name.clone(),
+            // named functions desugar to an assignment followed by the name
+            AstNode::Block(stmts, false) => match stmts.first() {
+                Some(Statement::Expression(e, _)) => match e.as_ref() {
+                    AstNode::Assign(name, ..) => name.clone(),
+                    _ => return self.error("expected an assignment, function or `use` after `pub`")
+                },
+                None => return self.error("expected an assignment, function or `use` after `pub`")
+            },
+            _ => return self.error("expected an assignment, function or `use` after `pub`")
+        };
+        Ast::new(AstNode::Pub { name, value })
     }
 
     pub fn expression(&mut self) -> Ast {
@@ -114,32 +210,78 @@ impl Parser {
     }
 
     fn parse_with_prec(&mut self, prec: u8) -> Ast {
+        // each level of nesting recurses, so a deep enough one would overflow the stack
+        if self.depth == self.max_depth {
+            self.advance();
+            return self.report(ParseError::new(self.current_span, "expression nested too deeply")
+                .note(format!("expressions can be nested at most {} deep", self.max_depth)))
+        }
+        self.depth += 1;
+        let node = self.nested(prec);
+        self.depth -= 1;
+        node
+    }
+
+    fn nested(&mut self, prec: u8) -> Ast {
         self.advance();
+        let start = self.current_span.start;
         let mut node = match &self.current {
-            Token::Number(..)
+            Token::Int(..)
+            | Token::Number(..)
             | Token::String(..)
             | Token::True | Token::False | Token::Nil => self.literal(),
             Token::Identifier(..) => self.ident(),
             Token::Func => self.func(),
             Token::If => self.if_expr(),
             Token::Loop => self.loop_expr(),
+            Token::For => self.for_expr(),
+            Token::Yield => self.yield_expr(),
             Token::LBrace => self.block(true, true),
+            Token::LParen => self.group_or_lambda(),
             Token::Break => Ast::new(AstNode::Break(None)),
-            Token::Not | Token::Sub => self.unary(),
+            Token::Try => self.try_expr(),
+            Token::Assert => self.assert(),
+            Token::Use => self.use_stmt(),
+            Token::Pub => self.report(ParseError::new(self.current_span, "`pub` is only allowed at the top level of a module")
+                .help("move the definition out to the top level of the file")),
+            Token::Defer => {
+                let deferred = self.expression();
+                Ast::new(AstNode::Defer(Rc::new(*deferred)))
+            },
+            Token::Throw => {
+                let thrown = self.expression();
+                Ast::new(AstNode::Throw(thrown))
+            },
+            Token::Not | Token::Sub | Token::BitNot => self.unary(),
+            Token::Error(e) => self.error(e.clone()),
             t => self.error(format!("unexpected token {:?}", t))
         };
         while prec <= self.next.prec() {
+            // `f` then `(g)` on the next line could be either a call or two statements
+            if matches!(self.next, Token::LParen | Token::LBracket) && self.line_break() {
+                let open = if self.next == Token::LParen { "(" } else { "[" };
+                // the line it starts is skipped along with the rest of the statement
+                self.advance();
+                return self.report(ParseError::new(self.current_span, format!("ambiguous `{}` at the start of a line", open))
+                    .help(format!("to continue the expression on the line before, move the `{}` onto it", open))
+                    .help("to start a new statement, end the line before with a `;`"))
+            }
             self.advance();
             node = if let Some(ifix) = self.current.infix() {
                 ifix(self, node, self.current.prec() + 1)
             } else {
                 return self.error(format!("{:?} has no infix value!", self.current))
+            };
+            // an infix node's span starts at its left side, which was parsed before it
+            if let Some(span) = node.span_mut() {
+                span.start = span.start.min(start);
             }
         }
         node
     }
 
     fn dot_expr(&mut self, lhs: Ast, _prec: u8) -> Ast {
+        let start = self.current_span.start;
         let Token::Identifier(i) = &self.next else {
             return self.error("identifier expected following `.`");
         };
@@ -149,12 +291,33 @@ impl Parser {
         Ast::new(AstNode::Binary(
             BinOp::Access,
             lhs, 
-            Ast::new(st)
+            Ast::new(st),
+            Span::new(start, self.current_span.end)
         ))
     }
 
     fn access_expr(&mut self, lhs: Ast, _prec: u8) -> Ast {
-        let key = self.expression();
+        let open = self.current_span.start;
+        let start = if self.next == Token::DotDot {
+            None
+        } else {
+            Some(self.expression())
+        };
+        if self.pick(&Token::DotDot) {
+            let end = if self.next == Token::RBracket {
+                None
+            } else {
+                Some(self.expression())
+            };
+            if !self.pick(&Token::RBracket) {
+                return self.error("expected `]` to close slice");
+            }
+            let span = Span::new(open, self.current_span.end);
+            return Ast::new(AstNode::Slice { target: lhs, start, end, span })
+        }
+        let Some(key) = start else {
+            return self.error("expected index key");
+        };
         if !self.pick(&Token::RBracket) {
             return self.error("expected `]` after expression to close index key");
         }
@@ -162,7 +325,8 @@ impl Parser {
         Ast::new(AstNode::Binary(
             BinOp::Access,
             lhs,
-            key
+            key,
+            Span::new(open, self.current_span.end)
         ))
     }
 
@@ -170,35 +334,78 @@ impl Parser {
         let Token::Identifier(s) = &self.current else {
             return self.error("expected an identifier");
         };
-        Ast::new(AstNode::Ident(s.clone()))
+        Ast::new(AstNode::Ident(s.clone(), Slot::Unresolved, self.current_span))
     }
 
     fn call(&mut self, func: Ast, _prec: u8) -> Ast {
+        let start = self.current_span.start;
         let mut args = Vec::new();
         while !self.pick(&Token::RParen) {
             args.push(*self.expression());
-            if self.next != Token::RParen {
-                if !self.pick(&Token::Comma) {
-                    return self.error("expected comma after expression");
-                }
+            if self.next != Token::RParen && !self.pick(&Token::Comma) {
+                return self.error("expected comma after expression");
             }
         }
-        Ast::new(AstNode::Call(func, args))
+        let span = Span::new(start, self.current_span.end);
+        Ast::new(AstNode::Call(func, args, span))
     }
 
     fn error(&mut self, message: impl Into<Cow<'static, str>>) -> Ast {
-        let m = message.into();
-        let error = ParseError {
-            span: self.current_span,
-            message: m.into()
-        };
-        self.errors.push(error);
+        self.report(ParseError::new(self.current_span, message))
+    }
+
+    /// records an error that's been given notes or help
+    fn report(&mut self, error: ParseError) -> Ast {
+        if !self.recovering {
+            self.errors.push(error);
+            self.recovering = true;
+        }
         Ast::new(AstNode::Error)
     }
 
+    /// after an error, skips the rest of the statement it was in. that ends
+    /// at a `;`, at the end of the line, unless a bracket is still open, or
+    /// at the `}` closing the block the statement is in
+    fn synchronize(&mut self) {
+        let mut open = 0usize;
+        loop {
+            match self.next {
+                Token::Eof => break,
+                Token::RBrace if open == 0 && self.block_depth > 0 => break,
+                _ if open == 0 && (self.line_break() || self.current == Token::Semicolon) => break,
+                Token::LParen | Token::LBracket | Token::LBrace => open += 1,
+                Token::RParen | Token::RBracket | Token::RBrace => open = open.saturating_sub(1),
+                _ => {}
+            }
+            self.advance();
+        }
+        self.recovering = false;
+    }
+
+    /// whether the next token is on a later line than the current one
+    fn line_break(&self) -> bool {
+        self.lexer.slice(Span::new(self.current_span.end, self.next_span.start)).contains('\n')
+    }
+
+    /// statements are separated by a `;` or a newline, unless the statement
+    /// is the last one in its block or ends with a block, like an `if` does
+    fn end_statement(&mut self, expect_end: bool) {
+        if self.pick(&Token::Semicolon) || self.line_break() || self.current == Token::RBrace {
+            return
+        }
+        match self.next {
+            Token::Eof => {},
+            Token::RBrace if expect_end => {},
+            _ => {
+                self.report(ParseError::new(self.next_span, "expected `;` or a newline after the statement")
+                    .help("statements on the same line need a `;` between them"));
+            }
+        }
+    }
+
     fn assign(&mut self, lhs: Ast, prec: u8) -> Ast {
-        let name = match &*lhs {
-            AstNode::Ident(s) => s.clone(),
+        let (name, target) = match &*lhs {
+            AstNode::Ident(s, _, span) => (s.clone(), *span),
             _ => return self.error("invalid assignment target")
         };
         macro_rules! map {
@@ -218,16 +425,35 @@ impl Parser {
             PowEq => Pow,
             ModEq => Mod
         );
+        let start = self.current_span.start;
         let rhs = self.parse_with_prec(prec);
         let value = if let Some(op) = op {
-            Ast::new(AstNode::Binary(op, lhs, rhs))
+            let span = Span::new(start, self.current_span.end);
+            Ast::new(AstNode::Binary(op, lhs, rhs, span))
         } else {
             rhs
         };
-        Ast::new(AstNode::Assign(name, value))
+        let span = Span::new(target.start, self.current_span.end);
+        Ast::new(AstNode::Assign(name, Slot::Unresolved, value, span))
+    }
+
+    // the signature is shared by every infix parselet
+    #[allow(clippy::boxed_local)]
+    fn pipe(&mut self, lhs: Ast, prec: u8) -> Ast {
+        // x |> f(a) ==> f(x, a)
+        // x |> f    ==> f(x)
+        let rhs = self.parse_with_prec(prec);
+        match *rhs {
+            AstNode::Call(func, mut args, span) => {
+                args.insert(0, *lhs);
+                Ast::new(AstNode::Call(func, args, span))
+            },
+            func => Ast::new(AstNode::Call(Ast::new(func), vec![*lhs], self.current_span))
+        }
     }
 
     fn func(&mut self) -> Ast {
+        let start = self.current_span.start;
         let name = match &self.next {
             Token::Identifier(s) => {
                 let n = s.clone();
@@ -240,27 +466,67 @@ impl Parser {
             return self.error("expected `(` to start argument list")
         }
         let mut args = Vec::new();
+        let mut rest = None;
         while !self.pick(&Token::RParen) {
+            if self.pick(&Token::Ellipsis) {
+                match self.rest_param() {
+                    Ok((r, _)) if args.iter().any(|p: &Param| p.name == r) => return self.duplicate_param(&r, self.current_span),
+                    Ok((r, _)) => rest = Some(r),
+                    Err(e) => return e
+                }
+                break
+            }
             self.advance();
-            let Token::Identifier(id) = &self.current else {
-                return self.error("expected identifier in argument list")
+            let name = match &self.current {
+                Token::Identifier(id) => id.clone(),
+                t => return match t.keyword() {
+                    Some(keyword) => self.error(format!("`{}` is a keyword, so it can't be a parameter name", keyword)),
+                    None => self.error(format!("expected a parameter name, got {:?}", t))
+                }
             };
-            args.push(id.clone());
-            if self.next != Token::RParen {
-                assert_eq!(self.next, Token::Comma);
-                self.advance();
+            if args.iter().any(|p: &Param| p.name == name) {
+                return self.duplicate_param(&name, self.current_span)
+            }
+            let ty = if self.pick(&Token::Colon) {
+                match self.type_annotation() {
+                    Ok(ty) => Some(ty),
+                    Err(e) => return e
+                }
+            } else { None };
+            let default = if self.pick(&Token::Assign) {
+                Some(self.expression())
+            } else if args.iter().any(|p: &Param| p.default.is_some()) {
+                return self.error("parameters without a default cannot follow ones with a default")
+            } else { None };
+            if self.next != Token::RParen && !self.pick(&Token::Comma) {
+                return self.report(ParseError::new(self.next_span, format!("expected `,` or `)` after the parameter `{}`", name))
+                    .help("parameters are separated by commas"))
             }
+            args.push(Param { name, default, ty });
         }
 
+        let ret = if self.pick(&Token::Arrow) {
+            match self.type_annotation() {
+                Ok(ty) => Some(ty),
+                Err(e) => return e
+            }
+        } else { None };
+
         if !self.pick(&Token::LBrace) {
             return self.error(
                 format!("expected `{{` to open the function block, got: {:?}", self.next));
         }
-        let ast = self.block(true, true);
+        let (ast, generator) = self.function_body(|p| p.block(true, true));
+        let span = Span::new(start, self.current_span.end);
         let func = Function {
             name: name.clone(),
             params: args,
-            ast
+            rest,
+            ast: ast.into(),
+            generator,
+            ret,
+            source: self.source.clone(),
+            span
         };
         let fn_value = Ast::new(AstNode::Value(Box::new(
             Value::Function(Rc::new(func))
@@ -268,13 +534,14 @@ impl Parser {
 
         if let Some(name) = name {
             // func hello() {} ==> hello = func hello() {}
+            // the statements made up here are part of the one the function is in
             let assignment = Statement::Expression(
-                Ast::new(AstNode::Assign(name.clone(), fn_value))
+                Ast::new(AstNode::Assign(name.clone(), Slot::Unresolved, fn_value, span)), Span::empty()
             );
             Ast::new(AstNode::Block(
                 vec![
                     assignment,
-                    Statement::Expression(Ast::new(AstNode::Ident(name)))
+                    Statement::Expression(Ast::new(AstNode::Ident(name, Slot::Unresolved, span)), Span::empty())
                 ], false
             ))
         } else {
@@ -282,8 +549,145 @@ impl Parser {
         }
     }
 
+    /// parses the type name following a `:` or `->`
+    fn type_annotation(&mut self) -> Result<Type, Ast> {
+        let ty = match &self.next {
+            Token::Identifier(name) => Type::from_name(name),
+            Token::Nil => Some(Type::Nil),
+            _ => None
+        };
+        let Some(ty) = ty else {
+            return Err(self.error(format!("expected a type name, got {:?}", self.next)))
+        };
+        self.advance();
+        Ok(ty)
+    }
+
+    /// parses the name following `...` and the `)` closing the parameter list
+    fn rest_param(&mut self) -> Result<(String, Span), Ast> {
+        let Token::Identifier(id) = &self.next else {
+            return Err(self.error("expected identifier after `...`"))
+        };
+        let rest = id.clone();
+        self.advance();
+        let span = self.current_span;
+        // a trailing comma is allowed, but nothing else
+        self.pick(&Token::Comma);
+        if !self.pick(&Token::RParen) {
+            return Err(self.error("the rest parameter must be the last parameter"))
+        }
+        Ok((rest, span))
+    }
+
+    fn duplicate_param(&mut self, name: &str, span: Span) -> Ast {
+        self.report(ParseError::new(span, format!("there's already a parameter named `{}`", name))
+            .help("each parameter needs its own name"))
+    }
+
+    /// parses either a parenthesized expression, `(a + b)`,
+    /// or a short lambda, `(a, b = 1) -> a + b`
+    fn group_or_lambda(&mut self) -> Ast {
+        let start = self.current_span.start;
+        let mut exprs = Vec::new();
+        let mut rest = None;
+        while !self.pick(&Token::RParen) {
+            if self.pick(&Token::Ellipsis) {
+                match self.rest_param() {
+                    Ok(r) => rest = Some(r),
+                    Err(e) => return e
+                }
+                break
+            }
+            let expr = self.expression();
+            let ty = if self.pick(&Token::Colon) {
+                match self.type_annotation() {
+                    Ok(ty) => Some(ty),
+                    Err(e) => return e
+                }
+            } else { None };
+            // `(a: number = 1)`, where the annotation comes between the name and default
+            let expr = match (*expr, ty) {
+                (AstNode::Ident(name, _, span), Some(_)) if self.pick(&Token::Assign) => {
+                    let default = self.expression();
+                    let span = Span::new(span.start, self.current_span.end);
+                    Ast::new(AstNode::Assign(name, Slot::Unresolved, default, span))
+                },
+                (expr, _) => Ast::new(expr)
+            };
+            exprs.push((expr, ty));
+            if self.next != Token::RParen && !self.pick(&Token::Comma) {
+                return self.error("expected `,` or `)` after expression");
+            }
+        }
+
+        if !self.pick(&Token::Arrow) {
+            if exprs.len() != 1 || rest.is_some() || exprs[0].1.is_some() {
+                return self.error("expected `->` after lambda parameters");
+            }
+            return exprs.pop().unwrap().0;
+        }
+
+        let mut params = Vec::new();
+        for (expr, ty) in exprs {
+            let (param, span) = match *expr {
+                AstNode::Ident(name, _, span) => (Param { name, default: None, ty }, span),
+                AstNode::Assign(name, _, default, span) => (Param { name, default: Some(default), ty }, span),
+                _ => return self.error("expected identifier in lambda parameter list")
+            };
+            if params.iter().any(|p: &Param| p.name == param.name) {
+                return self.duplicate_param(&param.name, span)
+            }
+            if param.default.is_none() && params.iter().any(|p: &Param| p.default.is_some()) {
+                return self.error("parameters without a default cannot follow ones with a default")
+            }
+            params.push(param);
+        }
+        if let Some((name, span)) = &rest {
+            if params.iter().any(|p| p.name == *name) {
+                return self.duplicate_param(name, *span)
+            }
+        }
+        let rest = rest.map(|(name, _)| name);
+        let (ast, generator) = self.function_body(|p| p.expression());
+        Ast::new(AstNode::Value(Box::new(Value::Function(Rc::new(Function {
+            name: None,
+            params,
+            rest,
+            ast: ast.into(),
+            generator,
+            ret: None,
+            source: self.source.clone(),
+            span: Span::new(start, self.current_span.end)
+        })))))
+    }
+
+    /// parses `use name`, which looks for `name.tr`,
+    /// or `use "path/to/name.tr"`. both bind the module to `name`
+    fn use_stmt(&mut self) -> Ast {
+        let (path, binding) = match &self.next {
+            Token::Identifier(name) => (name.clone(), name.clone()),
+            Token::String(path) => {
+                let stem = std::path::Path::new(path)
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                (path.clone(), stem)
+            },
+            _ => return self.error(format!("expected a module name or path after `use`, got {:?}", self.next))
+        };
+        self.advance();
+        let valid = binding.chars().next().is_some_and(|c| c.is_alphabetic())
+            && binding.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid {
+            return self.error(format!("the module name `{}` is not a valid identifier", binding))
+        }
+        Ast::new(AstNode::Use { path, binding })
+    }
+
     fn if_expr(&mut self) -> Ast {
+        let start = self.current_span.start;
         let cond = self.expression();
+        let span = Span::new(start, self.current_span.end);
         if !self.pick(&Token::LBrace) {
             return self.error("expected `{` to open then block after if condition")
         }
@@ -300,28 +704,96 @@ impl Parser {
         Ast::new(AstNode::If {
             cond,
             then,
-            or
+            or,
+            span
+        })
+    }
+
+    fn assert(&mut self) -> Ast {
+        if !self.pick(&Token::LParen) {
+            return self.error("expected `(` after `assert`")
+        }
+        let start = self.next_span.start;
+        let cond = self.expression();
+        let span = Span::new(start, self.current_span.end);
+        let mut message = None;
+        // either can be followed by a trailing comma
+        if self.pick(&Token::Comma) && self.next != Token::RParen {
+            message = Some(self.expression());
+            self.pick(&Token::Comma);
+        }
+        if !self.pick(&Token::RParen) {
+            return self.error("expected `)` to close assert")
+        }
+        Ast::new(AstNode::Assert {
+            cond,
+            message,
+            source: self.lexer.slice(span),
+            span
         })
     }
 
+    fn try_expr(&mut self) -> Ast {
+        if !self.pick(&Token::LBrace) {
+            return self.error("expected `{` to open try block")
+        }
+        let body = self.block(true, true);
+        if !self.pick(&Token::Catch) {
+            return self.error("expected `catch` after try block")
+        }
+        let binding = match &self.next {
+            Token::Identifier(s) => {
+                let b = s.clone();
+                self.advance();
+                Some(b)
+            },
+            _ => None
+        };
+        if !self.pick(&Token::LBrace) {
+            return self.error("expected `{` to open catch block")
+        }
+        let handler = self.block(true, true);
+        Ast::new(AstNode::Try { body, binding, handler })
+    }
+
     fn block(&mut self, expect_end: bool, scoped: bool) -> Ast {
+        // the `{` opening the block, when it has one
+        let open = self.current_span;
         let mut v = Vec::new();
+        if expect_end {
+            self.block_depth += 1;
+        }
         loop {
+            // empty statements
+            while self.pick(&Token::Semicolon) {}
             if expect_end && self.pick(&Token::RBrace) {
                 break;
             } else if self.pick(&Token::Eof) {
                 if expect_end {
-                    return self.error("expected closing `}`")
+                    self.block_depth -= 1;
+                    let (line, col) = open.line_col(&self.source);
+                    return self.report(ParseError::new(self.current_span, "expected closing `}`")
+                        .note(format!("the block was opened at line {}, column {}", line, col)))
                 } else {
                     break
                 }
             }
             v.push(self.statement());
+            if !self.recovering {
+                self.end_statement(expect_end);
+            }
+            if self.recovering {
+                self.synchronize();
+            }
+        }
+        if expect_end {
+            self.block_depth -= 1;
         }
         Ast::new(AstNode::Block(v, scoped))
     }
 
     fn binary(&mut self, lhs: Ast, prec: u8) -> Ast {
+        let start = self.current_span.start;
         let op = match &self.current {
             Token::Add => BinOp::Add,
             Token::Sub => BinOp::Sub,
@@ -331,31 +803,103 @@ impl Parser {
             Token::Pow => BinOp::Pow,
             Token::Eq => BinOp::Eq,
             Token::NotEq => BinOp::NotEq,
+            Token::In => BinOp::In,
+            Token::Is => BinOp::Is,
             Token::Gt => BinOp::Gt,
             Token::GtEq => BinOp::GtEq,
             Token::Lt => BinOp::Lt,
             Token::LtEq => BinOp::LtEq,
             Token::And => BinOp::And,
             Token::Or => BinOp::Or,
+            Token::BitAnd => BinOp::BitAnd,
+            Token::BitOr => BinOp::BitOr,
+            Token::BitXor => BinOp::BitXor,
+            Token::Shl => BinOp::Shl,
+            Token::Shr => BinOp::Shr,
 
             x => return self.error(format!("no binary expression implemented for {:?}", x))
         };
 
         let rhs = self.parse_with_prec(prec);
 
-        Ast::new(AstNode::Binary(op, lhs, rhs))
+        if prec == prec::COMP + 1 && self.next.prec() == prec::COMP {
+            return self.compare_chain(op, lhs, *rhs, start)
+        }
+
+        Ast::new(AstNode::Binary(op, lhs, rhs, Span::new(start, self.current_span.end)))
+    }
+
+    /// continues `a < b` into a chain like `a < b < c`
+    fn compare_chain(&mut self, op: BinOp, lhs: Ast, rhs: AstNode, start: usize) -> Ast {
+        let mut chain = vec![(op, rhs)];
+        while self.next.prec() == prec::COMP {
+            self.advance();
+            let op = match &self.current {
+                Token::Gt => BinOp::Gt,
+                Token::GtEq => BinOp::GtEq,
+                Token::Lt => BinOp::Lt,
+                Token::LtEq => BinOp::LtEq,
+                x => return self.error(format!("no comparison implemented for {:?}", x))
+            };
+            let rhs = self.parse_with_prec(prec::COMP + 1);
+            chain.push((op, *rhs));
+        }
+        Ast::new(AstNode::Compare(lhs, chain, Span::new(start, self.current_span.end)))
     }
 
     fn unary(&mut self) -> Ast {
+        let start = self.current_span.start;
         let op = match &self.current {
             Token::Not => UnOp::Not,
             Token::Sub => UnOp::Sub,
+            Token::BitNot => UnOp::BitNot,
 
             x => return self.error(format!("no unary expression implemented for {:?}", x))
         };
-        let expr = self.expression();
+        let expr = self.parse_with_prec(prec::UNARY);
+
+        Ast::new(AstNode::Unary(op, expr, Span::new(start, self.current_span.end)))
+    }
+
+    fn for_expr(&mut self) -> Ast {
+        let Token::Identifier(binding) = &self.next else {
+            return self.error("expected identifier after `for`")
+        };
+        let binding = binding.clone();
+        self.advance();
+        if !self.pick(&Token::In) {
+            return self.error("expected `in` after for loop variable")
+        }
+        let iterable = self.expression();
+        if !self.pick(&Token::LBrace) {
+            return self.error(format!("expected `{{` to open for loop, got {:?}", self.next));
+        }
+        let run = self.block(true, true);
+        Ast::new(AstNode::For { binding, iterable, run })
+    }
+
+    fn yield_expr(&mut self) -> Ast {
+        if self.func_depth == 0 {
+            return self.report(ParseError::new(self.current_span, "`yield` can only be used inside of a function")
+                .help("move it into a `func`, which makes calling that function return a generator"))
+        }
+        self.saw_yield = true;
+        let value = if self.next == Token::RBrace {
+            Ast::new(AstNode::Value(Box::new(Value::Nil)))
+        } else {
+            self.expression()
+        };
+        Ast::new(AstNode::Yield(value))
+    }
 
-        Ast::new(AstNode::Unary(op, expr))
+    /// parses a function body, returning
+    /// whether it yields and so makes a generator
+    fn function_body(&mut self, parse: impl FnOnce(&mut Self) -> Ast) -> (Ast, bool) {
+        let outer = std::mem::replace(&mut self.saw_yield, false);
+        self.func_depth += 1;
+        let body = parse(self);
+        self.func_depth -= 1;
+        (body, std::mem::replace(&mut self.saw_yield, outer))
     }
 
     fn loop_expr(&mut self) -> Ast {
@@ -369,7 +913,7 @@ impl Parser {
     }
 
     fn advance(&mut self) {
-        let (next, span) = self.lexer.next();
+        let (next, span) = self.lexer.next_token();
         std::mem::swap(&mut self.current, &mut self.next);
         std::mem::swap(&mut self.current_span, &mut self.next_span);
         self.next_span = span;
@@ -385,6 +929,7 @@ impl Parser {
 
     fn literal(&mut self) -> Ast {
         Ast::new(AstNode::Value(Box::new(match &self.current {
+            Token::Int(i) => Value::Int(*i),
             Token::Number(n) => Value::Number(*n),
             Token::String(s) => Value::String(Handle::new(s.clone())),
             Token::True => Value::Bool(true),
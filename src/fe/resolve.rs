@@ -0,0 +1,179 @@
+//! works out ahead of time where in the `LocalStack` each variable will be,
+//! so that looking one up doesn't mean searching every scope.
+//!
+//! scoping is dynamic, so this can only ever be a guess. each function is
+//! resolved on its own, and only variables from its own scopes get a slot.
+//! a name gets added to the innermost scope by the first assignment that
+//! doesn't find it, which is assumed to be the first one in the source.
+//! the executor checks the name at a slot before using it, and searches
+//! as usual when it isn't there. a variable found at its slot can't be
+//! shadowed by a nearer scope: an assignment in one would have updated the
+//! variable instead, and the names that are defined outright are known here
+
+use std::rc::Rc;
+
+use crate::value::Value;
+
+use super::ast::{AstNode, Slot, Statement};
+
+/// the names expected in a scope, in the order they'll be added
+#[derive(Default)]
+struct Scope {
+    names: Vec<String>
+}
+
+impl Scope {
+    fn with(names: impl IntoIterator<Item = String>) -> Self {
+        Self { names: names.into_iter().collect() }
+    }
+}
+
+/// the scopes of the function being resolved, innermost last
+struct Resolver {
+    scopes: Vec<Scope>
+}
+
+impl Resolver {
+    fn lookup(&self, name: &str) -> Option<Slot> {
+        self.scopes.iter().rev()
+            .enumerate()
+            .find_map(|(up, scope)| {
+                let index = scope.names.iter().rposition(|n| n == name)?;
+                Some(Slot::Local { up: up as u32, index: index as u32 })
+            })
+    }
+
+    /// the slot an assignment to `name` will most likely use,
+    /// adding it to the innermost scope if it's new
+    fn assign(&mut self, name: &str) -> Slot {
+        if let Some(slot) = self.lookup(name) {
+            return slot
+        }
+        let scope = self.scopes.last_mut().expect("resolving outside of any scope");
+        scope.names.push(name.to_string());
+        Slot::Local { up: 0, index: scope.names.len() as u32 - 1 }
+    }
+
+    /// resolves `node` inside a scope that starts out with `names`
+    fn scoped(&mut self, names: impl IntoIterator<Item = String>, node: &mut AstNode) {
+        self.scopes.push(Scope::with(names));
+        self.node(node);
+        self.scopes.pop();
+    }
+
+    fn node(&mut self, node: &mut AstNode) {
+        match node {
+            AstNode::Call(func, args, _) => {
+                self.node(func);
+                args.iter_mut().for_each(|a| self.node(a));
+            },
+            AstNode::Value(v) => {
+                // a function only has one owner until the program runs
+                if let Value::Function(func) = v.as_mut() {
+                    if let Some(func) = Rc::get_mut(func).and_then(|f| f.as_function_mut()) {
+                        // the parameters are bound in a scope of their own,
+                        // and each default can see the ones before it
+                        let mut resolver = Resolver { scopes: vec![Scope::default()] };
+                        for p in &mut func.params {
+                            if let Some(default) = &mut p.default {
+                                resolver.node(default);
+                            }
+                            resolver.scopes[0].names.push(p.name.clone());
+                        }
+                        if let Some(rest) = &func.rest {
+                            resolver.scopes[0].names.push(rest.clone());
+                        }
+                        if let Some(body) = Rc::get_mut(&mut func.ast) {
+                            resolver.node(body);
+                        }
+                    }
+                }
+            },
+            AstNode::Ident(name, slot, _) => *slot = self.lookup(name).unwrap_or(Slot::Unresolved),
+            AstNode::Assign(name, slot, value, _) => {
+                // the value is worked out before the name is looked for
+                self.node(value);
+                *slot = self.assign(name);
+            },
+            AstNode::MultiAssign(names, values) => {
+                values.iter_mut().for_each(|v| self.node(v));
+                for name in names.iter() {
+                    self.assign(name);
+                }
+            },
+            AstNode::Binary(_, a, b, _) => {
+                self.node(a);
+                self.node(b);
+            },
+            AstNode::Slice { target, start, end, .. } => {
+                self.node(target);
+                for bound in [start, end].into_iter().flatten() {
+                    self.node(bound);
+                }
+            },
+            AstNode::Compare(first, chain, _) => {
+                self.node(first);
+                chain.iter_mut().for_each(|(_, rhs)| self.node(rhs));
+            },
+            AstNode::Unary(_, value, _) | AstNode::Yield(value) | AstNode::Throw(value)
+                | AstNode::Pub { value, .. } => self.node(value),
+            AstNode::If { cond, then, or, .. } => {
+                self.node(cond);
+                self.node(then);
+                if let Some(or) = or {
+                    self.node(or);
+                }
+            },
+            AstNode::Block(stmts, scoped) => {
+                if *scoped {
+                    self.scopes.push(Scope::default());
+                }
+                for Statement::Expression(e, _) in stmts.iter_mut() {
+                    self.node(e);
+                }
+                if *scoped {
+                    self.scopes.pop();
+                }
+            },
+            AstNode::Loop { cond, run, .. } => {
+                if let Some(cond) = cond {
+                    self.node(cond);
+                }
+                self.node(run);
+            },
+            AstNode::For { binding, iterable, run } => {
+                self.node(iterable);
+                self.scoped([binding.clone()], run);
+            },
+            AstNode::Try { body, binding, handler } => {
+                self.node(body);
+                self.scoped(binding.clone(), handler);
+            },
+            AstNode::Assert { cond, message, .. } => {
+                self.node(cond);
+                if let Some(message) = message {
+                    self.node(message);
+                }
+            },
+            AstNode::Use { binding, .. } => {
+                self.assign(binding);
+            },
+            AstNode::Defer(expr) => {
+                // deferred expressions run just before their scope is popped
+                if let Some(expr) = Rc::get_mut(expr) {
+                    self.node(expr);
+                }
+            },
+            AstNode::Break(_) | AstNode::Error => {}
+        }
+    }
+}
+
+impl AstNode {
+    /// fills in the slot of every variable, including those in the bodies
+    /// of the functions in the tree. the tree is taken to run in a scope of
+    /// its own, as scripts and modules do
+    pub fn resolve(&mut self) {
+        Resolver { scopes: vec![Scope::default()] }.node(self);
+    }
+}
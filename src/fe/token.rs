@@ -1,4 +1,4 @@
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Token {
     // keywords
     Let,
@@ -11,11 +11,21 @@ pub enum Token {
     If,
     Else,
     Loop,
+    For,
+    Yield,
     Break,
     Continue,
+    Try,
+    Catch,
+    Throw,
+    Defer,
+    Assert,
+    In,
+    Is,
 
     // Literals
     String(String),
+    Int(i64),
     Number(f64),
     True,
     False,
@@ -30,6 +40,8 @@ pub enum Token {
     Lt,
     LtEq,
     Dot,
+    DotDot,
+    Ellipsis,
     Question,
     Colon,
     At,
@@ -51,6 +63,13 @@ pub enum Token {
     ModEq,
     And,
     Or,
+    Pipe,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
 
     // Misc
     Identifier(String),
@@ -61,8 +80,51 @@ pub enum Token {
     LBracket,
     RBracket,
 
+    /// a malformed token, carrying a message for the parser to report
+    Error(String),
+
     Start,
     Eof
 }
 
 
+
+/// the words that lex as something other than an identifier
+pub fn keywords() -> [(&'static str, Token); 24] {
+    use Token::*;
+    [
+        ("let", Let),
+        ("const", Const),
+        ("pub", Pub),
+        ("use", Use),
+        ("func", Func),
+        ("enum", Enum),
+        ("struct", Struct),
+        ("if", If),
+        ("else", Else),
+        ("loop", Loop),
+        ("for", For),
+        ("yield", Yield),
+        ("break", Break),
+        ("continue", Continue),
+        ("try", Try),
+        ("catch", Catch),
+        ("throw", Throw),
+        ("defer", Defer),
+        ("assert", Assert),
+        ("in", In),
+        ("is", Is),
+        ("true", True),
+        ("false", False),
+        ("nil", Nil)
+    ]
+}
+
+impl Token {
+    /// the keyword the token is written as, if it is one
+    pub fn keyword(&self) -> Option<&'static str> {
+        keywords().into_iter()
+            .find(|(_, token)| token == self)
+            .map(|(keyword, _)| keyword)
+    }
+}
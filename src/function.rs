@@ -1,17 +1,34 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, rc::Rc};
 
-use crate::{executor::{RuntimeError, VM}, fe::ast::Ast, value::Value};
+use crate::{executor::{RuntimeError, Tail, VM}, fe::{ast::{Ast, AstNode, Type}, diagnostic::Span}, generator::Generator, handle::Handle, value::Value};
 
 pub trait Callable: Debug {
     fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError>;
     fn display(&self) -> String;
+
+    /// the script function behind this callable, which
+    /// lets tail calls between script functions reuse the frame
+    fn as_function(&self) -> Option<&Function> {
+        None
+    }
+
+    fn as_function_mut(&mut self) -> Option<&mut Function> {
+        None
+    }
 }
 
+/// natives may call back into script functions through [`Callable::call`],
+/// which re-enters the VM on top of the native's caller. a native must not
+/// hold a borrow of a handle across such a call, since the callee is
+/// free to borrow (and change) the same value
 pub type NativeFunction = fn(vm: &mut VM, params: Vec<Value>) -> Result<Value, RuntimeError>;
 
 impl Callable for NativeFunction {
     fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
-        self(vm, vals)
+        let start = vm.enter_call(self, &vals);
+        let out = self(vm, vals);
+        vm.leave_call(self, start, out.as_ref().ok());
+        out
     }
     
     fn display(&self) -> String {
@@ -19,25 +36,133 @@ impl Callable for NativeFunction {
     }
 }
 
+#[derive(Debug)]
+pub struct Param {
+    pub name: String,
+    /// evaluated in the callee's scope when the argument is missing
+    pub default: Option<Ast>,
+    pub ty: Option<Type>
+}
+
 #[derive(Debug)]
 pub struct Function {
-    pub ast: Ast,
+    pub ast: Rc<AstNode>,
     pub name: Option<String>,
-    pub params: Vec<String>
+    pub params: Vec<Param>,
+    /// collects any arguments past `params` into an array
+    pub rest: Option<String>,
+    /// whether the body yields, making calls return a generator
+    pub generator: bool,
+    /// the annotated return type
+    pub ret: Option<Type>,
+    /// the code the function was parsed from, which
+    /// the spans of errors raised by its body point into
+    pub source: Rc<str>,
+    /// where the function is in `source`
+    pub span: Span
 }
 
-impl Callable for Function {
-    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
-        vm.locals.push();
-        if vals.len() != self.params.len() {
+impl Function {
+    /// the source of the function itself, which parses back into
+    /// the same function since it can't capture any variables
+    pub fn text(&self) -> String {
+        self.source.chars().skip(self.span.start).take(self.span.end - self.span.start).collect()
+    }
+
+    fn bind_params(&self, vm: &mut VM, vals: Vec<Value>) -> Result<(), RuntimeError> {
+        if self.rest.is_none() && vals.len() > self.params.len() {
             return Err(RuntimeError::IncorrectNumberOfArgs)
         }
-        for p in self.params.iter().enumerate() {
-            vm.locals.set(&p.1, vals[p.0].clone())
+        let mut vals = vals.into_iter();
+        for p in &self.params {
+            let val = match (vals.next(), &p.default) {
+                (Some(v), _) => v,
+                (None, Some(default)) => vm.execute(default)?,
+                (None, None) => return Err(RuntimeError::IncorrectNumberOfArgs)
+            };
+            vm.locals.define(&p.name, val);
+        }
+        if let Some(rest) = &self.rest {
+            vm.locals.define(rest, Value::Array(Handle::new(vals.collect())));
         }
-        let val = vm.execute(&*self.ast);
-        vm.locals.pop();
-        val
+        Ok(())
+    }
+
+    /// runs the body, leaving any call in tail position for `call` to make
+    fn call_tail(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Tail, RuntimeError> {
+        let start = vm.enter_call(self, &vals);
+        let out = match self.generator {
+            true => self.start_generator(vm, vals).map(Tail::Value),
+            false => self.run_body(vm, vals)
+        };
+        let returned = match &out {
+            Ok(Tail::Value(v)) => Some(v),
+            _ => None
+        };
+        vm.leave_call(self, start, returned);
+        out
+    }
+
+    fn run_body(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Tail, RuntimeError> {
+        vm.push_scope();
+        let val = self.bind_params(vm, vals)
+            .and_then(|_| vm.execute_tail(&self.ast))
+            .and_then(|t| vm.settle_tail(t));
+        let popped = vm.pop_scope();
+        val.and_then(|v| popped.map(|_| v))
+    }
+
+    /// binds the arguments into a frame for a generator
+    /// that runs the body once it is first iterated
+    fn start_generator(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        let depth = vm.locals.depth();
+        vm.push_scope();
+        if let Err(e) = self.bind_params(vm, vals) {
+            vm.pop_scope()?;
+            return Err(e)
+        }
+        let frame = vm.save_frame(depth);
+        let generator = Generator::new(self.name.clone(), self.ast.clone(), self.source.clone(), frame);
+        Ok(Value::Iterator(Rc::new(generator)))
+    }
+}
+
+impl Callable for Function {
+    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        let mut tail = self.call_tail(vm, vals).map_err(|e| e.with_source(&self.source))?;
+        // each tail call takes the place of the last one in the call stack
+        let depth = vm.calls.len();
+        // the code the pending tail call was made from
+        let mut source = self.source.clone();
+        loop {
+            tail = match tail {
+                Tail::Value(v) => {
+                    vm.calls.truncate(depth);
+                    return Ok(v)
+                },
+                Tail::Call(func, args, span) => {
+                    vm.calls.truncate(depth);
+                    vm.push_call(func.clone(), span);
+                    let (out, callee) = match func.as_function() {
+                        Some(f) => (f.call_tail(vm, args).map_err(|e| e.with_source(&f.source)), Some(f.source.clone())),
+                        None => (func.call(vm, args).map(Tail::Value), None)
+                    };
+                    let out = out.map_err(|e| e.at(span).with_source(&source))?;
+                    if let Some(callee) = callee {
+                        source = callee;
+                    }
+                    out
+                }
+            };
+        }
+    }
+
+    fn as_function(&self) -> Option<&Function> {
+        Some(self)
+    }
+
+    fn as_function_mut(&mut self) -> Option<&mut Function> {
+        Some(self)
     }
 
     fn display(&self) -> String {
@@ -0,0 +1,109 @@
+//! Generators are resumed by re-entering their body along the path
+//! recorded in `Resume` points when they last yielded.
+//!
+//! the `defer`s a body has run are kept with its frame while it's
+//! suspended, and run as its scopes end. a generator that's dropped before
+//! it finishes, like one a `for` loop breaks out of, never runs them, since
+//! there's no vm to run them on by then.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{executor::{Frame, Resume, RuntimeError, VM}, fe::ast::AstNode, iterator::Iterate, value::Value};
+
+#[derive(Debug)]
+enum State {
+    /// created, but the body hasn't started
+    Fresh(Frame),
+    Suspended(Frame, Vec<Resume>),
+    Running,
+    Done
+}
+
+#[derive(Debug)]
+pub struct Generator {
+    name: Option<String>,
+    body: Rc<AstNode>,
+    /// the code the body was parsed from
+    source: Rc<str>,
+    state: RefCell<State>,
+    /// what the body ended with, once it's done
+    returned: RefCell<Value>
+}
+
+impl Generator {
+    /// `frame` holds the scope with the bound arguments
+    pub fn new(name: Option<String>, body: Rc<AstNode>, source: Rc<str>, frame: Frame) -> Self {
+        Self {
+            name,
+            body,
+            source,
+            state: RefCell::new(State::Fresh(frame)),
+            returned: RefCell::new(Value::Nil)
+        }
+    }
+
+    /// the value the body finished with, or nil until it has
+    pub fn returned(&self) -> Value {
+        self.returned.borrow().clone()
+    }
+}
+
+impl Iterate for Generator {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        let state = std::mem::replace(&mut *self.state.borrow_mut(), State::Running);
+        let depth = vm.locals.depth();
+        match state {
+            State::Fresh(frame) => vm.restore_frame(frame),
+            // yielding from outside of a block leaves nothing to resume
+            State::Suspended(frame, resume) if resume.is_empty() => {
+                *self.state.borrow_mut() = State::Done;
+                vm.restore_frame(frame);
+                vm.pop_scope()?;
+                return Ok(None)
+            },
+            State::Suspended(frame, resume) => {
+                vm.restore_frame(frame);
+                vm.resume = resume;
+            },
+            State::Running => return Err(RuntimeError::GeneratorRunning),
+            State::Done => {
+                *self.state.borrow_mut() = State::Done;
+                return Ok(None)
+            }
+        }
+
+        match vm.execute(&self.body) {
+            Err(RuntimeError::Yield(v)) => {
+                let frame = vm.save_frame(depth);
+                let resume = std::mem::take(&mut vm.resume);
+                *self.state.borrow_mut() = State::Suspended(frame, resume);
+                Ok(Some(v))
+            },
+            Ok(v) => {
+                *self.state.borrow_mut() = State::Done;
+                *self.returned.borrow_mut() = v;
+                vm.pop_scope()?;
+                Ok(None)
+            },
+            Err(e) => {
+                *self.state.borrow_mut() = State::Done;
+                vm.resume.clear();
+                while vm.locals.depth() > depth {
+                    vm.pop_scope()?;
+                }
+                Err(e.with_source(&self.source))
+            }
+        }
+    }
+
+    fn display(&self) -> String {
+        match &self.name {
+            Some(name) => format!("< generator {} >", name),
+            None => "< anonymous generator >".to_owned()
+        }
+    }
+
+    fn as_generator(&self) -> Option<&Generator> {
+        Some(self)
+    }
+}
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, fmt::Debug, hash::Hash, ops::Deref, rc::Rc};
+use std::{cell::RefCell, fmt::Debug, hash::Hash, ops::Deref, rc::{Rc, Weak}};
 
 #[derive(Clone, PartialEq)]
 pub struct Handle<T: ?Sized>(Rc<RefCell<T>>);
@@ -28,4 +28,32 @@ impl<T> Handle<T> {
     pub fn new(x: T) -> Self {
         Self(Rc::new(RefCell::new(x)))
     }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// where the value is, which tells it apart from every other value
+    pub fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as *const () as usize
+    }
+
+    /// how many handles there are to the value, this one and weak ones included
+    pub fn refs(&self) -> usize {
+        Rc::strong_count(&self.0) + Rc::weak_count(&self.0)
+    }
+
+    pub fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle(Rc::downgrade(&self.0))
+    }
+}
+
+/// a handle that doesn't keep the value alive
+pub struct WeakHandle<T>(Weak<RefCell<T>>);
+
+impl<T> WeakHandle<T> {
+    /// the value, unless every handle to it is gone
+    pub fn upgrade(&self) -> Option<Handle<T>> {
+        self.0.upgrade().map(Handle)
+    }
 }
@@ -0,0 +1,56 @@
+//! Hooks a host can set on a VM to watch the scripts it runs, for building
+//! debuggers, profilers or audit logs on top of the executor. each is called
+//! as it happens: `on_call` with the name of the function being called and
+//! its arguments, `on_return` with the name and what it returned, and
+//! `on_statement` with the span of each statement about to run. `on_error`
+//! is called once for each runtime error, where it happened, before any
+//! `try` gets to catch it
+
+use std::collections::HashMap;
+
+use crate::{executor::RuntimeError, fe::diagnostic::Span, function::Callable, value::Value};
+
+pub type CallHook = Box<dyn FnMut(&str, &[Value])>;
+pub type ReturnHook = Box<dyn FnMut(&str, &Value)>;
+pub type StatementHook = Box<dyn FnMut(Span)>;
+pub type ErrorHook = Box<dyn FnMut(&RuntimeError)>;
+
+#[derive(Default)]
+pub struct Hooks {
+    pub on_call: Option<CallHook>,
+    pub on_return: Option<ReturnHook>,
+    pub on_statement: Option<StatementHook>,
+    pub on_error: Option<ErrorHook>
+}
+
+impl Hooks {
+    pub fn any(&self) -> bool {
+        self.on_call.is_some() || self.on_return.is_some() || self.on_statement.is_some() || self.on_error.is_some()
+    }
+}
+
+/// a function is told apart by where it is, which is the same for every
+/// `Rc` pointing to it and for the `self` it's called with
+pub fn address(func: &dyn Callable) -> usize {
+    func as *const dyn Callable as *const () as usize
+}
+
+/// the natives in `globals` by address, named by the global or module they
+/// were found in, since a native function can't name itself
+pub fn native_names(globals: &HashMap<String, Value>) -> HashMap<usize, String> {
+    let mut natives = HashMap::new();
+    for (name, val) in globals {
+        match val {
+            Value::Function(f) => {
+                natives.insert(address(&**f), name.clone());
+            },
+            Value::Map(m) => for (key, val) in m.borrow().iter() {
+                if let Value::Function(f) = val {
+                    natives.insert(address(&**f), format!("{}.{}", name, key));
+                }
+            },
+            _ => {}
+        }
+    }
+    natives
+}
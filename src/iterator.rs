@@ -0,0 +1,77 @@
+use std::{cell::Cell, fmt::Debug, rc::Rc};
+
+use crate::{corelib::Coroutine, executor::{RuntimeError, VM}, function::Callable, generator::Generator, handle::Handle, value::Value};
+
+/// something a `for` loop can drive
+pub trait Iterate: Debug {
+    /// the next value, or `None` once exhausted
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError>;
+    fn display(&self) -> String;
+
+    /// the coroutine behind this iterator, which
+    /// the `coroutine` module's functions act on
+    fn as_coroutine(&self) -> Option<&Coroutine> {
+        None
+    }
+
+    /// the generator behind this iterator, so a
+    /// coroutine can get the value its body ended with
+    fn as_generator(&self) -> Option<&Generator> {
+        None
+    }
+}
+
+/// walks an array by index, so changes made
+/// to it while iterating are seen
+#[derive(Debug)]
+pub struct ArrayIter {
+    array: Handle<Vec<Value>>,
+    index: Cell<usize>
+}
+
+impl ArrayIter {
+    pub fn new(array: Handle<Vec<Value>>) -> Self {
+        Self { array, index: Cell::new(0) }
+    }
+}
+
+impl Iterate for ArrayIter {
+    fn next(&self, _vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        let i = self.index.get();
+        let next = self.array.borrow().get(i).cloned();
+        if next.is_some() {
+            self.index.set(i + 1);
+        }
+        Ok(next)
+    }
+
+    fn display(&self) -> String {
+        "< array iterator >".to_owned()
+    }
+}
+
+/// the iterator protocol for values written in tram: a map whose `next`
+/// is a function, called for each value until it returns nil
+#[derive(Debug)]
+pub struct ProtocolIter {
+    next: Rc<dyn Callable>
+}
+
+impl ProtocolIter {
+    pub fn new(next: Rc<dyn Callable>) -> Self {
+        Self { next }
+    }
+}
+
+impl Iterate for ProtocolIter {
+    fn next(&self, vm: &mut VM) -> Result<Option<Value>, RuntimeError> {
+        match self.next.call(vm, vec![])? {
+            Value::Nil => Ok(None),
+            v => Ok(Some(v))
+        }
+    }
+
+    fn display(&self) -> String {
+        "< iterator >".to_owned()
+    }
+}
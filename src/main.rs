@@ -1,27 +1,201 @@
-use crate::{value::Value, handle::Handle};
+use std::{fs, process};
+
+use crate::{fe::{ast::Ast, diagnostic::{self, Diagnostic, Level}}, value::Value, handle::Handle};
 
 pub mod fe;
+pub mod bytecode;
 pub mod executor;
 pub mod function;
+pub mod generator;
+pub mod iterator;
+pub mod module;
 pub mod corelib;
 pub mod repl;
 pub mod handle;
+pub mod hooks;
+pub mod memory;
+pub mod profile;
+pub mod userdata;
 pub mod value;
 
+#[global_allocator]
+static ALLOCATOR: memory::Counting = memory::Counting;
+
 fn main() {
     eprintln!("🚋 tram lang");
+
+    let mut args = std::env::args().skip(1);
+    let mut first = args.next();
+    // errors in `check` are reported the same way, so this can come before it
+    if first.as_deref() == Some("--diagnostics=json") {
+        diagnostic::set_json(true);
+        first = args.next();
+    }
+    if first.as_deref() == Some("check") {
+        let Some(path) = args.next() else {
+            eprintln!("usage: tram check <file>");
+            process::exit(2);
+        };
+        process::exit(if check(path.trim()) { 0 } else { 1 });
+    }
+
     let mut vm = executor::VM::new();
     vm.register_stdlib();
+    // set to print the script's tree instead of running it, as JSON if true
+    let mut dump = None;
+    // flags come before the script's path
+    loop {
+        match first.as_deref() {
+            // runs scripts without compiling them, mostly for comparing the two
+            Some("--tree-walk") => vm.tree_walk = true,
+            Some("-O" | "--optimize") => vm.optimize = true,
+            // dividing by zero fails instead of giving inf or nan
+            Some("--strict-math") => vm.strict_math = true,
+            // prints everything the script does as it runs it
+            Some("--trace") => vm.set_trace(true),
+            // times every call, printing a report once the script or repl is done
+            Some("--profile") => vm.set_profiling(true),
+            // stops scripts after this many steps, which also applies to each repl input
+            Some("--fuel") => match args.next().and_then(|n| n.parse().ok()) {
+                Some(fuel) => vm.set_fuel(Some(fuel)),
+                None => {
+                    eprintln!("usage: tram --fuel <steps> [file]");
+                    process::exit(2);
+                }
+            },
+            Some("--max-memory") => match args.next().and_then(|n| n.parse().ok()) {
+                Some(bytes) => vm.set_memory_limit(Some(bytes)),
+                None => {
+                    eprintln!("usage: tram --max-memory <bytes> [file]");
+                    process::exit(2);
+                }
+            },
+            Some("--ast") => dump = Some(false),
+            Some("--ast=json") => dump = Some(true),
+            // reports errors as JSON, one to a line, for editors and ci
+            Some("--diagnostics=json") => diagnostic::set_json(true),
+            _ => break
+        }
+        first = args.next();
+    }
+    if let Some(json) = dump {
+        let Some(path) = first else {
+            eprintln!("usage: tram --ast[=json] <file>");
+            process::exit(2);
+        };
+        process::exit(if print_ast(path.trim(), json) { 0 } else { 1 });
+    }
+    // like PATH, TRAM_PATH holds directories separated by `:` (`;` on windows)
+    if let Some(dirs) = std::env::var_os("TRAM_PATH") {
+        for dir in std::env::split_paths(&dirs) {
+            vm.add_search_dir(dir);
+        }
+    }
+
+    if let Some(a) = first {
+        // everything after the script's path is passed on to it
+        vm.script_args = args.collect();
+        let script_args = vm.script_args.iter().map(|a| a.as_str().into()).collect();
+        vm.set_global("args", Value::Array(Handle::new(script_args)));
 
-    if let Some(a) = std::env::args().nth(1) {
         let val = Value::String(Handle::new(a.trim().to_owned()));
-        match corelib::run(&mut vm, vec![val]) {
+        // whatever the script left to the event loop runs once it's done
+        match corelib::run(&mut vm, vec![val]).and_then(|_| corelib::run_events(&mut vm)) {
             Ok(_) => {},
-            Err(e) => eprintln!("VM Error: {:?}", e)
+            Err(e) => vm.emit_error(&e, None)
         }
 
+        report_profile(&vm);
+        // dropping the VM prints the summary of any test cases
+        let failed = vm.tests.failed();
+        drop(vm);
+        if failed {
+            process::exit(1);
+        }
         return;
     }
 
     repl::run(&mut vm);
+    report_profile(&vm);
+}
+
+fn report_profile(vm: &executor::VM) {
+    if let Some(profile) = vm.profile() {
+        eprint!("== Profile\n{}", profile.report());
+    }
+}
+
+/// parses the file and prints the tree it parses into without running it
+fn print_ast(path: &str, json: bool) -> bool {
+    let source = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("failed to load file {}: {}", path, e);
+            return false
+        }
+    };
+    let prog: Ast = match source.parse() {
+        Ok(p) => p,
+        Err(errors) => {
+            for e in errors {
+                e.log(Some(path), Some(&source));
+            }
+            return false
+        }
+    };
+    if !json {
+        print!("{}", fe::dump::tree(&prog));
+        return true
+    }
+    match fe::dump::json(&prog) {
+        Ok(json) => {
+            println!("{}", json);
+            true
+        },
+        Err(e) => {
+            let diagnostic = Diagnostic {
+                level: Level::Error,
+                message: e.to_string(),
+                span: None,
+                notes: Vec::new(),
+                help: Vec::new(),
+                file: Some(path.to_owned())
+            };
+            diagnostic.emit(None);
+            false
+        }
+    }
+}
+
+/// parses the file and checks its type annotations without running it
+fn check(path: &str) -> bool {
+    let source = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("failed to load file {}: {}", path, e);
+            return false
+        }
+    };
+    let prog: Ast = match source.parse() {
+        Ok(p) => p,
+        Err(errors) => {
+            for e in errors {
+                e.log(Some(path), Some(&source));
+            }
+            return false
+        }
+    };
+    let errors = fe::check::check(&prog);
+    for e in &errors {
+        e.log(Some(path), Some(&source));
+    }
+    // warnings are shown, but don't fail the check
+    let warnings = fe::lint::lint(&prog);
+    for w in &warnings {
+        w.log(Some(path), Some(&source));
+    }
+    if errors.is_empty() && warnings.is_empty() {
+        eprintln!("--> no problems found in {}", path);
+    }
+    errors.is_empty()
 }
@@ -0,0 +1,54 @@
+//! Keeps count of the memory the process is using, so a VM can stop a
+//! script that allocates too much before it takes the host down with it.
+//! every allocation is counted, not just the values a script makes, which
+//! is as close as the count needs to be. it's counted for the whole process
+//! rather than each thread, so a script can't get more by spawning threads
+
+use std::{alloc::{GlobalAlloc, Layout, System}, sync::atomic::{AtomicIsize, Ordering}};
+
+/// bytes allocated and not yet freed, by any thread
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+fn count(bytes: isize) {
+    LIVE.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// the system allocator, counting what the process has allocated.
+/// installed in `main`, without which `live` is always zero
+pub struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            count(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            count(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        count(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            count(new_size as isize - layout.size() as isize);
+        }
+        new
+    }
+}
+
+/// the bytes the process is using
+pub fn live() -> usize {
+    LIVE.load(Ordering::Relaxed).max(0) as usize
+}
@@ -0,0 +1,165 @@
+//! Loads the files imported by `use`. Each module runs once per VM,
+//! in its own scope, and is bound in the importer as a map of the
+//! variables it marks `pub`.
+
+use std::{cell::{Cell, RefCell}, fmt::Debug, fs, path::{Path, PathBuf}, rc::Rc};
+
+use crate::{executor::{RuntimeError, VM}, fe::ast::{Ast, AstNode, Statement}, function::Callable, handle::Handle, value::Value};
+
+/// the extensions tried, in order, for `use name`
+const EXTENSIONS: [&str; 2] = ["tr", "tram"];
+
+/// a module's top-level variables, which outlive the run of the module
+#[derive(Default)]
+struct Env {
+    vars: RefCell<Vec<(String, Value)>>,
+    /// whether a call into the module is running, in which case
+    /// the variables are already on the stack
+    entered: Cell<bool>
+}
+
+/// a function exported from a module. scoping is dynamic, so without
+/// this a module's functions couldn't see the module's other variables
+/// once they were called from the importer
+pub struct ModuleFunction {
+    func: Rc<dyn Callable>,
+    env: Rc<Env>
+}
+
+impl Debug for ModuleFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the environment holds this function, so printing it would never end
+        f.debug_tuple("ModuleFunction").field(&self.func).finish()
+    }
+}
+
+impl Callable for ModuleFunction {
+    fn call(&self, vm: &mut VM, vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        if self.env.entered.get() {
+            return self.func.call(vm, vals)
+        }
+        vm.push_scope();
+        for (name, val) in self.env.vars.borrow().iter() {
+            vm.locals.define(name, val.clone());
+        }
+        self.env.entered.set(true);
+        let out = self.func.call(vm, vals);
+        self.env.entered.set(false);
+        // keep any changes the call made to the module's variables
+        *self.env.vars.borrow_mut() = vm.locals.innermost().to_vec();
+        vm.pop_scope()?;
+        out
+    }
+
+    fn display(&self) -> String {
+        self.func.display()
+    }
+}
+
+/// the file that `use path` refers to. it's looked for relative to the
+/// importing file first, then in each directory of the search path
+fn resolve(vm: &VM, path: &str) -> Option<PathBuf> {
+    let dir = match vm.files.last().and_then(|f| f.parent()) {
+        Some(dir) => dir.to_path_buf(),
+        None => PathBuf::from(".")
+    };
+    let with_extensions = |base: PathBuf| {
+        if Path::new(path).extension().is_some() {
+            vec![base]
+        } else {
+            EXTENSIONS.iter().map(|ext| base.with_extension(ext)).collect()
+        }
+    };
+    std::iter::once(&dir)
+        .chain(vm.search_path())
+        .flat_map(|dir| with_extensions(dir.join(path)))
+        .find(|p| p.is_file())
+        .and_then(|p| p.canonicalize().ok())
+}
+
+/// the names marked `pub` at the top level of a module
+fn exports(prog: &AstNode) -> Vec<&str> {
+    let AstNode::Block(stmts, _) = prog else {
+        return Vec::new()
+    };
+    stmts.iter()
+        .filter_map(|Statement::Expression(e, _)| match e.as_ref() {
+            AstNode::Pub { name, .. } => Some(name.as_str()),
+            _ => None
+        })
+        .collect()
+}
+
+/// runs the module at `path`, or fetches it from the cache if it already ran
+pub fn import(vm: &mut VM, path: &str) -> Result<Value, RuntimeError> {
+    if !vm.options().allow_fs {
+        return Err(RuntimeError::NotAllowed(format!("importing `{}`", path)))
+    }
+    let Some(file) = resolve(vm, path) else {
+        return Err(RuntimeError::ModuleNotFound(path.to_string()))
+    };
+    if let Some(module) = vm.modules.get(&file) {
+        return Ok(module.clone())
+    }
+    // a module that's still running hasn't been cached yet,
+    // so importing it again would recurse forever
+    let running: Vec<PathBuf> = vm.files.iter()
+        .map(|f| f.canonicalize().unwrap_or_else(|_| f.to_path_buf()))
+        .collect();
+    if let Some(start) = running.iter().position(|f| *f == file) {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let chain: Vec<String> = running[start..].iter()
+            .chain(std::iter::once(&file))
+            .map(|f| f.strip_prefix(&cwd).unwrap_or(f).display().to_string())
+            .collect();
+        return Err(RuntimeError::ImportCycle(chain.join(" → ")))
+    }
+
+    let source: Rc<str> = fs::read_to_string(&file)
+        .map_err(|_| RuntimeError::ModuleNotFound(path.to_string()))?
+        .into();
+    vm.add_source(&file, &source);
+    let mut prog: Ast = match source.parse() {
+        Ok(p) => p,
+        Err(errors) => {
+            for e in errors {
+                e.log(Some(&file.display().to_string()), Some(&source));
+            }
+            return Err(RuntimeError::ModuleParseFailed(path.to_string()))
+        }
+    };
+    vm.prepare(&mut prog);
+
+    // the module can't see the importer's variables, only the globals
+    let frame = vm.save_frame(0);
+    vm.files.push(file.as_path().into());
+    vm.push_scope();
+    let out = vm.execute(&prog);
+    let vars = vm.locals.innermost().to_vec();
+    let popped = vm.pop_scope();
+    vm.files.pop();
+    vm.restore_frame(frame);
+    out.map_err(|e| e.with_source(&source))?;
+    popped?;
+
+    let env = Rc::new(Env::default());
+    let vars: Vec<(String, Value)> = vars.into_iter()
+        .map(|(name, val)| match val {
+            Value::Function(func) => {
+                let func = ModuleFunction { func, env: env.clone() };
+                (name, Value::Function(Rc::new(func)))
+            },
+            val => (name, val)
+        })
+        .collect();
+    *env.vars.borrow_mut() = vars.clone();
+
+    // everything else stays private to the module's functions
+    let public = exports(&prog);
+    let module = Value::Map(Handle::new(vars.into_iter()
+        .filter(|(name, _)| public.contains(&name.as_str()))
+        .map(|(name, val)| (name.into(), val))
+        .collect()));
+    vm.modules.insert(file, module.clone());
+    Ok(module)
+}
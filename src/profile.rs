@@ -0,0 +1,95 @@
+//! Timing the calls scripts make, for `--profile`. each function's calls
+//! are counted, and the time spent in them is added up, including the time
+//! spent in whatever they call. a call to a function that's already underway
+//! further up the stack isn't timed again, so recursion isn't counted twice
+
+use std::{collections::HashMap, hash::{Hash, Hasher}, rc::Rc, time::{Duration, Instant}};
+
+use crate::{fe::diagnostic::Span, function::Callable, hooks::address};
+
+/// what a function's calls are counted under. a closure can be freed and
+/// another made where it was, so script functions go by where they're
+/// defined, holding on to the source so its address stays theirs. natives
+/// are all made when the vm is, so their address is enough
+#[derive(Debug, Clone)]
+pub enum Key {
+    Function(Rc<str>, Span),
+    Native(usize)
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Key::Function(a, x), Key::Function(b, y)) => Rc::ptr_eq(a, b) && (x.start, x.end) == (y.start, y.end),
+            (Key::Native(a), Key::Native(b)) => a == b,
+            _ => false
+        }
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Key::Function(source, span) => {
+                std::ptr::hash(&**source, state);
+                (span.start, span.end).hash(state);
+            },
+            Key::Native(addr) => addr.hash(state)
+        }
+    }
+}
+
+struct Entry {
+    name: String,
+    calls: u64,
+    total: Duration,
+    /// how many calls to the function are underway
+    active: u32
+}
+
+/// the key `func`'s calls are counted under
+pub fn key(func: &dyn Callable) -> Key {
+    match func.as_function() {
+        Some(f) => Key::Function(f.source.clone(), f.span),
+        None => Key::Native(address(func))
+    }
+}
+
+#[derive(Default)]
+pub struct Profile {
+    entries: HashMap<Key, Entry>
+}
+
+impl Profile {
+    /// notes a call to the function at `key` starting, returning when it did
+    pub fn enter(&mut self, key: Key, name: impl FnOnce() -> String) -> Instant {
+        let entry = self.entries.entry(key)
+            .or_insert_with(|| Entry { name: name(), calls: 0, total: Duration::ZERO, active: 0 });
+        entry.calls += 1;
+        entry.active += 1;
+        Instant::now()
+    }
+
+    /// notes the call to the function at `key` that started at `start` having returned
+    pub fn leave(&mut self, key: &Key, start: Instant) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.active -= 1;
+            if entry.active == 0 {
+                entry.total += start.elapsed();
+            }
+        }
+    }
+
+    /// a table of every function called, the slowest first
+    pub fn report(&self) -> String {
+        let mut entries: Vec<_> = self.entries.values().collect();
+        entries.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+        let mut out = format!("{:>12}  {:>10}  function\n", "total ms", "calls");
+        for e in entries {
+            out.push_str(&format!("{:>12.3}  {:>10}  {}\n", e.total.as_secs_f64() * 1000.0, e.calls, e.name));
+        }
+        out
+    }
+}
@@ -1,30 +1,199 @@
-use std::io::Write;
+use std::{borrow::Cow, collections::{HashMap, HashSet}, env, path::PathBuf};
 
-use crate::{executor::VM, fe::ast::Ast};
+use rustyline::{completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter,
+    history::DefaultHistory, validate::Validator, Context, Editor};
+
+use crate::{corelib, executor::VM, fe::{ast::Ast, dump, lexer::tokenize, lint::{lint, Lint}, token::{keywords, Token}}, value::Value};
+
+/// what tab completes to, taken from the VM before each prompt,
+/// and how the line being typed is colored
+#[derive(Default)]
+struct Helper {
+    /// whether to color the line, which NO_COLOR turns off
+    color: bool,
+    /// every variable that can be seen, and the keywords
+    names: Vec<String>,
+    /// the keys of each map that can be seen, for completing
+    /// after `name.`, which is how modules are used
+    members: HashMap<String, Vec<String>>
+}
+
+impl Helper {
+    fn refresh(&mut self, vm: &VM) {
+        self.names.clear();
+        self.members.clear();
+        let mut seen = HashSet::new();
+        for (name, val) in vm.locals.visible() {
+            // a shadowed variable isn't what `name.` would see
+            if !seen.insert(name) {
+                continue
+            }
+            self.names.push(name.to_owned());
+            if let Value::Map(m) = val {
+                let keys = m.borrow().keys()
+                    .filter_map(|k| match k {
+                        Value::String(s) => Some(s.borrow().clone()),
+                        _ => None
+                    })
+                    .collect();
+                self.members.insert(name.to_owned(), keys);
+            }
+        }
+        self.names.extend(keywords().into_iter().map(|(keyword, _)| keyword.to_owned()));
+        self.names.sort();
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Completer for Helper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before.trim_end_matches(is_ident_char).len();
+        let word = &before[start..];
+        let candidates = match before[..start].strip_suffix('.') {
+            Some(target) => {
+                let target = &target[target.trim_end_matches(is_ident_char).len()..];
+                let mut keys: Vec<_> = self.members.get(target).into_iter().flatten().collect();
+                keys.sort();
+                keys
+            },
+            None if word.is_empty() => vec![],
+            None => self.names.iter().collect()
+        };
+        Ok((start, candidates.into_iter().filter(|c| c.starts_with(word)).cloned().collect()))
+    }
+}
+
+impl Hinter for Helper {
+    type Hint = String;
+}
+
+/// the color each kind of token is shown in, if it has one
+fn token_color(token: &Token) -> Option<&'static str> {
+    match token {
+        Token::String(_) => Some("\x1b[32m"),
+        Token::Int(_) | Token::Number(_) => Some("\x1b[33m"),
+        Token::Error(_) => Some("\x1b[31m"),
+        token if token.keyword().is_some() => Some("\x1b[35m"),
+        _ => None
+    }
+}
+
+impl Highlighter for Helper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !self.color {
+            return Cow::Borrowed(line)
+        }
+        // spans count chars, not bytes
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = String::new();
+        let mut at = 0;
+        for (token, span) in tokenize(line) {
+            let Some(color) = token_color(&token) else { continue };
+            let end = span.end.min(chars.len());
+            if span.start < at || span.start >= end {
+                continue
+            }
+            out.extend(&chars[at..span.start]);
+            out.push_str(color);
+            out.extend(&chars[span.start..end]);
+            out.push_str("\x1b[0m");
+            at = end;
+        }
+        out.extend(&chars[at..]);
+        Cow::Owned(out)
+    }
+
+    // any key can change how the rest of the line lexes, like a `"`
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        self.color
+    }
+}
+
+impl Validator for Helper {}
+
+impl rustyline::Helper for Helper {}
+
+/// where inputs are kept between sessions: TRAM_HISTORY, or `.tram_history`
+/// in the home directory. an empty TRAM_HISTORY keeps them for the session only
+fn history_path() -> Option<PathBuf> {
+    match env::var_os("TRAM_HISTORY") {
+        Some(path) if path.is_empty() => None,
+        Some(path) => Some(path.into()),
+        None => env::home_dir().map(|home| home.join(".tram_history"))
+    }
+}
 
 pub fn run(vm: &mut VM) {
+    // a runaway input shouldn't leave the ones after it without fuel
+    let fuel = vm.fuel();
+    let mut editor: Editor<Helper, DefaultHistory> = Editor::new().expect("failed to set up the line editor");
+    editor.set_helper(Some(Helper { color: env::var_os("NO_COLOR").is_none(), ..Helper::default() }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // there's nothing to load the first time
+        let _ = editor.load_history(path);
+    }
     loop {
-        print!("> ");
-        std::io::stdout().flush().expect("failed to flush stdout");
-        let mut buffer = String::new();
-        std::io::stdin().read_line(&mut buffer)
-            .expect("failed to read from stdin!");
-        if buffer == "" {
-            // no return must mean EOF
-            break
+        vm.set_fuel(fuel);
+        if let Some(helper) = editor.helper_mut() {
+            helper.refresh(vm);
         }
+        let mut buffer = match editor.readline("> ") {
+            Ok(line) => line,
+            // ctrl-c throws away the line, like in a shell
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => panic!("failed to read from stdin: {}", e)
+        };
         if buffer.trim() == "quit" { break }
-        let prog: Ast = match buffer.parse() {
+        if !buffer.trim().is_empty() {
+            // a full history only means the oldest entry is dropped
+            let _ = editor.add_history_entry(buffer.as_str());
+            // saved as it's entered, since a script can exit the process.
+            // failing to only loses it for later sessions
+            if let Some(path) = &history {
+                let _ = editor.append_history(path);
+            }
+        }
+        // `:ast code` prints the tree the code parses into instead of running it
+        let dump = buffer.trim_start().starts_with(":ast");
+        if dump {
+            buffer = buffer.trim_start()[":ast".len()..].to_owned();
+        }
+        let mut prog: Ast = match buffer.parse() {
             Ok(p) => p,
             Err(e) => {
                 for err in e {
-                    err.log(Some(&buffer));
+                    err.log(None, Some(&buffer));
                 }
                 continue
             }
         };
-        match vm.execute(&prog) {
-            Err(e) => println!("== Runtime error from VM: {:?}", e),
+        if dump {
+            print!("{}", dump::tree(&prog));
+            continue
+        }
+        // a variable is read by a later input, if at all
+        for warning in lint(&prog).iter().filter(|w| w.lint != Lint::UnusedVariable) {
+            warning.log(None, Some(&buffer));
+        }
+        vm.prepare(&mut prog);
+        let out = vm.execute(&prog);
+        // the repl's top level scope ends with each input
+        let out = out.and_then(|v| vm.run_deferred().map(|_| v));
+        // and whatever it left to the event loop runs before the next prompt
+        let out = out.and_then(|v| corelib::run_events(vm).map(|_| v));
+        match out.map_err(|e| e.with_source(&buffer.as_str().into())) {
+            Err(e) => {
+                vm.emit_error(&e, Some(&buffer));
+                vm.calls.clear();
+            },
             Ok(v) => {
                 println!("\x1b[36m{:?}\x1b[0m", v)
             }
@@ -0,0 +1,83 @@
+//! Host objects, which let embedders and native modules hand scripts things
+//! like sockets that aren't made of tram values. scripts can't see inside
+//! one, only pass it around and call its methods, natives that are given the
+//! object first. `obj.name(args...)` calls a method, the same as calling it
+//! with `obj` as the first argument. the object is freed, and whatever it
+//! holds dropped, once the last value referring to it is
+
+use std::{any::Any, collections::HashMap, fmt::Debug, rc::Rc};
+
+use crate::{executor::{RuntimeError, VM}, function::{Callable, NativeFunction}, value::Value};
+
+/// the methods objects of one type share
+pub type Methods = HashMap<&'static str, Rc<dyn Callable>>;
+
+/// a method table of natives
+pub fn methods(natives: &[(&'static str, NativeFunction)]) -> Rc<Methods> {
+    Rc::new(natives.iter()
+        .map(|(name, func)| (*name, Rc::new(*func) as Rc<dyn Callable>))
+        .collect())
+}
+
+pub struct UserData {
+    type_name: &'static str,
+    methods: Rc<Methods>,
+    data: Box<dyn Any>
+}
+
+impl UserData {
+    /// an object of the type `type_name` holding `data`, which `.into()` makes
+    /// a value. natives that change it need it to have a `RefCell` or similar
+    /// inside, since scripts share it
+    pub fn new(type_name: &'static str, methods: Rc<Methods>, data: impl Any) -> Self {
+        Self { type_name, methods, data: Box::new(data) }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn downcast<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+
+    pub fn method(&self, name: &str) -> Option<Rc<dyn Callable>> {
+        self.methods.get(name).cloned()
+    }
+}
+
+impl From<UserData> for Value {
+    fn from(obj: UserData) -> Self {
+        Value::UserData(Rc::new(obj))
+    }
+}
+
+impl Debug for UserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "< {} >", self.type_name)
+    }
+}
+
+/// a method looked up on an object, which passes the object along when called
+#[derive(Debug)]
+pub struct Method {
+    this: Rc<UserData>,
+    func: Rc<dyn Callable>
+}
+
+impl Method {
+    pub fn new(this: Rc<UserData>, func: Rc<dyn Callable>) -> Self {
+        Self { this, func }
+    }
+}
+
+impl Callable for Method {
+    fn call(&self, vm: &mut VM, mut vals: Vec<Value>) -> Result<Value, RuntimeError> {
+        vals.insert(0, Value::UserData(self.this.clone()));
+        self.func.call(vm, vals)
+    }
+
+    fn display(&self) -> String {
+        format!("< {} method >", self.this.type_name)
+    }
+}
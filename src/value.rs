@@ -1,38 +1,75 @@
-use std::{collections::HashMap, fmt::{Debug, Display}, hash::Hash, rc::Rc};
+use std::{any::Any, cmp::Ordering, collections::HashMap, fmt::{Debug, Display}, hash::{Hash, Hasher}, rc::Rc};
 
-use crate::{executor::RuntimeError, function::Callable, handle::Handle};
+use crate::{executor::RuntimeError, function::Callable, handle::Handle, iterator::{ArrayIter, Iterate, ProtocolIter}, userdata::UserData};
 
 #[derive(Clone)]
 pub enum Value {
+    Int(i64),
     Number(f64),
     String(Handle<String>),
     Bool(bool),
     Array(Handle<Vec<Self>>),
     Map(Handle<HashMap<Self, Self>>),
+    /// binary data, like the contents of a file read with `fs.read_bytes`
+    Bytes(Handle<Vec<u8>>),
     Function(Rc<dyn Callable>),
+    /// a runtime error caught by `try`
+    Error(Rc<RuntimeError>),
+    Iterator(Rc<dyn Iterate>),
+    /// an object from the host, see [`UserData`]
+    UserData(Rc<UserData>),
     Nil
 }
 
 impl Hash for Value {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // ints and whole floats compare equal, so they must hash the same.
+        // that covers -0.0 too, which is the same key as 0. nan isn't equal
+        // to anything, itself included, so like in go a nan key can be set
+        // but never found again, and setting one twice makes two entries.
+        // every nan hashes alike, whatever its payload
+        match self {
+            Self::Int(i) => return i.hash(state),
+            Self::Number(n) => return match float_as_int(*n) {
+                Some(i) => i.hash(state),
+                None if n.is_nan() => f64::NAN.to_bits().hash(state),
+                None => n.to_bits().hash(state)
+            },
+            _ => {}
+        }
         std::mem::discriminant(self).hash(state);
         match self {
-            Self::Number(n) => n.to_string().hash(state),
+            Self::Int(_) | Self::Number(_) => unreachable!(),
             Self::String(s) => s.borrow().hash(state),
             Self::Bool(b) => b.hash(state),
+            // arrays and maps hash by identity. their contents can change
+            // while they're a key, or be borrowed by the map they're going
+            // into, so only the same one is sure to find the entry
             Self::Array(a) => a.hash(state),
             Self::Map(m) => m.hash(state),
-            Self::Function(func) => std::ptr::hash(&*func, state),
+            Self::Bytes(b) => b.borrow().hash(state),
+            Self::Function(func) => std::ptr::hash(func, state),
+            Self::Error(e) => std::ptr::hash(&**e, state),
+            Self::Iterator(i) => std::ptr::hash(&**i, state),
+            Self::UserData(u) => std::ptr::hash(&**u, state),
             Self::Nil => {}
         }
     }
 }
 
+/// the float as an int, if it holds a whole number in range of an i64
+fn float_as_int(n: f64) -> Option<i64> {
+    // `as` saturates, so 2.0 ** 63 would otherwise pass for i64::MAX
+    let in_range = (-9223372036854775808.0..9223372036854775808.0).contains(&n);
+    if in_range && n.fract() == 0.0 { Some(n as i64) } else { None }
+}
+
 impl Value {
     pub fn truthy(&self) -> bool {
         match self {
-            Self::Number(_) | Self::Map(_) | Self::String(_)
-                | Self::Array(_) | Self::Function(_) => true,
+            Self::Int(_) | Self::Number(_) | Self::Map(_) | Self::String(_)
+                | Self::Array(_) | Self::Bytes(_) | Self::Function(_) | Self::Error(_)
+                | Self::Iterator(_) | Self::UserData(_) => true,
             Self::Bool(b) => *b,
             Self::Nil => false
         }
@@ -40,16 +77,64 @@ impl Value {
 
     pub fn num(&self) -> Result<f64, RuntimeError> {
         match self {
+            Self::Int(i) => Ok(*i as f64),
             Self::Number(n) => Ok(*n),
             _ => Err(RuntimeError::NotANumber)
         }
     }
 
+    /// applies an arithmetic operator, staying an int when both operands
+    /// are ints and `int_op` doesn't overflow, and promoting to a float otherwise
+    pub fn arith(&self, other: &Value, int_op: fn(i64, i64) -> Option<i64>, float_op: fn(f64, f64) -> f64)
+        -> Result<Value, RuntimeError> {
+        if let (Self::Int(a), Self::Int(b)) = (self, other) {
+            if let Some(n) = int_op(*a, *b) {
+                return Ok(Value::Int(n))
+            }
+        }
+        Ok(Value::Number(float_op(self.num()?, other.num()?)))
+    }
+
+    /// compares two numbers without losing the precision of large ints
+    pub fn num_cmp(&self, other: &Value) -> Result<Option<Ordering>, RuntimeError> {
+        Ok(match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(b)),
+            _ => self.num()?.partial_cmp(&other.num()?)
+        })
+    }
+
     pub fn num_op(&self, other: &Value, op: fn(f64, f64) -> Result<f64, RuntimeError>)
         -> Result<Value, RuntimeError> {
         let a = self.num()?;
         let b = other.num()?;
-        op(a, b).map(|num| Value::Number(num))
+        op(a, b).map(Value::Number)
+    }
+
+    /// the number truncated to an integer, for bitwise operations
+    pub fn int(&self) -> Result<i64, RuntimeError> {
+        match self {
+            Self::Int(i) => Ok(*i),
+            _ => self.num().map(|n| n as i64)
+        }
+    }
+
+    pub fn int_op(&self, other: &Value, op: fn(i64, i64) -> i64)
+        -> Result<Value, RuntimeError> {
+        let a = self.int()?;
+        let b = other.int()?;
+        Ok(Value::Int(op(a, b)))
+    }
+
+    /// identity comparison, as used by `is`. handles are the same
+    /// when they point at the same object, everything else compares by value
+    pub fn same(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Self::String(l), Self::String(r)) => l.ptr_eq(r),
+            (Self::Array(l), Self::Array(r)) => l.ptr_eq(r),
+            (Self::Map(l), Self::Map(r)) => l.ptr_eq(r),
+            (Self::Bytes(l), Self::Bytes(r)) => l.ptr_eq(r),
+            _ => self == other
+        }
     }
 
     pub fn func(&self) -> Result<Rc<dyn Callable>, RuntimeError> {
@@ -59,6 +144,34 @@ impl Value {
         })
     }
 
+    /// an iterator over the elements of an array, the characters of a
+    /// string, the bytes of a bytes value as ints, the keys of a map,
+    /// or the values of an iterator. a map with a `next` function
+    /// follows the iterator protocol instead of giving its keys
+    pub fn iter(&self) -> Result<Rc<dyn Iterate>, RuntimeError> {
+        Ok(match self {
+            Self::Array(a) => Rc::new(ArrayIter::new(a.clone())),
+            Self::String(s) => {
+                let chars = s.borrow().chars().map(|c| c.to_string().into()).collect();
+                Rc::new(ArrayIter::new(Handle::new(chars)))
+            },
+            Self::Bytes(b) => {
+                let ints = b.borrow().iter().map(|b| Value::Int(*b as i64)).collect();
+                Rc::new(ArrayIter::new(Handle::new(ints)))
+            },
+            Self::Map(m) => {
+                let next = m.borrow().get(&Value::from("next")).cloned();
+                if let Some(Value::Function(next)) = next {
+                    return Ok(Rc::new(ProtocolIter::new(next)))
+                }
+                let keys = m.borrow().keys().cloned().collect();
+                Rc::new(ArrayIter::new(Handle::new(keys)))
+            },
+            Self::Iterator(i) => i.clone(),
+            _ => return Err(RuntimeError::NotIterable)
+        })
+    }
+
     pub fn string(&self) -> Result<Handle<String>, RuntimeError> {
         Ok(match self {
             Self::String(s) => s.clone(),
@@ -66,6 +179,45 @@ impl Value {
         })
     }
 
+    pub fn array(&self) -> Result<Handle<Vec<Self>>, RuntimeError> {
+        Ok(match self {
+            Self::Array(a) => a.clone(),
+            _ => return Err(RuntimeError::NotAnArray)
+        })
+    }
+
+    pub fn bytes(&self) -> Result<Handle<Vec<u8>>, RuntimeError> {
+        Ok(match self {
+            Self::Bytes(b) => b.clone(),
+            _ => return Err(RuntimeError::NotBytes)
+        })
+    }
+
+    /// the contents of a bytes value, or the UTF-8 of a string
+    pub fn data(&self) -> Result<Vec<u8>, RuntimeError> {
+        match self {
+            Self::Bytes(b) => Ok(b.borrow().clone()),
+            Self::String(s) => Ok(s.borrow().as_bytes().to_vec()),
+            _ => Err(RuntimeError::NotBytes)
+        }
+    }
+
+    pub fn error(&self) -> Result<Rc<RuntimeError>, RuntimeError> {
+        Ok(match self {
+            Self::Error(e) => e.clone(),
+            _ => return Err(RuntimeError::NotAnError)
+        })
+    }
+
+    /// what the host object holds, if it's a `type_name` holding a `T`
+    pub fn user_data<T: Any>(&self, type_name: &str) -> Result<&T, RuntimeError> {
+        match self {
+            Self::UserData(obj) if obj.type_name() == type_name => obj.downcast()
+                .ok_or_else(|| RuntimeError::InvalidArgument(format!("{} is not a {}", self, type_name))),
+            _ => Err(RuntimeError::InvalidArgument(format!("{} is not a {}", self, type_name)))
+        }
+    }
+
     pub fn map(&self) -> Result<Handle<HashMap<Self, Self>>, RuntimeError> {
         Ok(match self {
             Self::Map(m) => m.clone(),
@@ -74,54 +226,206 @@ impl Value {
     }
 }
 
-// Todo: revisit this! this is poorly implemented :(
+/// structural equality. see [`Value::same`] for identity
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
+impl Value {
+    /// `comparing` holds the arrays and maps being compared further up, by
+    /// address. meeting a pair again means the two contain themselves in the
+    /// same way, and if nothing else differs they're equal, so it's taken as
+    /// equal rather than going around forever
+    fn equals(&self, other: &Self, comparing: &mut Vec<(usize, usize)>) -> bool {
         match (self, other) {
+            (Self::Int(l), Self::Int(r)) => l == r,
             (Self::Number(l), Self::Number(r)) => l == r,
+            (Self::Int(i), Self::Number(n))
+                | (Self::Number(n), Self::Int(i)) => float_as_int(*n) == Some(*i),
             (Self::String(l), Self::String(r)) => l == r,
             (Self::Bool(l), Self::Bool(r)) => l == r,
-            (Self::Array(l), Self::Array(r)) => l == r,
+            // one that's being changed, like the map a key is going into,
+            // can't be looked at, so it's only equal to itself
+            (Self::Array(l), Self::Array(r)) => nested(l.addr(), r.addr(), comparing, true, |comparing| {
+                let (Ok(l), Ok(r)) = (l.try_borrow(), r.try_borrow()) else {
+                    return false
+                };
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.equals(r, comparing))
+            }),
+            (Self::Map(l), Self::Map(r)) => nested(l.addr(), r.addr(), comparing, true, |comparing| {
+                let (Ok(l), Ok(r)) = (l.try_borrow(), r.try_borrow()) else {
+                    return false
+                };
+                l.len() == r.len() && l.iter().all(|(k, l)| r.get(k).is_some_and(|r| l.equals(r, comparing)))
+            }),
+            (Self::Bytes(l), Self::Bytes(r)) => l == r,
             (Self::Function(f1), Self::Function(f2)) => core::ptr::eq(f1.as_ref(), f2.as_ref()),
+            (Self::Error(e1), Self::Error(e2)) => Rc::ptr_eq(e1, e2),
+            (Self::Iterator(i1), Self::Iterator(i2)) => core::ptr::eq(i1.as_ref(), i2.as_ref()),
+            (Self::UserData(u1), Self::UserData(u2)) => Rc::ptr_eq(u1, u2),
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
 }
 
+/// runs `f` to compare the arrays or maps at `l` and `r`, unless they're the
+/// same one or already being compared further up, which gives `same`
+fn nested<T>(l: usize, r: usize, comparing: &mut Vec<(usize, usize)>, same: T,
+    f: impl FnOnce(&mut Vec<(usize, usize)>) -> T) -> T {
+    if l == r || comparing.contains(&(l, r)) {
+        return same
+    }
+    comparing.push((l, r));
+    let out = f(comparing);
+    comparing.pop();
+    out
+}
+
+impl Value {
+    /// orders any two values, for sorting. values of different types order
+    /// by type: nil, bools, numbers, strings, bytes, arrays, maps, functions,
+    /// errors, iterators, then host objects. within a type, false is before
+    /// true, numbers go by value with nan after all the rest, and strings,
+    /// bytes and arrays go item by item, a prefix first. maps go by size, then
+    /// by their entries in order. functions, errors, iterators and host objects
+    /// have no order of their own, so they go by where they are, which is only
+    /// consistent for as long as they're alive. values that are `==` order equal
+    pub fn compare(&self, other: &Self) -> Ordering {
+        self.compare_in(other, &mut Vec::new())
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Nil => 0,
+            Self::Bool(_) => 1,
+            Self::Int(_) | Self::Number(_) => 2,
+            Self::String(_) => 3,
+            Self::Bytes(_) => 4,
+            Self::Array(_) => 5,
+            Self::Map(_) => 6,
+            Self::Function(_) => 7,
+            Self::Error(_) => 8,
+            Self::Iterator(_) => 9,
+            Self::UserData(_) => 10
+        }
+    }
+
+    fn compare_in(&self, other: &Self, comparing: &mut Vec<(usize, usize)>) -> Ordering {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.cmp(b),
+            // whole floats compare as ints, so large ones agree with `==`
+            (Self::Int(i), Self::Number(n)) => match float_as_int(*n) {
+                Some(n) => i.cmp(&n),
+                None => compare_floats(*i as f64, *n)
+            },
+            (Self::Number(_), Self::Int(_)) => other.compare_in(self, comparing).reverse(),
+            (Self::Number(a), Self::Number(b)) => compare_floats(*a, *b),
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            (Self::String(a), Self::String(b)) => a.borrow().cmp(&*b.borrow()),
+            (Self::Bytes(a), Self::Bytes(b)) => a.borrow().cmp(&*b.borrow()),
+            (Self::Array(a), Self::Array(b)) => nested(a.addr(), b.addr(), comparing, Ordering::Equal, |comparing| {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.iter().zip(b.iter())
+                    .map(|(a, b)| a.compare_in(b, comparing))
+                    .find(|ord| ord.is_ne())
+                    .unwrap_or_else(|| a.len().cmp(&b.len()))
+            }),
+            (Self::Map(a), Self::Map(b)) => nested(a.addr(), b.addr(), comparing, Ordering::Equal, |comparing| {
+                let (a, b) = (a.borrow(), b.borrow());
+                if a.len() != b.len() {
+                    return a.len().cmp(&b.len())
+                }
+                let mut a: Vec<_> = a.iter().collect();
+                let mut b: Vec<_> = b.iter().collect();
+                a.sort_by(|x, y| x.0.compare(y.0));
+                b.sort_by(|x, y| x.0.compare(y.0));
+                a.iter().zip(b.iter())
+                    .map(|((ak, av), (bk, bv))| ak.compare_in(bk, comparing).then_with(|| av.compare_in(bv, comparing)))
+                    .find(|ord| ord.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            }),
+            (Self::Function(a), Self::Function(b)) => (Rc::as_ptr(a) as *const ()).cmp(&(Rc::as_ptr(b) as *const ())),
+            (Self::Error(a), Self::Error(b)) => Rc::as_ptr(a).cmp(&Rc::as_ptr(b)),
+            (Self::Iterator(a), Self::Iterator(b)) => (Rc::as_ptr(a) as *const ()).cmp(&(Rc::as_ptr(b) as *const ())),
+            (Self::UserData(a), Self::UserData(b)) => Rc::as_ptr(a).cmp(&Rc::as_ptr(b)),
+            _ => self.rank().cmp(&other.rank())
+        }
+    }
+}
+
+/// like `f64::total_cmp`, but with -0.0 equal to 0.0 and every nan alike
+fn compare_floats(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (false, false) => a.partial_cmp(&b).expect("neither is nan"),
+        (a, b) => a.cmp(&b)
+    }
+}
+
 // This assertion is false!!
 // but it makes HashMaps easier and the Hash implementation takes care of this
 impl Eq for Value {}
 
 impl Debug for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_debug(f, &mut Vec::new())
+    }
+}
+
+impl Value {
+    /// `printing` holds the arrays and maps being written further up, by
+    /// address. one that contains itself is written as `[...]` or `%{...}`
+    /// where it comes up again, rather than going around forever
+    fn write_debug(&self, f: &mut std::fmt::Formatter<'_>, printing: &mut Vec<usize>) -> std::fmt::Result {
         match self {
+            Value::Int(i) => write!(f, "{}", i)?,
             Value::Number(n) => write!(f, "{}", n)?,
             Value::String(s) => write!(f, "{:?}", s.borrow())?,
             Value::Bool(b) => Display::fmt(b, f)?,
+            Value::Array(a) if printing.contains(&a.addr()) => write!(f, "[...]")?,
             Value::Array(a) => {
+                printing.push(a.addr());
                 write!(f, "[")?;
                 let a = a.borrow();
                 for (i, elem) in a.iter().enumerate() {
-                    write!(f, "{}", elem.to_string())?;
+                    elem.write_display(f, printing)?;
                     if i != a.len() - 1 {
                         write!(f, ", ")?;
                     }
                 }
                 write!(f, "]")?;
+                printing.pop();
             }
+            Value::Map(m) if printing.contains(&m.addr()) => write!(f, "%{{...}}")?,
             Value::Map(m) => {
+                printing.push(m.addr());
                 let m = m.borrow();
-                write!(f, "%{{\n")?;
+                writeln!(f, "%{{")?;
                 for (i, (k, v)) in m.iter().enumerate() {
-                    write!(f, "    {} => {}", k, v)?;
+                    write!(f, "    ")?;
+                    k.write_display(f, printing)?;
+                    write!(f, " => ")?;
+                    v.write_display(f, printing)?;
                     if i != m.len() - 1 {
                         write!(f, ", ")?;
                     }
-                    write!(f, "\n")?;
+                    writeln!(f)?;
                 }
                 write!(f, "}}")?;
+                printing.pop();
             }
+            Value::Bytes(b) => {
+                write!(f, "< bytes")?;
+                for byte in b.borrow().iter() {
+                    write!(f, " {:02x}", byte)?;
+                }
+                write!(f, " >")?;
+            },
             Value::Function(func) => write!(f, "{}", func.display())?,
+            Value::Error(e) => write!(f, "< error {}: {} >", e.inner().kind(), e.inner().message())?,
+            Value::Iterator(i) => write!(f, "{}", i.display())?,
+            Value::UserData(u) => write!(f, "{:?}", u)?,
             Value::Nil => write!(f, "nil")?
         };
         Ok(())
@@ -130,13 +434,18 @@ impl Debug for Value {
 
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_display(f, &mut Vec::new())
+    }
+}
+
+impl Value {
+    fn write_display(&self, f: &mut std::fmt::Formatter<'_>, printing: &mut Vec<usize>) -> std::fmt::Result {
         match self {
             // strings can be printed directly
-            Value::String(s) => write!(f, "{}", s.borrow())?,
+            Value::String(s) => write!(f, "{}", s.borrow()),
             // fall back to debug implementation for everything else
-            _ => Debug::fmt(&self, f)?
-        };
-        Ok(())
+            _ => self.write_debug(f, printing)
+        }
     }
 }
 